@@ -5,11 +5,14 @@ from PIL import Image
 import json
 import torchvision.models as models
 import argparse
+from features import penultimate_features
+from worker_protocol import indexed_result, serve
 
 # 全局变量存储模型
 model = None
 transform = None
-class_names = ['airplane', 'automobile', 'bird', 'cat', 'deer', 'dog', 'frog', 'horse', 'ship', 'truck']
+# 类别数量，类别名称见 resources/labels/cifar10.json
+NUM_CLASSES = 10
 
 def load_model(model_path):
     global model
@@ -22,7 +25,7 @@ def load_model(model_path):
             torch.nn.Linear(num_ftrs, 256),
             torch.nn.ReLU(),
             torch.nn.Dropout(0.2),
-            torch.nn.Linear(256, 10)  # CIFAR-10分类
+            torch.nn.Linear(256, NUM_CLASSES)  # CIFAR-10分类
         )
         
         # 加载预训练权重
@@ -44,51 +47,43 @@ def preprocess_image(image_path):
     image = Image.open(image_path).convert('RGB')
     return transform(image).unsqueeze(0)  # 添加batch维度
 
+def format_result(probabilities):
+    """将单张图片的概率分布整理为结果，按概率从高到低返回全部类别索引"""
+    probs, idxs = torch.sort(probabilities, descending=True)
+    return indexed_result(idxs.tolist(), probs.tolist())
+
 def predict(image_tensor):
     with torch.no_grad():
         outputs = model(image_tensor)
         probabilities = torch.nn.functional.softmax(outputs, dim=1)[0]
-        
-        # 获取最高概率的类别
-        _, predicted_idx = torch.max(probabilities, 0)
-        predicted_class = class_names[predicted_idx.item()]
-        confidence = probabilities[predicted_idx].item()
-        
-        # 构建所有类别的概率字典
-        class_probs = {class_names[i]: prob.item() for i, prob in enumerate(probabilities)}
-        
+    return format_result(probabilities)
+
+def predict_batch(image_tensors):
+    """一次前向计算多张图片，返回与输入顺序一致的结果列表"""
+    with torch.no_grad():
+        outputs = model(torch.cat(image_tensors))
+        probabilities = torch.nn.functional.softmax(outputs, dim=1)
+    return [format_result(row) for row in probabilities]
+
+def embed(image_tensor):
+    """推理并返回分类层的输入作为特征向量，返回 (结果, 特征向量)"""
+    outputs, features = penultimate_features(model, model.fc[-1], image_tensor)
+    probabilities = torch.nn.functional.softmax(outputs, dim=1)[0]
+    return format_result(probabilities), features
+
+def describe():
+    """返回模型信息，用于 info 命令"""
     return {
-        "prediction": predicted_class,
-        "confidence": confidence,
-        "class_probabilities": class_probs
+        "name": "CIFAR-10图像识别",
+        "model_type": "ResNet34",
+        "num_classes": NUM_CLASSES,
+        "device": "cpu",
     }
 
 def run_server(model_path):
-    """运行服务模式，持续接收命令"""
-    print("Python推理服务已启动", file=sys.stderr)
-    load_model(model_path)
-    
-    try:
-        while True:
-            line = sys.stdin.readline().strip()
-            if not line or line == "exit":
-                break
-                
-            if line.startswith("process_image:"):
-                image_path = line[len("process_image:"):]
-                try:
-                    image_tensor = preprocess_image(image_path)
-                    result = predict(image_tensor)
-                    print(json.dumps(result))
-                    sys.stdout.flush()  # 确保输出被立即发送
-                except Exception as e:
-                    error_result = {"error": str(e)}
-                    print(json.dumps(error_result))
-                    sys.stdout.flush()
-    except KeyboardInterrupt:
-        print("服务终止", file=sys.stderr)
-    
-    print("Python推理服务已关闭", file=sys.stderr)
+    """运行服务模式，通过 JSON 行协议持续接收命令"""
+    serve(model_path, "CIFAR-10图像识别", load_model, preprocess_image, predict, describe,
+          predict_batch=predict_batch, embed=embed)
 
 def main():
     parser = argparse.ArgumentParser(description="图像识别推理服务")
@@ -0,0 +1,25 @@
+"""提取模型倒数第二层的特征向量，供推理脚本实现 embed 命令
+
+特征向量即分类层的输入，内容相近的图片在该空间中方向相近，用于相似图片检索。
+"""
+import torch
+
+
+def penultimate_features(model, head, image_tensor):
+    """对一张图片（带 batch 维度）推理，同时取出分类层 head 的输入
+
+    head 一般为最后一个全连接层（如 ResNet 的 fc、MobileNet 的 classifier[-1]）。
+    返回 (模型输出, 特征)，特征为一维张量。
+    """
+    captured = {}
+
+    def forward_hook(module, inputs, output):
+        captured["features"] = inputs[0]
+
+    handle = head.register_forward_hook(forward_hook)
+    try:
+        with torch.no_grad():
+            outputs = model(image_tensor)
+    finally:
+        handle.remove()
+    return outputs, captured["features"][0].flatten()
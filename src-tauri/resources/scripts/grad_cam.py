@@ -0,0 +1,36 @@
+"""Grad-CAM 类激活图，供推理脚本实现 explain 命令
+
+用目标类别得分对卷积层输出的梯度加权该层的特征图，得到图片各区域对预测结果的贡献。
+"""
+import torch
+
+
+def grad_cam(model, target_layer, image_tensor, class_index=None):
+    """对一张图片（带 batch 维度）计算 Grad-CAM
+
+    target_layer 一般取最后一个卷积层（如 ResNet 的 layer4）。class_index 为空时使用得分最高的类别。
+    返回 (模型输出, cam)，cam 为 target_layer 输出尺寸的二维张量，已归一化到 0~1。
+    """
+    captured = {}
+
+    def forward_hook(module, inputs, output):
+        captured["activations"] = output
+        output.register_hook(lambda grad: captured.__setitem__("gradients", grad))
+
+    handle = target_layer.register_forward_hook(forward_hook)
+    try:
+        with torch.enable_grad():
+            outputs = model(image_tensor)
+            if class_index is None:
+                class_index = int(outputs[0].argmax())
+            model.zero_grad()
+            outputs[0, class_index].backward()
+    finally:
+        handle.remove()
+
+    weights = captured["gradients"].mean(dim=(2, 3), keepdim=True)
+    cam = torch.relu((weights * captured["activations"]).sum(dim=1))[0].detach()
+    cam = cam - cam.min()
+    if cam.max() > 0:
+        cam = cam / cam.max()
+    return outputs.detach(), cam
@@ -5,32 +5,20 @@ from torchvision import models
 import torch.nn as nn
 import json
 import sys
-import io
 import argparse
+from worker_protocol import activation_map, indexed_result, serve
+from features import penultimate_features
+from grad_cam import grad_cam
 
 # 全局变量存储模型和变换
 model = None
 transform = None
-class_names = None
 
 # 强制设置标准输出为UTF-8编码
-sys.stdout = io.TextIOWrapper(sys.stdout.buffer, encoding='utf-8', errors='replace')
+sys.stdout.reconfigure(encoding='utf-8', errors='replace')
 
-# 预定义类别列表 - 蘑菇名称
-CLASS_NAMES = ["美味蘑菇", "黄孢环柄菇", "美洲红褶伞", "奥古斯塔鹅膏菌", "褐变鹅膏菌", "鳞盖鹅膏菌", "黄鳞鹅膏菌",
-                "毒蝇伞", "桃红鹅膏菌", "毒鹅膏菌", "白鹅膏菌", "蜜环菌", "无环柄菇", "珊瑚齿菌", "蛋黄菇",
-                "苍白牛肝菌", "春王牛肝菌", "加州鸡油菌", "朱红鸡油菌", "鳞柄多孔菌", "褐环褶菇", "绿褶菇",
-                "紫丁香蘑菇", "云母鬼伞", "兔粪鬼伞", "毛头鬼伞", "光柄鸟巢菌", "隐孔包", "迷宫革菌",
-                "流产粉褶菌", "金针菇", "芒斯拟层孔菌", "盔孢伞", "树舌灵芝", "柯蒂斯灵芝", "俄勒冈灵芝",
-                "铁杉灵芝", "鹦鹉小菇", "栅孔菌", "灰树花", "锈鳞伞", "珊瑚猴头菌", "猴头菇", "橙黄小脆柄菇",
-                "黄孢环柄菇", "砖红丝膜菌", "虾夷菇", "树脂革菌", "紫褐喇叭菌", "泪菇", "靛蓝乳菇", "硫磺菌",
-                "松杉卧孔菌", "红菇", "红褶伞", "白环柄菇", "树皮网", "梨包", "梨形马勃", "血红菇", "橙盖小菇",
-                "发光脐菇", "西瓜红柄小香菇", "草地裸伞", "环纹斑褶菇", "凤头斑褶菇", "苦味小香菇", "染匠蘑菇", 
-                "胶革菌", "桔黄褶菌", "平菇", "肺形侧耳", "鹿纹蘑菇", "脆柄菇", "银耳", "艾伦裸盖菇", "阿兹特克裸盖菇", 
-                "天蓝裸盖菇", "青变裸盖菇", "古巴裸盖菇", "蓝帽裸盖菇", "墨西哥裸盖菇", "新哈拉帕裸盖菇", "卵囊裸盖菇", 
-                "薄皮裸盖菇", "萨波特克裸盖菇", "饰柄网孢牛肝菌", "晚生肉伞", "裂褶菌", "牡蛎革菌", "变绿环锈伞", 
-                "皱环球盖菇", "美洲乳牛肝菌", "粘盖牛肝菌", "斯普拉格乳牛肝菌", "黑绒革耳", "桦木蹄", "凸纹革菌", 
-                "云芝", "双色革菌", "马瑞利亚松口蘑", "红鳞伞", "糠秕小菇", "苦牛肝菌", "红褐牛肝菌", "粪生伞"]
+# 类别数量，类别名称见 resources/labels/mushroom.json
+NUM_CLASSES = 106
 
 def safe_json_print(data):
     """安全地将对象打印为JSON，处理编码问题"""
@@ -52,15 +40,12 @@ def safe_json_print(data):
         sys.stdout.flush()
 
 def load_model(model_path):
-    """加载预训练模型和类别映射"""
-    global model, transform, class_names
+    """加载预训练模型"""
+    global model, transform
     
     if model is None:
         print(f"加载模型: {model_path}", file=sys.stderr)
         try:
-            # 使用预定义的类别列表
-            class_names = CLASS_NAMES
-            
             # 加载预训练模型
             checkpoint = torch.load(model_path, map_location="cpu")
             
@@ -71,7 +56,7 @@ def load_model(model_path):
                 nn.Linear(model.fc.in_features, 512),
                 nn.ReLU(),
                 nn.Dropout(0.3),
-                nn.Linear(512, len(class_names))
+                nn.Linear(512, NUM_CLASSES)
             )
             
             # 加载模型权重
@@ -103,91 +88,69 @@ def preprocess_image(image_path):
         print(f"图像预处理失败: {str(e)}", file=sys.stderr)
         raise e
 
+def format_result(probabilities, top_n=10):
+    """将单张图片的概率分布整理为结果，只返回类别索引，名称由 Rust 端按类别文件映射"""
+    # 获取top_n最高概率的类别
+    top_n = min(top_n, probabilities.size(0))  # 确保不超过类别总数
+    top_probs, top_idxs = torch.topk(probabilities, top_n)
+    return indexed_result(top_idxs.tolist(), top_probs.tolist())
+
 def predict(image_tensor, top_n=10):
     """使用模型执行推理"""
     try:
         with torch.no_grad():
             outputs = model(image_tensor)
             probabilities = torch.nn.functional.softmax(outputs, dim=1)[0]
-            
-            # 获取top_n最高概率的类别
-            top_n = min(top_n, len(class_names))  # 确保不超过类别总数
-            top_probs, top_idxs = torch.topk(probabilities, top_n)
-            
-            # 获取最高概率的类别作为主预测结果
-            predicted_class = class_names[top_idxs[0].item()]
-            confidence = top_probs[0].item()
-            
-            # 构建top_n类别的概率字典
-            class_probabilities = {
-                class_names[top_idxs[i].item()]: float(top_probs[i].item())
-                for i in range(top_n)
-            }
-            
-        return {
-            "prediction": predicted_class,
-            "confidence": float(confidence),
-            "class_probabilities": class_probabilities
-        }
+        return format_result(probabilities, top_n)
     except Exception as e:
         print(f"预测过程出错: {str(e)}", file=sys.stderr)
-        # 返回格式完整的错误结果
-        return {
-            "prediction": "错误",
-            "confidence": 0.0,
-            "class_probabilities": {},
-            "error": str(e)
-        }
+        raise e
 
-def run_server(model_path):
-    """运行服务模式，持续接收命令"""
-    print("蘑菇图像识别服务已启动", file=sys.stderr)
+def predict_batch(image_tensors, top_n=10):
+    """一次前向计算多张图片，返回与输入顺序一致的结果列表"""
     try:
-        load_model(model_path)
+        with torch.no_grad():
+            outputs = model(torch.cat(image_tensors))
+            probabilities = torch.nn.functional.softmax(outputs, dim=1)
+        return [format_result(row, top_n) for row in probabilities]
     except Exception as e:
-        print(f"服务启动失败: {str(e)}", file=sys.stderr)
-        # 即使在启动失败的情况下也返回格式正确的错误
-        safe_json_print({
-            "prediction": "服务启动失败",
-            "confidence": 0.0, 
-            "class_probabilities": {},
-            "error": str(e)
-        })
-        return
-    
+        print(f"批量预测过程出错: {str(e)}", file=sys.stderr)
+        raise e
+
+def explain(image_tensor, top_n=10):
+    """对得分最高的类别计算 Grad-CAM，返回 (结果, 类激活图)"""
     try:
-        while True:
-            line = sys.stdin.readline().strip()
-            if not line or line == "exit":
-                break
-                
-            if line.startswith("process_image:"):
-                image_path = line[len("process_image:"):]
-                print(f"接收到图像处理请求: {image_path}", file=sys.stderr)
-                
-                try:
-                    image_tensor = preprocess_image(image_path)
-                    result = predict(image_tensor)
-                    # 使用安全的JSON打印
-                    safe_json_print(result)
-                except Exception as e:
-                    error_msg = str(e)
-                    print(f"处理图像时出错: {error_msg}", file=sys.stderr)
-                    
-                    # 确保返回完整的错误结构
-                    error_result = {
-                        "prediction": "处理失败",
-                        "confidence": 0.0,
-                        "class_probabilities": {},
-                        "error": error_msg
-                    }
-                    safe_json_print(error_result)
-    except KeyboardInterrupt:
-        print("服务终止", file=sys.stderr)
+        outputs, cam = grad_cam(model, model.layer4, image_tensor)
+        probabilities = torch.nn.functional.softmax(outputs, dim=1)[0]
+        return format_result(probabilities, top_n), activation_map(cam)
     except Exception as e:
-        print(f"服务异常: {str(e)}", file=sys.stderr)
-    
-    print("蘑菇图像识别服务已关闭", file=sys.stderr)
+        print(f"生成热力图出错: {str(e)}", file=sys.stderr)
+        raise e
+
+def embed(image_tensor, top_n=10):
+    """推理并返回分类层的输入作为特征向量，返回 (结果, 特征向量)"""
+    try:
+        outputs, features = penultimate_features(model, model.fc[-1], image_tensor)
+        probabilities = torch.nn.functional.softmax(outputs, dim=1)[0]
+        return format_result(probabilities, top_n), features
+    except Exception as e:
+        print(f"提取特征向量出错: {str(e)}", file=sys.stderr)
+        raise e
+
+def describe():
+    """返回模型信息，用于 info 命令"""
+    return {
+        "name": "蘑菇图像识别",
+        "model_type": "ResNet50",
+        "num_classes": NUM_CLASSES,
+        "device": "cpu",
+    }
+
+def run_server(model_path):
+    """运行服务模式，通过 JSON 行协议持续接收命令"""
+    serve(model_path, "蘑菇图像识别", load_model, preprocess_image, predict, describe,
+          predict_batch=predict_batch, explain=explain,
+          embed=embed)
 
 def main():
     parser = argparse.ArgumentParser(description="蘑菇图像识别推理服务")
@@ -0,0 +1,364 @@
+"""Rust 端与推理脚本之间的 JSON 行协议
+
+每行一个 JSON 对象，请求格式:
+    {"v": 1, "id": "req-1", "cmd": "infer", "image_path": "..."}
+    {"v": 1, "id": "req-2", "cmd": "ping"}
+    {"v": 1, "id": "req-3", "cmd": "info"}
+    {"v": 1, "id": "req-4", "cmd": "shutdown"}
+    {"v": 1, "id": "req-5", "cmd": "infer_batch", "image_paths": ["...", "..."]}
+    {"v": 1, "id": "req-6", "cmd": "explain", "image_path": "..."}
+    {"v": 1, "id": "req-7", "cmd": "embed", "image_path": "..."}
+
+启动时先加载模型，成功后发送就绪消息，失败则发送错误并退出:
+    {"v": 1, "id": null, "type": "ready", "info": {...}}
+    {"v": 1, "id": null, "type": "error", "code": "model_load_failed", "message": "..."}
+
+响应格式（id 与请求一致）:
+    {"v": 1, "id": "req-1", "type": "result", "result": {...}}
+    {"v": 1, "id": "req-2", "type": "pong"}
+    {"v": 1, "id": "req-3", "type": "info", "info": {...}}
+    {"v": 1, "id": "req-4", "type": "bye"}
+    {"v": 1, "id": "req-1", "type": "error", "code": "image_error", "message": "..."}
+    {"v": 1, "id": "req-5", "type": "batch_result", "results": [
+        {"type": "result", "result": {...}},
+        {"type": "error", "code": "image_error", "message": "..."}]}
+    {"v": 1, "id": "req-6", "type": "explanation", "result": {...},
+     "activation_map": {"width": 7, "height": 7, "values": [...]}}
+    {"v": 1, "id": "req-7", "type": "embedding", "result": {...}, "embedding": [0.12, ...]}
+
+infer_batch 仅在脚本提供 predict_batch 时可用，此时 info 中的 capabilities 包含 "infer_batch"。
+explain 仅在脚本提供 explain 时可用，此时 capabilities 包含 "explain"；activation_map 为预测类别的
+类激活图（见 activation_map 和 grad_cam.py），按行排列、取值 0~1，由 Rust 端叠加到原图上保存。
+embed 仅在脚本提供 embed 时可用，此时 capabilities 包含 "embed"；embedding 为模型倒数第二层的输出
+（见 features.py），Rust 端保存后用于相似图片检索。
+
+推理结果只包含按概率从高到低排列的类别索引，类别名称由 Rust 端按模型的类别文件
+映射为用户选择的语言（见 indexed_result）:
+    {"class_indices": [3, 0, 7], "probabilities": [0.91, 0.05, 0.02]}
+旧版脚本直接返回 {"prediction": ..., "confidence": ..., "class_probabilities": {...}} 也仍然支持。
+多标签模型同样返回 indexed_result，概率为各类别独立的 sigmoid 得分（不做 softmax），
+应包含所有类别，阳性类别由 Rust 端按类别文件中的阈值判断。
+
+检测模型返回目标列表，坐标为相对图片宽高的比例（见 detection_result）:
+    {"detections": [{"class_index": 2, "score": 0.87, "box": [0.12, 0.30, 0.55, 0.92]}]}
+没有类别文件的模型可以用 "label" 直接给出类别名称代替 "class_index"。
+
+协议消息独占 stdout，脚本中其它 print 输出会被重定向到 stderr。
+旧版 "process_image:<路径>" 命令仍然可用，返回不带信封的结果对象。
+"""
+import json
+import sys
+
+PROTOCOL_VERSION = 1
+
+# 错误码 - 与 Rust 端 WorkerErrorCode 保持一致
+INVALID_REQUEST = "invalid_request"
+UNSUPPORTED_VERSION = "unsupported_version"
+UNKNOWN_COMMAND = "unknown_command"
+IMAGE_ERROR = "image_error"
+INFERENCE_ERROR = "inference_error"
+MODEL_LOAD_FAILED = "model_load_failed"
+INTERNAL_ERROR = "internal_error"
+
+# 协议输出通道，只有协议消息会写入真正的 stdout
+_protocol_out = None
+
+
+def claim_stdout():
+    """接管 stdout：协议消息写入原始 stdout，其余 print 输出转到 stderr"""
+    global _protocol_out
+    if _protocol_out is None:
+        _protocol_out = sys.stdout
+        _protocol_out.reconfigure(encoding="utf-8", errors="replace")
+        sys.stdout = sys.stderr
+    return _protocol_out
+
+
+def write_line(data):
+    """向协议通道写入一行 JSON"""
+    out = claim_stdout()
+    out.write(json.dumps(data, ensure_ascii=True) + "\n")
+    out.flush()
+
+
+def send(request_id, msg_type, **fields):
+    """发送一条协议消息"""
+    message = {"v": PROTOCOL_VERSION, "id": request_id, "type": msg_type}
+    message.update(fields)
+    try:
+        write_line(message)
+    except (TypeError, ValueError) as e:
+        print(f"JSON序列化失败: {e}", file=sys.stderr)
+        write_line({
+            "v": PROTOCOL_VERSION,
+            "id": request_id,
+            "type": "error",
+            "code": INTERNAL_ERROR,
+            "message": f"JSON序列化失败: {e}",
+        })
+
+
+def send_error(request_id, code, message):
+    """发送类型化的错误消息"""
+    send(request_id, "error", code=code, message=message)
+
+
+# 脚本可选支持的命令，通过 info 中的 capabilities 声明
+CAPABILITY_INFER_BATCH = "infer_batch"
+CAPABILITY_EXPLAIN = "explain"
+CAPABILITY_EMBED = "embed"
+
+
+def indexed_result(class_indices, probabilities):
+    """构建推理结果：按概率从高到低排列的类别索引及其概率"""
+    return {
+        "class_indices": [int(i) for i in class_indices],
+        "probabilities": [float(p) for p in probabilities],
+    }
+
+
+def detection_result(class_indices, scores, boxes, image_size=None):
+    """构建检测结果：每个目标的类别索引、得分和 [x_min, y_min, x_max, y_max]
+
+    给出 image_size=(宽, 高) 时 boxes 为像素坐标，按图片尺寸换算为 0~1 的比例。
+    """
+    width, height = image_size or (1, 1)
+    detections = []
+    for class_index, score, box in zip(class_indices, scores, boxes):
+        x_min, y_min, x_max, y_max = (float(v) for v in box)
+        detections.append({
+            "class_index": int(class_index),
+            "score": float(score),
+            "box": [
+                min(max(x_min / width, 0.0), 1.0),
+                min(max(y_min / height, 0.0), 1.0),
+                min(max(x_max / width, 0.0), 1.0),
+                min(max(y_max / height, 0.0), 1.0),
+            ],
+        })
+    return {"detections": detections}
+
+
+def activation_map(cam):
+    """构建类激活图：cam 为二维数组（列表、numpy 数组或张量），取值应已归一化到 0~1"""
+    if hasattr(cam, "tolist"):
+        cam = cam.tolist()
+    rows = [[float(v) for v in row] for row in cam]
+    return {
+        "width": len(rows[0]) if rows else 0,
+        "height": len(rows),
+        "values": [v for row in rows for v in row],
+    }
+
+
+def embedding_vector(features):
+    """把特征（列表、numpy 数组或张量，任意形状）展平为浮点数列表"""
+    if hasattr(features, "tolist"):
+        features = features.tolist()
+    if not isinstance(features, (list, tuple)):
+        return [float(features)]
+    return [v for item in features for v in embedding_vector(item)]
+
+
+def run_inference(image_path, preprocess, predict):
+    """执行一次推理，按阶段抛出带错误码的异常"""
+    try:
+        image_tensor = preprocess(image_path)
+    except Exception as e:
+        raise WorkerError(IMAGE_ERROR, f"图像预处理失败: {e}")
+    try:
+        return predict(image_tensor)
+    except Exception as e:
+        raise WorkerError(INFERENCE_ERROR, f"预测过程出错: {e}")
+
+
+class WorkerError(Exception):
+    """带协议错误码的异常"""
+
+    def __init__(self, code, message):
+        super().__init__(message)
+        self.code = code
+        self.message = message
+
+
+def run_batch(image_paths, preprocess, predict_batch):
+    """批量推理：逐张预处理后一次前向计算，返回与输入顺序一致的结果列表"""
+    entries = [None] * len(image_paths)
+    indexes = []
+    tensors = []
+    for i, image_path in enumerate(image_paths):
+        try:
+            tensors.append(preprocess(image_path))
+            indexes.append(i)
+        except Exception as e:
+            entries[i] = {"type": "error", "code": IMAGE_ERROR,
+                          "message": f"图像预处理失败: {e}"}
+
+    if tensors:
+        try:
+            results = predict_batch(tensors)
+            for i, result in zip(indexes, results):
+                entries[i] = {"type": "result", "result": result}
+        except Exception as e:
+            for i in indexes:
+                entries[i] = {"type": "error", "code": INFERENCE_ERROR,
+                              "message": f"预测过程出错: {e}"}
+    return entries
+
+
+def handle_legacy(line, preprocess, predict):
+    """兼容旧版 process_image:<路径> 命令"""
+    image_path = line[len("process_image:"):]
+    print(f"接收到旧版图像处理请求: {image_path}", file=sys.stderr)
+    try:
+        result = run_inference(image_path, preprocess, predict)
+    except WorkerError as e:
+        result = {
+            "prediction": "处理失败",
+            "confidence": 0.0,
+            "class_probabilities": {},
+            "error": e.message,
+        }
+    write_line(result)
+
+
+def model_info(describe, predict_batch=None, explain=None, embed=None):
+    """构建 info/ready 消息中的模型信息"""
+    info = dict(describe())
+    info["protocol_version"] = PROTOCOL_VERSION
+    info["capabilities"] = []
+    if predict_batch:
+        info["capabilities"].append(CAPABILITY_INFER_BATCH)
+    if explain:
+        info["capabilities"].append(CAPABILITY_EXPLAIN)
+    if embed:
+        info["capabilities"].append(CAPABILITY_EMBED)
+    return info
+
+
+def handle_request(request, preprocess, predict, describe, predict_batch=None, explain=None,
+                   embed=None):
+    """处理一条协议请求，返回 False 表示应当退出服务循环"""
+    request_id = request.get("id")
+    if request.get("v") != PROTOCOL_VERSION:
+        send_error(request_id, UNSUPPORTED_VERSION,
+                   f"不支持的协议版本: {request.get('v')}，当前版本: {PROTOCOL_VERSION}")
+        return True
+
+    cmd = request.get("cmd")
+    if cmd == "infer":
+        image_path = request.get("image_path")
+        if not isinstance(image_path, str) or not image_path:
+            send_error(request_id, INVALID_REQUEST, "缺少 image_path 参数")
+            return True
+        print(f"接收到图像处理请求[{request_id}]: {image_path}", file=sys.stderr)
+        try:
+            result = run_inference(image_path, preprocess, predict)
+        except WorkerError as e:
+            print(e.message, file=sys.stderr)
+            send_error(request_id, e.code, e.message)
+            return True
+        send(request_id, "result", result=result)
+    elif cmd == "infer_batch" and predict_batch:
+        image_paths = request.get("image_paths")
+        if not isinstance(image_paths, list) or not all(isinstance(p, str) for p in image_paths):
+            send_error(request_id, INVALID_REQUEST, "image_paths 必须是路径列表")
+            return True
+        print(f"接收到批量处理请求[{request_id}]: {len(image_paths)} 张图片", file=sys.stderr)
+        send(request_id, "batch_result", results=run_batch(image_paths, preprocess, predict_batch))
+    elif cmd == "explain" and explain:
+        image_path = request.get("image_path")
+        if not isinstance(image_path, str) or not image_path:
+            send_error(request_id, INVALID_REQUEST, "缺少 image_path 参数")
+            return True
+        print(f"接收到热力图请求[{request_id}]: {image_path}", file=sys.stderr)
+        try:
+            result, cam = run_inference(image_path, preprocess, explain)
+        except WorkerError as e:
+            print(e.message, file=sys.stderr)
+            send_error(request_id, e.code, e.message)
+            return True
+        send(request_id, "explanation", result=result, activation_map=cam)
+    elif cmd == "embed" and embed:
+        image_path = request.get("image_path")
+        if not isinstance(image_path, str) or not image_path:
+            send_error(request_id, INVALID_REQUEST, "缺少 image_path 参数")
+            return True
+        print(f"接收到特征提取请求[{request_id}]: {image_path}", file=sys.stderr)
+        try:
+            result, vector = run_inference(image_path, preprocess, embed)
+        except WorkerError as e:
+            print(e.message, file=sys.stderr)
+            send_error(request_id, e.code, e.message)
+            return True
+        send(request_id, "embedding", result=result, embedding=embedding_vector(vector))
+    elif cmd == "ping":
+        send(request_id, "pong")
+    elif cmd == "info":
+        send(request_id, "info", info=model_info(describe, predict_batch, explain, embed))
+    elif cmd == "shutdown":
+        send(request_id, "bye")
+        return False
+    else:
+        send_error(request_id, UNKNOWN_COMMAND, f"未知命令: {cmd}")
+    return True
+
+
+def serve(model_path, name, load_model, preprocess, predict, describe, predict_batch=None,
+          explain=None, embed=None):
+    """运行服务模式，持续接收协议命令
+
+    load_model(model_path) 加载模型；preprocess(image_path) 返回模型输入；
+    predict(tensor) 返回结果字典；describe() 返回模型信息字典；
+    可选的 predict_batch(tensors) 返回结果字典列表，提供时支持 infer_batch 命令；
+    可选的 explain(tensor) 返回 (结果字典, activation_map(...))，提供时支持 explain 命令；
+    可选的 embed(tensor) 返回 (结果字典, 特征向量)，提供时支持 embed 命令。
+    """
+    claim_stdout()
+    print(f"{name}服务已启动", file=sys.stderr)
+    try:
+        load_model(model_path)
+    except Exception as e:
+        print(f"服务启动失败: {str(e)}", file=sys.stderr)
+        send_error(None, MODEL_LOAD_FAILED, str(e))
+        return
+
+    # 通知宿主模型已就绪，此后才会处理请求
+    send(None, "ready", info=model_info(describe, predict_batch, explain, embed))
+
+    try:
+        while True:
+            line = sys.stdin.readline()
+            # 读到EOF说明宿主进程已关闭管道
+            if not line:
+                break
+            line = line.strip()
+            if not line:
+                continue
+            if line == "exit":
+                break
+
+            if line.startswith("process_image:"):
+                handle_legacy(line, preprocess, predict)
+                continue
+
+            try:
+                request = json.loads(line)
+            except ValueError as e:
+                send_error(None, INVALID_REQUEST, f"无法解析请求: {e}")
+                continue
+            if not isinstance(request, dict):
+                send_error(None, INVALID_REQUEST, "请求必须是JSON对象")
+                continue
+
+            try:
+                if not handle_request(request, preprocess, predict, describe, predict_batch,
+                                      explain, embed):
+                    break
+            except Exception as e:
+                print(f"服务异常: {str(e)}", file=sys.stderr)
+                send_error(request.get("id"), INTERNAL_ERROR, str(e))
+    except KeyboardInterrupt:
+        print("服务终止", file=sys.stderr)
+
+    print(f"{name}服务已关闭", file=sys.stderr)
@@ -0,0 +1,514 @@
+//! 远程推理服务的参考实现，用常驻Python进程为多个模型提供HTTP识别接口
+//!
+//! 需要启用 `server` 特性编译（`cargo build --features server --bin inference_server`），用法：
+//!
+//! ```text
+//! inference_server --models models.json [--addr 127.0.0.1:8765] [--token TOKEN]
+//!                  [--python python] [--cache-dir DIR] [--cache-max-mb 1024]
+//!                  [--cache-max-age-hours 24] [--locale zh-CN]
+//! ```
+//!
+//! `models.json` 为模型列表，模型ID需与客户端一致；脚本返回类别索引时需要 `labels_path`：
+//!
+//! ```json
+//! [{ "id": "MEDICINE_MODEL_ID", "script_path": "scripts/medicine_val.py",
+//!    "model_path": "models/medicine_model.pth", "protocol": "json",
+//!    "labels_path": "labels/medicine.json" }]
+//! ```
+//!
+//! 多标签和检测模型需要填写 `task`（`multi_label`、`detection`），与模型清单一致。
+//!
+//! 结果中的类别名称使用 `--locale` 指定的语言，客户端会按类别ID换成本地选择的语言。
+
+use axum::body::Bytes;
+use axum::extract::{DefaultBodyLimit, Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use vision_match::models::inference_result::{
+    ModelResult, ModelTask, RemoteErrorBody, RemoteHashRequest, WorkerErrorCode, WorkerInfo,
+    WorkerProtocol, REMOTE_SHA256_HEADER,
+};
+use vision_match::services::labels::{self, LabelSet};
+use vision_match::services::python::{PythonService, ServiceError};
+
+/// 上传图片的大小上限
+const MAX_IMAGE_BYTES: usize = 64 * 1024 * 1024;
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(120);
+const INFERENCE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 清理图片缓存的间隔
+const CACHE_PRUNE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// 缓存图片临时文件名的序号，与进程ID一起保证并发上传的临时文件不重名
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// 模型列表文件中的一项
+#[derive(Debug, Deserialize)]
+struct ServerModel {
+    id: String,
+    script_path: String,
+    model_path: String,
+    #[serde(default)]
+    protocol: WorkerProtocol,
+    #[serde(default)]
+    labels_path: Option<String>,
+    /// 任务类型，未填写时为分类
+    #[serde(default)]
+    task: ModelTask,
+}
+
+struct ServerOptions {
+    addr: String,
+    models_path: String,
+    token: Option<String>,
+    python: String,
+    cache_dir: PathBuf,
+    /// 缓存图片的总大小上限
+    cache_max_bytes: u64,
+    /// 超过该时间未使用的缓存图片被删除
+    cache_max_age: Duration,
+}
+
+struct ServerState {
+    services: HashMap<String, Arc<PythonService>>,
+    token: Option<String>,
+    cache_dir: PathBuf,
+}
+
+/// 接口错误，响应体与客户端解析的 `RemoteErrorBody` 一致
+struct ApiError {
+    status: StatusCode,
+    code: WorkerErrorCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, code: WorkerErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<ServiceError> for ApiError {
+    fn from(error: ServiceError) -> Self {
+        let message = error.to_string();
+        match error {
+            // 推理脚本拒绝了图片，原样返回脚本的错误码和信息
+            ServiceError::Worker { code, message } => {
+                Self::new(StatusCode::UNPROCESSABLE_ENTITY, code, message)
+            }
+            ServiceError::ModelLoading(_) => Self::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                WorkerErrorCode::InternalError,
+                message,
+            ),
+            ServiceError::ModelLoadFailed(_) | ServiceError::StartupTimeout(_) => Self::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                WorkerErrorCode::ModelLoadFailed,
+                message,
+            ),
+            ServiceError::Timeout(_) => Self::new(
+                StatusCode::GATEWAY_TIMEOUT,
+                WorkerErrorCode::InternalError,
+                message,
+            ),
+            _ => Self::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                WorkerErrorCode::InternalError,
+                message,
+            ),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = RemoteErrorBody {
+            code: self.code,
+            message: self.message,
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+fn parse_args() -> Result<ServerOptions, String> {
+    let mut options = ServerOptions {
+        addr: "127.0.0.1:8765".to_string(),
+        models_path: String::new(),
+        token: std::env::var("REMOTE_INFERENCE_TOKEN").ok(),
+        python: std::env::var("PYTHON_EXECUTABLE").unwrap_or_else(|_| "python".to_string()),
+        cache_dir: std::env::temp_dir().join("vision-match-inference-cache"),
+        cache_max_bytes: 1024 * 1024 * 1024,
+        cache_max_age: Duration::from_secs(24 * 60 * 60),
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("参数 {} 缺少值", arg));
+        match arg.as_str() {
+            "--addr" => options.addr = value()?,
+            "--models" => options.models_path = value()?,
+            "--token" => options.token = Some(value()?),
+            "--python" => options.python = value()?,
+            "--cache-dir" => options.cache_dir = PathBuf::from(value()?),
+            "--cache-max-mb" => {
+                let megabytes: u64 = value()?
+                    .parse()
+                    .map_err(|_| "--cache-max-mb 必须是整数".to_string())?;
+                options.cache_max_bytes = megabytes * 1024 * 1024;
+            }
+            "--cache-max-age-hours" => {
+                let hours: u64 = value()?
+                    .parse()
+                    .map_err(|_| "--cache-max-age-hours 必须是整数".to_string())?;
+                options.cache_max_age = Duration::from_secs(hours * 60 * 60);
+            }
+            "--locale" => labels::set_locale(&value()?),
+            _ => return Err(format!("未知参数: {}", arg)),
+        }
+    }
+
+    if options.models_path.is_empty() {
+        return Err("缺少 --models 参数".to_string());
+    }
+    Ok(options)
+}
+
+fn start_services(options: &ServerOptions) -> Result<HashMap<String, Arc<PythonService>>, String> {
+    let content = std::fs::read_to_string(&options.models_path)
+        .map_err(|e| format!("无法读取模型列表 {}: {}", options.models_path, e))?;
+    let models: Vec<ServerModel> =
+        serde_json::from_str(&content).map_err(|e| format!("模型列表格式错误: {}", e))?;
+
+    let mut services = HashMap::new();
+    for model in models {
+        println!("启动模型 {}: {}", model.id, model.model_path);
+        let labels = match &model.labels_path {
+            Some(labels_path) => Some(Arc::new(LabelSet::load(labels_path.as_ref())?)),
+            None => None,
+        };
+        let mut service = PythonService::new(
+            options.python.clone(),
+            model.script_path,
+            model.model_path,
+            model.protocol,
+            STARTUP_TIMEOUT,
+            INFERENCE_TIMEOUT,
+        )
+        .map_err(|e| format!("无法启动模型 {}: {}", model.id, e))?;
+        if let Some(labels) = labels {
+            service.set_labels(labels);
+        }
+        service.set_task(model.task);
+        services.insert(model.id, Arc::new(service));
+    }
+    Ok(services)
+}
+
+impl ServerState {
+    fn authorize(&self, headers: &HeaderMap) -> Result<(), ApiError> {
+        let Some(token) = &self.token else {
+            return Ok(());
+        };
+        let expected = format!("Bearer {}", token);
+        let provided = headers
+            .get(axum::http::header::AUTHORIZATION)
+            .map(|value| value.as_bytes())
+            .unwrap_or_default();
+        if constant_time_eq(provided, expected.as_bytes()) {
+            Ok(())
+        } else {
+            Err(ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                WorkerErrorCode::InvalidRequest,
+                "访问令牌无效",
+            ))
+        }
+    }
+
+    fn service(&self, model_id: &str) -> Result<Arc<PythonService>, ApiError> {
+        self.services.get(model_id).cloned().ok_or_else(|| {
+            ApiError::new(
+                StatusCode::BAD_REQUEST,
+                WorkerErrorCode::InvalidRequest,
+                format!("模型ID不存在: {}", model_id),
+            )
+        })
+    }
+
+    /// 缓存图片的路径，摘要必须是64位十六进制字符串
+    fn cached_image_path(&self, sha256: &str) -> Result<PathBuf, ApiError> {
+        if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                WorkerErrorCode::InvalidRequest,
+                "无效的图片摘要",
+            ));
+        }
+        Ok(self
+            .cache_dir
+            .join(format!("{}.img", sha256.to_ascii_lowercase())))
+    }
+}
+
+/// 比较令牌，耗时与内容无关，避免通过响应时间逐字节猜出令牌
+///
+/// 先各自取摘要再逐字节比较，长度不同的令牌也不会提前返回。
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let (a, b) = (Sha256::digest(a), Sha256::digest(b));
+    a.iter()
+        .zip(b.iter())
+        .fold(0u8, |diff, (x, y)| diff | (x ^ y))
+        == 0
+}
+
+async fn model_info(
+    State(state): State<Arc<ServerState>>,
+    Path(model_id): Path<String>,
+    headers: HeaderMap,
+) -> ApiResult<WorkerInfo> {
+    state.authorize(&headers)?;
+    let service = state.service(&model_id)?;
+    service.wait_ready().await?;
+    Ok(Json(service.info().await?))
+}
+
+/// 上传图片并识别，图片按摘要缓存，之后可以只发送摘要
+async fn infer_upload(
+    State(state): State<Arc<ServerState>>,
+    Path(model_id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> ApiResult<ModelResult> {
+    state.authorize(&headers)?;
+    let service = state.service(&model_id)?;
+
+    let sha256 = format!("{:x}", Sha256::digest(&body));
+    if let Some(declared) = headers
+        .get(REMOTE_SHA256_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        if !declared.eq_ignore_ascii_case(&sha256) {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                WorkerErrorCode::InvalidRequest,
+                "图片摘要与内容不一致",
+            ));
+        }
+    }
+
+    let path = state.cached_image_path(&sha256)?;
+    if path.exists() {
+        touch_cached_image(&path).await;
+    } else {
+        save_cached_image(&path, &body).await.map_err(|e| {
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                WorkerErrorCode::InternalError,
+                format!("无法保存图片: {}", e),
+            )
+        })?;
+    }
+
+    Ok(Json(service.process_image(&path.to_string_lossy()).await?))
+}
+
+/// 先写临时文件再改名，避免并发请求读到不完整的图片；失败时删除临时文件
+async fn save_cached_image(path: &std::path::Path, body: &[u8]) -> std::io::Result<()> {
+    let temp_path = path.with_extension(format!(
+        "{}-{}.tmp",
+        std::process::id(),
+        TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let saved = match tokio::fs::write(&temp_path, body).await {
+        Ok(()) => tokio::fs::rename(&temp_path, path).await,
+        Err(e) => Err(e),
+    };
+    if saved.is_err() {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+    }
+    saved
+}
+
+/// 更新缓存图片的修改时间，清理缓存时按修改时间判断图片最近是否使用过
+async fn touch_cached_image(path: &std::path::Path) {
+    let path = path.to_path_buf();
+    let _ = tokio::task::spawn_blocking(move || {
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()))
+    })
+    .await;
+}
+
+/// 删除超过保留时间的缓存图片和残留的临时文件，总大小超过上限时再从最久未使用的图片开始删除
+fn prune_cache(cache_dir: &std::path::Path, max_age: Duration, max_bytes: u64) {
+    let entries = match std::fs::read_dir(cache_dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            println!("无法读取图片缓存目录: {}", e);
+            return;
+        }
+    };
+
+    let now = SystemTime::now();
+    let mut images = Vec::new();
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_cache_file = matches!(
+            path.extension().and_then(|extension| extension.to_str()),
+            Some("img" | "tmp")
+        );
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if !is_cache_file || !metadata.is_file() {
+            continue;
+        }
+        let modified = metadata.modified().unwrap_or(now);
+        let age = now.duration_since(modified).unwrap_or_default();
+        if age >= max_age {
+            if std::fs::remove_file(&path).is_ok() {
+                removed += 1;
+            }
+        } else if path.extension().is_some_and(|extension| extension == "img") {
+            images.push((modified, metadata.len(), path));
+        }
+    }
+
+    let mut total: u64 = images.iter().map(|(_, len, _)| len).sum();
+    images.sort_by_key(|(modified, _, _)| *modified);
+    for (_, len, path) in images {
+        if total <= max_bytes {
+            break;
+        }
+        if std::fs::remove_file(&path).is_ok() {
+            total -= len;
+            removed += 1;
+        }
+    }
+
+    if removed > 0 {
+        println!(
+            "已清理 {} 个缓存图片，剩余 {} MB",
+            removed,
+            total / 1024 / 1024
+        );
+    }
+}
+
+/// 启动时及之后定期清理图片缓存
+async fn run_cache_pruner(cache_dir: PathBuf, max_age: Duration, max_bytes: u64) {
+    loop {
+        let dir = cache_dir.clone();
+        let _ = tokio::task::spawn_blocking(move || prune_cache(&dir, max_age, max_bytes)).await;
+        tokio::time::sleep(CACHE_PRUNE_INTERVAL).await;
+    }
+}
+
+/// 按摘要识别已缓存的图片，没有缓存时返回 404，客户端随后上传图片
+async fn infer_by_hash(
+    State(state): State<Arc<ServerState>>,
+    Path(model_id): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<RemoteHashRequest>,
+) -> ApiResult<ModelResult> {
+    state.authorize(&headers)?;
+    let service = state.service(&model_id)?;
+
+    let path = state.cached_image_path(&request.sha256)?;
+    if !path.exists() {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            WorkerErrorCode::InvalidRequest,
+            "服务端没有该图片",
+        ));
+    }
+    touch_cached_image(&path).await;
+
+    Ok(Json(service.process_image(&path.to_string_lossy()).await?))
+}
+
+#[tokio::main]
+async fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!(
+                "用法: inference_server --models <models.json> [--addr 127.0.0.1:8765] \
+                 [--token TOKEN] [--python python] [--cache-dir DIR] [--cache-max-mb 1024] \
+                 [--cache-max-age-hours 24] [--locale zh-CN]"
+            );
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(e) = std::fs::create_dir_all(&options.cache_dir) {
+        eprintln!("无法创建图片缓存目录: {}", e);
+        std::process::exit(1);
+    }
+    tokio::spawn(run_cache_pruner(
+        options.cache_dir.clone(),
+        options.cache_max_age,
+        options.cache_max_bytes,
+    ));
+
+    let services = match start_services(&options) {
+        Ok(services) => services,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let state = Arc::new(ServerState {
+        services,
+        token: options.token.clone(),
+        cache_dir: options.cache_dir.clone(),
+    });
+
+    let app = Router::new()
+        .route("/v1/models/{model_id}", get(model_info))
+        .route("/v1/models/{model_id}/infer", post(infer_upload))
+        .route("/v1/models/{model_id}/infer-by-hash", post(infer_by_hash))
+        .layer(DefaultBodyLimit::max(MAX_IMAGE_BYTES))
+        .with_state(Arc::clone(&state));
+
+    let listener = match tokio::net::TcpListener::bind(&options.addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("无法监听 {}: {}", options.addr, e);
+            std::process::exit(1);
+        }
+    };
+    println!("推理服务已启动: http://{}", options.addr);
+
+    let server = axum::serve(listener, app).with_graceful_shutdown(async {
+        let _ = tokio::signal::ctrl_c().await;
+        println!("正在关闭推理服务");
+    });
+    if let Err(e) = server.await {
+        eprintln!("推理服务出错: {}", e);
+    }
+
+    let shutdowns = state.services.values().map(|service| service.shutdown());
+    futures::future::join_all(shutdowns).await;
+}
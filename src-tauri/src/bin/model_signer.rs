@@ -0,0 +1,157 @@
+//! 模型发布者使用的签名工具，为模型清单记录文件摘要并生成 ed25519 签名
+//!
+//! 用法：
+//!
+//! ```text
+//! model_signer keygen <私钥文件>
+//! model_signer public-key <私钥文件>
+//! model_signer sign <私钥文件> <清单文件> --key-id ID [--base DIR]
+//! ```
+//!
+//! `keygen` 生成私钥并输出公钥，公钥加入使用方配置的 `model_integrity.trusted_keys`。
+//! `sign` 计算清单中权重文件、推理脚本及其同目录辅助脚本的 SHA-256，连同签名写回清单；
+//! 清单中的相对路径相对于 `--base`，默认为清单所在目录。
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ed25519_dalek::{Signer, SigningKey};
+use std::fs;
+use std::path::{Path, PathBuf};
+use vision_match::config::models::ModelManifest;
+use vision_match::models::inference_result::ModelSignature;
+use vision_match::services::integrity::{helpers_digest, signing_message};
+use vision_match::utils::file::calculate_path_hash;
+
+const USAGE: &str = "用法:
+  model_signer keygen <私钥文件>
+  model_signer public-key <私钥文件>
+  model_signer sign <私钥文件> <清单文件> --key-id ID [--base DIR]";
+
+fn read_signing_key(path: &Path) -> Result<SigningKey, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("无法读取私钥文件 {}: {}", path.display(), e))?;
+    let seed: [u8; 32] = BASE64
+        .decode(content.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(format!("私钥文件格式错误: {}", path.display()))?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+fn public_key(key: &SigningKey) -> String {
+    BASE64.encode(key.verifying_key().to_bytes())
+}
+
+fn keygen(path: &Path) -> Result<(), String> {
+    if path.exists() {
+        return Err(format!("私钥文件已存在: {}", path.display()));
+    }
+    let mut seed = [0u8; 32];
+    getrandom::getrandom(&mut seed).map_err(|e| format!("无法生成随机数: {}", e))?;
+    let key = SigningKey::from_bytes(&seed);
+    write_private(path, &BASE64.encode(seed))?;
+
+    println!("私钥已保存到 {}，请妥善保管", path.display());
+    println!("公钥: {}", public_key(&key));
+    Ok(())
+}
+
+/// 写入私钥文件，Unix 下只允许所有者读写
+fn write_private(path: &Path, content: &str) -> Result<(), String> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .map_err(|e| format!("无法创建私钥文件 {}: {}", path.display(), e))?;
+    std::io::Write::write_all(&mut file, content.as_bytes())
+        .map_err(|e| format!("无法写入私钥文件 {}: {}", path.display(), e))
+}
+
+fn sign(key_path: &Path, manifest_path: &Path, key_id: &str, base: &Path) -> Result<(), String> {
+    let key = read_signing_key(key_path)?;
+    let mut manifest = ModelManifest::from_file(manifest_path)?;
+
+    let digest = |relative: &str| {
+        let path = base.join(relative);
+        calculate_path_hash(&path).map_err(|e| format!("无法读取 {}: {}", path.display(), e))
+    };
+    let weights_sha256 = digest(&manifest.weights)?;
+    let script_sha256 = match &manifest.script {
+        Some(script) => Some(digest(script)?),
+        None => None,
+    };
+    let helpers_sha256 = match &manifest.script {
+        Some(script) => {
+            let script_dir = base
+                .join(script)
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+            Some(
+                helpers_digest(&script_dir)
+                    .map_err(|e| format!("无法读取辅助脚本 {}: {}", script_dir.display(), e))?,
+            )
+        }
+        None => None,
+    };
+
+    let message = signing_message(
+        &manifest.id,
+        &weights_sha256,
+        script_sha256.as_deref(),
+        helpers_sha256.as_deref(),
+    );
+    manifest.signature = Some(ModelSignature {
+        key_id: key_id.to_string(),
+        signature: BASE64.encode(key.sign(message.as_bytes()).to_bytes()),
+    });
+    manifest.weights_sha256 = Some(weights_sha256);
+    manifest.script_sha256 = script_sha256;
+    manifest.helpers_sha256 = helpers_sha256;
+    manifest.save(manifest_path)?;
+
+    println!("已签名模型 {}: {}", manifest.id, manifest_path.display());
+    println!("公钥 {}: {}", key_id, public_key(&key));
+    Ok(())
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    match args {
+        [command, key] if command == "keygen" => keygen(Path::new(key)),
+        [command, key] if command == "public-key" => {
+            println!("{}", public_key(&read_signing_key(Path::new(key))?));
+            Ok(())
+        }
+        [command, key, manifest, options @ ..] if command == "sign" => {
+            let manifest = PathBuf::from(manifest);
+            let mut key_id = None;
+            let mut base = manifest.parent().map(Path::to_path_buf).unwrap_or_default();
+
+            let mut options = options.iter();
+            while let Some(option) = options.next() {
+                let mut value = || options.next().ok_or(format!("参数 {} 缺少值", option));
+                match option.as_str() {
+                    "--key-id" => key_id = Some(value()?.clone()),
+                    "--base" => base = PathBuf::from(value()?),
+                    _ => return Err(format!("未知参数: {}", option)),
+                }
+            }
+            let key_id = key_id.ok_or("缺少 --key-id 参数")?;
+            sign(Path::new(key), &manifest, &key_id, &base)
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = run(&args) {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    }
+}
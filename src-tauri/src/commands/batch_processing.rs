@@ -0,0 +1,339 @@
+use crate::commands::file_management::store_image;
+use crate::config::models::MODEL_REGISTRY;
+use crate::db::histories_collection::{ImageHistoryRepository, RecognitionStatus};
+use crate::models::inference_result::{
+    BatchFinishedEvent, BatchJob, BatchProgressEvent, ModelInfo, ModelResult, SaveImageResult,
+};
+use crate::services::inference::{acquire_ready, InferenceBackend};
+use crate::utils::network::get_main_mac_address;
+use crate::utils::path_utils::get_app_data_path;
+use lazy_static::lazy_static;
+use mongodb::bson::oid::ObjectId;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{command, AppHandle, Emitter};
+
+lazy_static! {
+    /// 正在运行的批量任务及其取消标记
+    static ref BATCH_JOBS: Mutex<HashMap<String, Arc<AtomicBool>>> = Mutex::new(HashMap::new());
+}
+
+/// 每张图片处理完成时发送的事件名
+pub const BATCH_PROGRESS_EVENT: &str = "batch-progress";
+
+/// 批量任务结束时发送的事件名
+pub const BATCH_FINISHED_EVENT: &str = "batch-finished";
+
+/// 每次发送给推理脚本的图片数量
+const BATCH_CHUNK_SIZE: usize = 8;
+
+/// 目录中会被识别的图片扩展名
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "bmp", "webp"];
+
+/// 批量识别图片，paths 可以是图片文件或目录（只读取目录第一层的图片）
+///
+/// 任务在后台运行，立即返回任务ID；每张图片处理完成后发送 `batch-progress` 事件，
+/// 全部结束或取消后发送 `batch-finished` 事件。
+#[command]
+pub async fn process_batch(
+    app_handle: AppHandle,
+    paths: Vec<String>,
+    model_id: Option<String>,
+) -> Result<BatchJob, String> {
+    let files = collect_image_files(&paths)?;
+    if files.is_empty() {
+        return Err("没有找到可识别的图片".to_string());
+    }
+
+    // 获取目标模型
+    let model = {
+        let registry = MODEL_REGISTRY.lock().map_err(|_| "无法获取模型注册表锁")?;
+        match &model_id {
+            Some(model_id) => registry
+                .get_model(model_id)
+                .ok_or_else(|| format!("模型ID不存在: {}", model_id))?,
+            None => registry.get_active_model().ok_or("没有活跃的模型")?,
+        }
+    };
+
+    let job_id = ObjectId::new().to_hex();
+    let cancelled = Arc::new(AtomicBool::new(false));
+    BATCH_JOBS
+        .lock()
+        .map_err(|_| "无法获取批量任务锁")?
+        .insert(job_id.clone(), Arc::clone(&cancelled));
+
+    println!(
+        "开始批量识别任务 {}: {} 张图片, 模型: {}",
+        job_id,
+        files.len(),
+        model.name
+    );
+
+    let job = BatchJob {
+        job_id: job_id.clone(),
+        total: files.len(),
+    };
+    tokio::spawn(run_batch(app_handle, job_id, cancelled, model, files));
+    Ok(job)
+}
+
+/// 取消批量任务，已发送给推理脚本的图片仍会处理完成
+#[command]
+pub fn cancel_batch(job_id: String) -> Result<bool, String> {
+    let jobs = BATCH_JOBS.lock().map_err(|_| "无法获取批量任务锁")?;
+    match jobs.get(&job_id) {
+        Some(cancelled) => {
+            println!("取消批量识别任务: {}", job_id);
+            cancelled.store(true, Ordering::Relaxed);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// 展开目录并筛选图片文件，保持用户选择的顺序
+fn collect_image_files(paths: &[String]) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    for path in paths {
+        let path = Path::new(path);
+        if path.is_dir() {
+            let mut entries: Vec<PathBuf> = std::fs::read_dir(path)
+                .map_err(|e| format!("无法读取目录 {}: {}", path.display(), e))?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.is_file() && is_image_file(path))
+                .collect();
+            entries.sort();
+            files.extend(entries);
+        } else if path.is_file() {
+            files.push(path.to_path_buf());
+        } else {
+            return Err(format!("文件不存在: {}", path.display()));
+        }
+    }
+    Ok(files)
+}
+
+fn is_image_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// 已保存、等待识别的图片
+struct StoredImage {
+    index: usize,
+    source_path: String,
+    saved: SaveImageResult,
+    abs_path: String,
+}
+
+/// 批量任务的执行过程和统计
+struct BatchRun {
+    app_handle: AppHandle,
+    job_id: String,
+    total: usize,
+    mac_address: String,
+    model: ModelInfo,
+    succeeded: usize,
+    failed: usize,
+}
+
+impl BatchRun {
+    /// 记录一张图片的结果并通知前端
+    fn report(
+        &mut self,
+        index: usize,
+        source_path: String,
+        saved: Option<&SaveImageResult>,
+        result: Result<ModelResult, String>,
+    ) {
+        let (result, error) = match result {
+            Ok(result) => {
+                self.succeeded += 1;
+                (Some(result), None)
+            }
+            Err(error) => {
+                println!("批量识别 {} 失败: {}", source_path, error);
+                self.failed += 1;
+                (None, Some(error))
+            }
+        };
+
+        let event = BatchProgressEvent {
+            job_id: self.job_id.clone(),
+            index,
+            total: self.total,
+            source_path,
+            image_id: saved.map(|saved| saved.image_id.clone()),
+            file_path: saved.map(|saved| saved.file_path.clone()),
+            result,
+            error,
+        };
+        if let Err(e) = self.app_handle.emit(BATCH_PROGRESS_EVENT, event) {
+            println!("发送批量进度事件失败: {}", e);
+        }
+    }
+
+    /// 保存一组图片，读取或保存失败的图片直接报告错误
+    async fn store_chunk(&mut self, chunk: &[(usize, PathBuf)]) -> Vec<StoredImage> {
+        let mut stored = Vec::with_capacity(chunk.len());
+        for (index, path) in chunk {
+            let source_path = path.to_string_lossy().into_owned();
+            match self.store(path).await {
+                Ok((saved, abs_path)) => stored.push(StoredImage {
+                    index: *index,
+                    source_path,
+                    saved,
+                    abs_path,
+                }),
+                Err(e) => self.report(*index, source_path, None, Err(e)),
+            }
+        }
+        stored
+    }
+
+    async fn store(&self, path: &Path) -> Result<(SaveImageResult, String), String> {
+        let file_data = tokio::fs::read(path)
+            .await
+            .map_err(|e| format!("读取图片失败: {}", e))?;
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let saved = store_image(&self.app_handle, file_data, &file_name).await?;
+        let abs_path = get_app_data_path(&self.app_handle, &saved.file_path)?;
+        Ok((saved, abs_path))
+    }
+
+    /// 识别一组已保存的图片，并为每张图片写入历史记录
+    async fn recognize_chunk(&mut self, backend: &dyn InferenceBackend, images: Vec<StoredImage>) {
+        if images.is_empty() {
+            return;
+        }
+
+        let abs_paths: Vec<String> = images.iter().map(|image| image.abs_path.clone()).collect();
+        let results = match backend.infer_batch(&abs_paths).await {
+            Ok(results) => results
+                .into_iter()
+                .map(|result| result.map_err(|e| e.to_string()))
+                .collect(),
+            // 整批失败时每张图片记录相同的错误
+            Err(e) => vec![Err(e.to_string()); images.len()],
+        };
+
+        for (image, result) in images.into_iter().zip(results) {
+            let result = result.map(|mut result| {
+                result.model_type = Some(self.model.model_type.clone());
+                result
+            });
+            self.save_history(&image.saved, &result).await;
+            self.report(image.index, image.source_path, Some(&image.saved), result);
+        }
+    }
+
+    /// 写入历史记录，失败时只记录日志
+    async fn save_history(&self, saved: &SaveImageResult, result: &Result<ModelResult, String>) {
+        let image_oid = match ObjectId::parse_str(&saved.image_id) {
+            Ok(oid) => oid,
+            Err(_) => {
+                println!("无效的图像ID: {}", saved.image_id);
+                return;
+            }
+        };
+
+        let (status, result_value, confidence, error_message) = match result {
+            Ok(result) => (
+                RecognitionStatus::Success,
+                serde_json::to_value(result).ok(),
+                Some(result.confidence as f64),
+                None,
+            ),
+            Err(error) => (RecognitionStatus::Failed, None, None, Some(error.as_str())),
+        };
+
+        // 与单张识别一致，历史记录中的模型名称使用模型类型
+        if let Err(e) = ImageHistoryRepository::add_history(
+            &self.mac_address,
+            image_oid,
+            &self.model.model_type,
+            status,
+            result_value,
+            confidence,
+            error_message,
+        )
+        .await
+        {
+            println!("批量识别历史记录保存失败: {}", e);
+        }
+    }
+
+    fn finish(self, cancelled: bool) {
+        println!(
+            "批量识别任务 {} 结束: 成功 {}, 失败 {}, 已取消: {}",
+            self.job_id, self.succeeded, self.failed, cancelled
+        );
+        let event = BatchFinishedEvent {
+            job_id: self.job_id.clone(),
+            total: self.total,
+            succeeded: self.succeeded,
+            failed: self.failed,
+            cancelled,
+        };
+        if let Err(e) = self.app_handle.emit(BATCH_FINISHED_EVENT, event) {
+            println!("发送批量完成事件失败: {}", e);
+        }
+    }
+}
+
+/// 在后台执行批量任务
+async fn run_batch(
+    app_handle: AppHandle,
+    job_id: String,
+    cancelled: Arc<AtomicBool>,
+    model: ModelInfo,
+    files: Vec<PathBuf>,
+) {
+    let mut run = BatchRun {
+        app_handle,
+        job_id: job_id.clone(),
+        total: files.len(),
+        mac_address: get_main_mac_address(),
+        model,
+        succeeded: 0,
+        failed: 0,
+    };
+
+    let files: Vec<(usize, PathBuf)> = files.into_iter().enumerate().collect();
+    match acquire_ready(&run.app_handle, &run.model).await {
+        Ok(backend) => {
+            for chunk in files.chunks(BATCH_CHUNK_SIZE) {
+                if cancelled.load(Ordering::Relaxed) {
+                    break;
+                }
+                let images = run.store_chunk(chunk).await;
+                run.recognize_chunk(backend.as_ref(), images).await;
+            }
+        }
+        Err(e) => {
+            // 模型无法启动时所有图片都记为失败
+            for (index, path) in files {
+                run.report(
+                    index,
+                    path.to_string_lossy().into_owned(),
+                    None,
+                    Err(e.clone()),
+                );
+            }
+        }
+    }
+
+    if let Ok(mut jobs) = BATCH_JOBS.lock() {
+        jobs.remove(&job_id);
+    }
+    run.finish(cancelled.load(Ordering::Relaxed));
+}
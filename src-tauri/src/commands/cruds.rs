@@ -1,7 +1,8 @@
 use crate::db::db_client::DbError;
 use crate::db::histories_collection::{ImageHistory, ImageHistoryRepository, RecognitionStatus};
 use crate::db::images_collection::{Image, ImageRepository};
-use crate::models::dto::{HistoryDto, HistoryWithImageDto, ImageDto};
+use crate::models::dto::{HistoryDto, HistoryWithImageDto, ImageDto, SimilarImageDto};
+use crate::services::similarity;
 use crate::utils::network::get_main_mac_address;
 use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
@@ -27,6 +28,12 @@ fn convert_to_history_dto(history: &ImageHistory) -> HistoryDto {
         created_at: history.created_at.timestamp_millis(),
         image_id: history.image_id.to_string(),
         model_name: history.model_name.clone(),
+        task: history
+            .result
+            .as_ref()
+            .and_then(|result| result.get("task"))
+            .and_then(|task| serde_json::from_value(task.clone()).ok())
+            .unwrap_or_default(),
         result: history.result.clone(),
         confidence: history.confidence,
         status: match history.status {
@@ -37,6 +44,10 @@ fn convert_to_history_dto(history: &ImageHistory) -> HistoryDto {
             RecognitionStatus::Error => "error".to_string(),
         },
         error_message: history.error_message.clone(),
+        started_at: history.started_at.map(|t| t.timestamp_millis()),
+        finished_at: history.finished_at.map(|t| t.timestamp_millis()),
+        duration_ms: history.duration_ms,
+        group_id: history.group_id.map(|id| id.to_hex()),
     }
 }
 
@@ -193,6 +204,78 @@ pub async fn get_history_by_model(
     Ok(results)
 }
 
+/// 获取结果中包含指定类别的历史记录，类别可以是ID或当前语言的名称
+#[command]
+pub async fn get_history_by_label(
+    label: String,
+    limit: Option<u32>,
+) -> Result<Vec<HistoryWithImageDto>, String> {
+    let mac_address = get_main_mac_address();
+
+    // 获取历史记录
+    let histories = ImageHistoryRepository::find_by_label_and_mac(
+        &mac_address,
+        label.trim(),
+        limit.map(|v| v as i64),
+    )
+    .await
+    .map_err(map_db_error)?;
+
+    // 转换为DTO格式
+    let mut results = Vec::with_capacity(histories.len());
+    for history in histories {
+        let image_id = history.image_id.to_string();
+        let image = ImageRepository::find_by_id(&image_id)
+            .await
+            .map_err(map_db_error)?;
+
+        let history_dto = convert_to_history_dto(&history);
+        let image_dto = image.as_ref().map(convert_to_image_dto);
+
+        results.push(HistoryWithImageDto {
+            history: history_dto,
+            image: image_dto,
+        });
+    }
+
+    Ok(results)
+}
+
+/// 统计结果中包含指定类别的历史记录数量
+#[command]
+pub async fn count_history_by_label(label: String) -> Result<u64, String> {
+    let mac_address = get_main_mac_address();
+
+    ImageHistoryRepository::count_by_label_and_mac(&mac_address, label.trim())
+        .await
+        .map_err(map_db_error)
+}
+
+/// 获取同一组的历史记录（如一次多模型对比保存的结果），按模型对比时的顺序排列
+#[command]
+pub async fn get_history_group(group_id: String) -> Result<Vec<HistoryWithImageDto>, String> {
+    let histories = ImageHistoryRepository::find_by_group(&group_id)
+        .await
+        .map_err(map_db_error)?;
+
+    // 同一组的记录属于同一张图片，只需查询一次
+    let image = match histories.first() {
+        Some(history) => ImageRepository::find_by_id(&history.image_id.to_string())
+            .await
+            .map_err(map_db_error)?,
+        None => None,
+    };
+    let image_dto = image.as_ref().map(convert_to_image_dto);
+
+    Ok(histories
+        .iter()
+        .map(|history| HistoryWithImageDto {
+            history: convert_to_history_dto(history),
+            image: image_dto.clone(),
+        })
+        .collect())
+}
+
 /// 5. 更新历史记录状态
 #[command]
 pub async fn update_history_status(
@@ -313,3 +396,42 @@ pub async fn get_recent_images(limit: Option<u32>) -> Result<Vec<Image>, String>
         .await
         .map_err(map_db_error)
 }
+
+/// 相似图片检索默认返回的数量
+const DEFAULT_SIMILAR_IMAGES: u32 = 10;
+
+/// 查找与图片内容相似的其它图片，按余弦相似度从高到低排列
+///
+/// 使用识别时（`process_image` 带 `embed`）保存的特征向量，只在同一模型提取的向量之间比较。
+/// 未指定 `model_id` 时优先使用活跃模型的特征向量。
+#[command]
+pub async fn find_similar_images(
+    image_id: String,
+    k: Option<u32>,
+    model_id: Option<String>,
+) -> Result<Vec<SimilarImageDto>, String> {
+    let k = k.unwrap_or(DEFAULT_SIMILAR_IMAGES);
+    if k == 0 {
+        return Err("k 必须大于 0".to_string());
+    }
+
+    let (model_id, matches) =
+        similarity::find_similar(&image_id, model_id.as_deref(), k as usize).await?;
+
+    // 关联图片记录
+    let mut results = Vec::with_capacity(matches.len());
+    for (similar_id, score) in matches {
+        let image = ImageRepository::find_by_id(&similar_id.to_hex())
+            .await
+            .map_err(map_db_error)?;
+
+        results.push(SimilarImageDto {
+            image_id: similar_id.to_hex(),
+            model_id: model_id.clone(),
+            score,
+            image: image.as_ref().map(convert_to_image_dto),
+        });
+    }
+
+    Ok(results)
+}
@@ -47,6 +47,15 @@ pub async fn save_uploaded_image(
     app_handle: AppHandle,
     file_data: Vec<u8>,
     file_name: String,
+) -> Result<SaveImageResult, String> {
+    store_image(&app_handle, file_data, &file_name).await
+}
+
+/// 按内容哈希保存图片并写入数据库，相同内容的图片只保存一次
+pub async fn store_image(
+    app_handle: &AppHandle,
+    file_data: Vec<u8>,
+    file_name: &str,
 ) -> Result<SaveImageResult, String> {
     // 1. 计算文件哈希值 - 使用工具类中的SHA-256算法
     let hash = file::calculate_file_hash(&file_data);
@@ -74,11 +83,11 @@ pub async fn save_uploaded_image(
     }
 
     // 3. 获取文件扩展名和不带扩展的原名
-    let (name, ext) = file::split_filename(&file_name);
+    let (name, ext) = file::split_filename(file_name);
     // 使用哈希值前16位作为文件名
     let new_file_name = format!("{}.{}", &hash[..16], ext);
     // 4. 获取上传目录和相对路径
-    let (upload_dir, upload_dir_rel) = get_upload_dir(&app_handle)?;
+    let (upload_dir, upload_dir_rel) = get_upload_dir(app_handle)?;
     // 5. 保存文件
     let file_path: PathBuf = upload_dir.join(&new_file_name);
     print!("保存文件到: {:?}", &file_path);
@@ -1,18 +1,35 @@
-use crate::config::constants;
 use crate::config::models::MODEL_REGISTRY;
-use crate::models::inference_result::ModelResult;
-use crate::services::python::{PythonService, PYTHON_SERVICE};
-use crate::utils::path_utils::{get_app_data_path, get_resource_path};
-use serde_json;
-use std::path::Path;
+use crate::db::images_collection::ImageRepository;
+use crate::models::inference_result::{ModelResult, RecognitionOptions};
+use crate::services::inference::{acquire_backend, apply_options, validate_options};
+use crate::services::python::ServiceError;
+use crate::services::{explain, similarity};
+use crate::utils::file::calculate_path_hash;
+use crate::utils::path_utils::get_app_data_path;
+use mongodb::bson::oid::ObjectId;
+use std::path::{Path, PathBuf};
 use tauri::{command, AppHandle};
 
+/// 识别图像，未指定 model_id 时使用当前活跃模型
+///
+/// `options` 可以限制返回的类别数和最低置信度，并在最高置信度过低时把结果标记为不确定。
+/// `explain` 为真时同时生成预测类别的热力图，保存在原图旁边，路径记录在结果的 `heatmap_path` 中；
+/// 模型不支持时照常识别，结果中没有热力图。
+/// `embed` 为真时同时提取特征向量，返回在结果的 `embedding` 中，并按图片和模型保存，
+/// 供 `find_similar_images` 检索；模型不支持时同样照常识别。
 #[command]
 pub async fn process_image(
     app_handle: AppHandle,
     image_path: String,
+    model_id: Option<String>,
+    options: Option<RecognitionOptions>,
+    explain: Option<bool>,
+    embed: Option<bool>,
 ) -> Result<ModelResult, String> {
     println!("处理图像: {}", image_path);
+    if let Some(options) = &options {
+        validate_options(options)?;
+    }
     let image_abs_path = get_app_data_path(&app_handle, &image_path)?;
 
     // 确保图像文件存在
@@ -20,59 +37,122 @@ pub async fn process_image(
         return Err(format!("图像文件不存在: {}", image_abs_path));
     }
 
-    // 获取当前活跃模型
-    let registry = MODEL_REGISTRY.lock().map_err(|_| "无法获取模型注册表锁")?;
-    let active_model = registry.get_active_model().ok_or("没有活跃的模型")?;
-
-    // 获取模型脚本和路径
-    let script_abs_path = get_resource_path(&app_handle, &active_model.script_path)?;
-
-    let model_abs_path = get_resource_path(&app_handle, &active_model.path)?;
-    println!("使用脚本路径: {}", script_abs_path);
-    println!("使用模型路径: {}", model_abs_path);
-    // 获取或初始化Python服务
-    let mut service_lock = PYTHON_SERVICE.lock().map_err(|_| "无法获取Python服务锁")?;
+    // 获取目标模型，注册表锁不能跨越await持有
+    let model = {
+        let registry = MODEL_REGISTRY.lock().map_err(|_| "无法获取模型注册表锁")?;
+        match &model_id {
+            Some(model_id) => registry
+                .get_model(model_id)
+                .ok_or_else(|| format!("模型ID不存在: {}", model_id))?,
+            None => registry.get_active_model().ok_or("没有活跃的模型")?,
+        }
+    };
 
-    if service_lock.is_none() {
-        // 使用常量配置获取Python路径
-        let python_executable = &constants::get_config().python_executable;
+    // 按模型声明的后端类型获取推理后端
+    let (backend, started) = acquire_backend(&app_handle, &model).await?;
 
-        println!(
-            "初始化Python服务，使用Python: {}, 模型: {}",
-            python_executable, active_model.name
-        );
+    // 新创建的后端等待模型加载完成
+    if started {
+        backend.load().await.map_err(|e| e.to_string())?;
+    }
 
-        // 创建新的Python服务
-        *service_lock = Some(PythonService::new(
-            python_executable.clone(),
-            script_abs_path,
-            model_abs_path,
-        ));
+    // 处理图像，多个请求可以同时进行；只请求特征向量时与识别一次完成
+    let (explain, embed) = (explain.unwrap_or(false), embed.unwrap_or(false));
+    let (mut model_result, activation_map, mut embedding) = if explain {
+        match backend.explain(&image_abs_path).await {
+            Ok((result, map)) => (result, Some(map), None),
+            Err(ServiceError::Unsupported(_)) => {
+                println!("模型 {} 不支持生成热力图，只返回识别结果", model.name);
+                let result = backend
+                    .infer(&image_abs_path)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                (result, None, None)
+            }
+            Err(e) => return Err(e.to_string()),
+        }
+    } else if embed {
+        match backend.embed(&image_abs_path).await {
+            Ok((result, vector)) => (result, None, Some(vector)),
+            Err(ServiceError::Unsupported(_)) => {
+                println!("模型 {} 不支持提取特征向量，只返回识别结果", model.name);
+                let result = backend
+                    .infer(&image_abs_path)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                (result, None, None)
+            }
+            Err(e) => return Err(e.to_string()),
+        }
     } else {
-        // 确保使用的是当前活跃模型
-        service_lock
-            .as_mut()
-            .unwrap()
-            .switch_model(&script_abs_path, &model_abs_path)?;
+        let result = backend
+            .infer(&image_abs_path)
+            .await
+            .map_err(|e| e.to_string())?;
+        (result, None, None)
+    };
+
+    // 同时请求热力图时，特征向量需要再单独提取一次
+    if explain && embed {
+        match backend.embed(&image_abs_path).await {
+            Ok((_, vector)) => embedding = Some(vector),
+            Err(ServiceError::Unsupported(_)) => {
+                println!("模型 {} 不支持提取特征向量", model.name)
+            }
+            Err(e) => return Err(e.to_string()),
+        }
     }
 
-    // 处理图像
-    let model_type = active_model.model_type.clone();
-    let result = service_lock
-        .as_mut()
-        .unwrap()
-        .process_image(&image_abs_path)?;
-    // 解析JSON响应
-    match serde_json::from_str::<ModelResult>(&result) {
-        Ok(mut model_result) => {
-            // 添加模型类型信息
-            model_result.model_type = Some(model_type);
-            Ok(model_result)
+    if let Some(options) = &options {
+        model_result = apply_options(model_result, options);
+        if model_result.uncertain {
+            println!(
+                "识别结果不确定: {} ({:.2})",
+                model_result.prediction, model_result.confidence
+            );
         }
-        Err(e) => {
-            println!("JSON解析失败: {}", e);
-            println!("原始输出: {}", result);
-            Err(format!("结果解析失败: {}", e))
+    }
+
+    // 热力图保存在原图旁边，结果中记录相对应用数据目录的路径
+    if let Some(map) = activation_map {
+        let heatmap_path = explain::heatmap_relative_path(&image_path, &model.id);
+        let output_path = Path::new(&image_abs_path)
+            .with_file_name(explain::heatmap_file_name(&image_path, &model.id));
+        let source_path = PathBuf::from(&image_abs_path);
+        tokio::task::spawn_blocking(move || {
+            explain::save_overlay(&source_path, &map, &output_path)
+        })
+        .await
+        .map_err(|e| format!("生成热力图失败: {}", e))??;
+        println!("热力图已保存: {}", heatmap_path);
+        model_result.heatmap_path = Some(heatmap_path);
+    }
+
+    // 特征向量按图片记录保存，保存失败不影响识别结果
+    if let Some(vector) = &embedding {
+        match find_image_id(&image_abs_path).await {
+            Ok(Some(image_id)) => {
+                if let Err(e) = similarity::store_embedding(image_id, &model.id, vector).await {
+                    println!("{}", e);
+                }
+            }
+            Ok(None) => println!("图片没有上传记录，特征向量未保存: {}", image_path),
+            Err(e) => println!("查找图片记录失败，特征向量未保存: {}", e),
         }
     }
+    model_result.embedding = embedding;
+
+    // 添加模型类型信息
+    model_result.model_type = Some(model.model_type.clone());
+    Ok(model_result)
+}
+
+/// 按文件内容哈希查找图片在数据库中的记录，上传时图片以同样的哈希去重
+pub async fn find_image_id(image_abs_path: &str) -> Result<Option<ObjectId>, String> {
+    let hash = calculate_path_hash(Path::new(image_abs_path))
+        .map_err(|e| format!("无法读取图像: {}", e))?;
+    let image = ImageRepository::find_by_hash(&hash)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(image.and_then(|image| image.id))
 }
@@ -1,5 +1,9 @@
+pub mod batch_processing;
 pub mod cruds;
 pub mod file_management;
 pub mod image_processing;
+pub mod model_comparison;
 pub mod model_management;
+pub mod python_environment;
+pub mod recognition_jobs;
 pub mod save_image_history;
@@ -0,0 +1,254 @@
+use crate::commands::image_processing::find_image_id;
+use crate::config::models::MODEL_REGISTRY;
+use crate::db::histories_collection::{GroupedHistory, ImageHistoryRepository, RecognitionStatus};
+use crate::models::inference_result::{
+    ComparisonAgreement, ModelComparison, ModelComparisonEntry, ModelInfo, ModelResult,
+    PredictionVote, RecognitionOptions,
+};
+use crate::services::inference::{acquire_backend, apply_options, validate_options};
+use crate::utils::network::get_main_mac_address;
+use crate::utils::path_utils::get_app_data_path;
+use mongodb::bson::oid::ObjectId;
+use std::path::Path;
+use std::time::Instant;
+use tauri::{command, AppHandle};
+
+/// 用多个模型识别同一张图片，对比各模型的结果
+///
+/// 模型依次运行，不改变当前活跃模型；某个模型识别失败时记录在该模型的结果中，不影响其他模型。
+/// `save_history` 为真时把所有结果保存为一组关联的历史记录，可以用 `get_history_group` 查询，
+/// 此时图片需要先上传。
+#[command]
+pub async fn compare_models(
+    app_handle: AppHandle,
+    image_path: String,
+    model_ids: Vec<String>,
+    options: Option<RecognitionOptions>,
+    save_history: Option<bool>,
+) -> Result<ModelComparison, String> {
+    println!("多模型对比: {}, 模型: {:?}", image_path, model_ids);
+    if let Some(options) = &options {
+        validate_options(options)?;
+    }
+    let image_abs_path = get_app_data_path(&app_handle, &image_path)?;
+
+    // 确保图像文件存在
+    if !Path::new(&image_abs_path).exists() {
+        return Err(format!("图像文件不存在: {}", image_abs_path));
+    }
+
+    let models = resolve_models(&model_ids)?;
+
+    // 保存历史记录需要图片的上传记录，先检查以免识别完才发现无法保存
+    let save_history = save_history.unwrap_or(false);
+    let image_id = if save_history {
+        Some(
+            find_image_id(&image_abs_path)
+                .await?
+                .ok_or_else(|| format!("图片没有上传记录，无法保存历史记录: {}", image_path))?,
+        )
+    } else {
+        None
+    };
+
+    let mut entries = Vec::with_capacity(models.len());
+    for model in &models {
+        let entry = match run_model(&app_handle, model, &image_abs_path, options.as_ref()).await {
+            Ok((result, latency_ms, load_ms)) => ModelComparisonEntry {
+                model_id: model.id.clone(),
+                model_name: model.name.clone(),
+                result: Some(result),
+                error: None,
+                latency_ms: Some(latency_ms),
+                load_ms,
+                history_id: None,
+            },
+            Err(e) => {
+                println!("模型 {} 识别失败: {}", model.name, e);
+                ModelComparisonEntry {
+                    model_id: model.id.clone(),
+                    model_name: model.name.clone(),
+                    result: None,
+                    error: Some(e),
+                    latency_ms: None,
+                    load_ms: None,
+                    history_id: None,
+                }
+            }
+        };
+        entries.push(entry);
+    }
+
+    let agreement = agreement(&entries);
+
+    let group_id = match image_id {
+        Some(image_id) => Some(save_group(image_id, &models, &mut entries).await?),
+        None => None,
+    };
+
+    Ok(ModelComparison {
+        image_path,
+        entries,
+        agreement,
+        group_id: group_id.map(|id| id.to_hex()),
+    })
+}
+
+/// 按请求的顺序查找模型，忽略重复的模型ID
+fn resolve_models(model_ids: &[String]) -> Result<Vec<ModelInfo>, String> {
+    if model_ids.is_empty() {
+        return Err("至少需要选择一个模型".to_string());
+    }
+
+    let registry = MODEL_REGISTRY.lock().map_err(|_| "无法获取模型注册表锁")?;
+    let mut models: Vec<ModelInfo> = Vec::with_capacity(model_ids.len());
+    for model_id in model_ids {
+        if models.iter().any(|model| &model.id == model_id) {
+            continue;
+        }
+        let model = registry
+            .get_model(model_id)
+            .ok_or_else(|| format!("模型ID不存在: {}", model_id))?;
+        models.push(model);
+    }
+    Ok(models)
+}
+
+/// 用一个模型识别图片，返回结果、识别耗时和新启动模型时的加载耗时（毫秒）
+async fn run_model(
+    app_handle: &AppHandle,
+    model: &ModelInfo,
+    image_abs_path: &str,
+    options: Option<&RecognitionOptions>,
+) -> Result<(ModelResult, u64, Option<u64>), String> {
+    let load_started = Instant::now();
+    let (backend, started) = acquire_backend(app_handle, model).await?;
+
+    // 模型可能由其他请求启动、仍在加载中，同样等待其就绪
+    backend.load().await.map_err(|e| e.to_string())?;
+    let load_ms = started.then(|| load_started.elapsed().as_millis() as u64);
+
+    let infer_started = Instant::now();
+    let mut result = backend
+        .infer(image_abs_path)
+        .await
+        .map_err(|e| e.to_string())?;
+    let latency_ms = infer_started.elapsed().as_millis() as u64;
+
+    if let Some(options) = options {
+        result = apply_options(result, options);
+    }
+    result.model_type = Some(model.model_type.clone());
+    Ok((result, latency_ms, load_ms))
+}
+
+/// 统计各模型的主要结果，识别失败或没有结果的模型不投票
+fn agreement(entries: &[ModelComparisonEntry]) -> ComparisonAgreement {
+    let mut votes: Vec<PredictionVote> = Vec::new();
+    let mut confidences: Vec<f32> = Vec::new();
+    let mut successful = 0;
+
+    for entry in entries {
+        let Some(result) = &entry.result else {
+            continue;
+        };
+        successful += 1;
+        if result.prediction.is_empty() {
+            continue;
+        }
+
+        // 有类别ID时按ID比较，不同模型的类别名称可能不同
+        let class_id = result.class_id.clone();
+        let index = votes
+            .iter()
+            .position(|vote| match (&vote.class_id, &class_id) {
+                (Some(a), Some(b)) => a == b,
+                (None, None) => vote.label == result.prediction,
+                _ => false,
+            });
+        match index {
+            Some(index) => {
+                votes[index].model_ids.push(entry.model_id.clone());
+                confidences[index] += result.confidence;
+            }
+            None => {
+                votes.push(PredictionVote {
+                    label: result.prediction.clone(),
+                    class_id,
+                    model_ids: vec![entry.model_id.clone()],
+                    mean_confidence: 0.0,
+                });
+                confidences.push(result.confidence);
+            }
+        }
+    }
+
+    for (vote, total) in votes.iter_mut().zip(confidences) {
+        vote.mean_confidence = total / vote.model_ids.len() as f32;
+    }
+    votes.sort_by(|a, b| {
+        b.model_ids
+            .len()
+            .cmp(&a.model_ids.len())
+            .then(b.mean_confidence.total_cmp(&a.mean_confidence))
+    });
+
+    let top = votes.first().map(|vote| vote.model_ids.len()).unwrap_or(0);
+    ComparisonAgreement {
+        successful,
+        unanimous: successful > 0 && top == successful,
+        consensus: votes.first().map(|vote| vote.label.clone()),
+        agreement_ratio: if successful > 0 {
+            top as f32 / successful as f32
+        } else {
+            0.0
+        },
+        votes,
+    }
+}
+
+/// 把对比结果保存为一组历史记录，并在各模型的结果中记录历史记录ID
+async fn save_group(
+    image_id: ObjectId,
+    models: &[ModelInfo],
+    entries: &mut [ModelComparisonEntry],
+) -> Result<ObjectId, String> {
+    let mut group = Vec::with_capacity(entries.len());
+    for (model, entry) in models.iter().zip(entries.iter()) {
+        // 特征向量单独保存，不写入历史记录
+        let result = match &entry.result {
+            Some(result) => {
+                let mut result = result.clone();
+                result.embedding = None;
+                Some(
+                    serde_json::to_value(&result)
+                        .map_err(|e| format!("序列化识别结果失败: {}", e))?,
+                )
+            }
+            None => None,
+        };
+        group.push(GroupedHistory {
+            model_id: model.id.clone(),
+            model_name: model.model_type.clone(),
+            status: if entry.result.is_some() {
+                RecognitionStatus::Success
+            } else {
+                RecognitionStatus::Failed
+            },
+            result,
+            confidence: entry.result.as_ref().map(|r| r.confidence as f64),
+            error_message: entry.error.clone(),
+            duration_ms: entry.latency_ms.map(|ms| ms as i64),
+        });
+    }
+
+    let group_id = ObjectId::new();
+    let ids = ImageHistoryRepository::add_group(&get_main_mac_address(), image_id, group_id, group)
+        .await
+        .map_err(|e| format!("保存历史记录失败: {}", e))?;
+    for (entry, id) in entries.iter_mut().zip(ids) {
+        entry.history_id = Some(id.to_hex());
+    }
+    println!("多模型对比结果已保存，组ID: {}", group_id.to_hex());
+    Ok(group_id)
+}
@@ -1,8 +1,16 @@
+use crate::config::constants;
 use crate::config::models::MODEL_REGISTRY;
-use crate::models::inference_result::{AvailableModels, ModelInfo};
-use crate::services::python::PYTHON_SERVICE;
-use crate::utils::path_utils::to_absolute_path;
-use tauri::command;
+use crate::models::inference_result::{
+    AvailableModels, DiagnosticsReport, LabelLocales, ModelImportRequest, ModelInfo,
+    ModelMetadataUpdate, ModelPackageReport, ServiceStatus,
+};
+use crate::services::inference::acquire_backend;
+use crate::services::labels::{self, LabelSet};
+use crate::services::worker_pool::WORKER_POOL;
+use crate::services::{diagnostics, model_package, model_store, similarity};
+use crate::utils::path_utils::get_resource_path;
+use std::path::Path;
+use tauri::{command, AppHandle};
 
 #[command]
 pub fn get_available_models() -> Result<AvailableModels, String> {
@@ -19,27 +27,182 @@ pub fn get_available_models() -> Result<AvailableModels, String> {
     })
 }
 
+/// 设置活跃模型并保存到配置文件，同时在后台预热该模型的进程
+///
+/// 声明为异步命令，使模型进程在 Tauri 的异步运行时中启动
 #[command]
-pub fn switch_model(model_id: String) -> Result<ModelInfo, String> {
+pub async fn switch_model(app_handle: AppHandle, model_id: String) -> Result<ModelInfo, String> {
     println!("切换到模型: {}", model_id);
 
     // 获取模型信息并设置为活跃
-    let mut registry = MODEL_REGISTRY.lock().map_err(|_| "无法获取模型注册表锁")?;
-    let model = registry.set_active_model(&model_id)?;
+    let model = {
+        let mut registry = MODEL_REGISTRY.lock().map_err(|_| "无法获取模型注册表锁")?;
+        registry.set_active_model(&model_id)?
+    };
 
-    // 获取脚本和模型的绝对路径
-    let script_abs_path = to_absolute_path(&model.script_path);
-    let model_abs_path = to_absolute_path(&model.path);
-
-    // 切换Python服务中使用的模型
-    let mut service_lock = PYTHON_SERVICE.lock().map_err(|_| "无法获取Python服务锁")?;
-
-    if let Some(service) = service_lock.as_mut() {
-        // 如果服务已存在，切换模型
-        service.switch_model(&script_abs_path, &model_abs_path)?;
+    // 保存失败不影响本次切换，只是下次启动时不会恢复
+    if let Err(e) = constants::save_active_model(&app_handle, &model.id) {
+        println!("保存活跃模型失败: {}", e);
     }
-    // 注意：如果服务不存在，不需要创建，因为第一次调用process_image时会创建
+
+    // 提前加载模型，其他模型的后端保留在池中，切换回来时无需重新加载
+    acquire_backend(&app_handle, &model).await?;
 
     println!("模型切换成功，当前活跃模型: {}", model.name);
     Ok(model)
 }
+
+/// 获取模型进程状态，未指定 model_id 时查询活跃模型，模型加载中时前端可轮询此接口
+#[command]
+pub fn get_model_status(model_id: Option<String>) -> Result<ServiceStatus, String> {
+    let model_id = match model_id {
+        Some(model_id) => model_id,
+        None => {
+            let registry = MODEL_REGISTRY.lock().map_err(|_| "无法获取模型注册表锁")?;
+            registry
+                .get_active_model()
+                .map(|m| m.id)
+                .ok_or("没有活跃的模型")?
+        }
+    };
+
+    let pool = WORKER_POOL.lock().map_err(|_| "无法获取模型进程池锁")?;
+    Ok(pool.status(&model_id))
+}
+
+/// 导入用户模型，复制文件到应用数据目录并用示例图片试运行，成功后注册
+#[command]
+pub async fn import_model(
+    app_handle: AppHandle,
+    request: ModelImportRequest,
+) -> Result<ModelInfo, String> {
+    println!("导入模型: {}", request.name);
+    model_store::import_model(&app_handle, request).await
+}
+
+/// 删除用户导入的模型，内置模型不能删除
+///
+/// 声明为异步命令，以便在 Tauri 的异步运行时中卸载该模型的进程
+#[command]
+pub async fn remove_model(app_handle: AppHandle, model_id: String) -> Result<(), String> {
+    println!("删除模型: {}", model_id);
+    model_store::remove_user_model(&app_handle, &model_id)?;
+    // 数据库不可用时只记录，模型文件已经删除
+    if let Err(e) = similarity::forget_model(&model_id).await {
+        println!("{}", e);
+    }
+    Ok(())
+}
+
+/// 修改用户模型的名称、描述或类型
+#[command]
+pub fn update_model_metadata(
+    model_id: String,
+    update: ModelMetadataUpdate,
+) -> Result<ModelInfo, String> {
+    model_store::update_model_metadata(&model_id, update)
+}
+
+/// 验证模型包，返回各示例图片的识别结果，不安装
+///
+/// 没有签名的 Python 模型包需要用户确认后传入 `allow_unsigned_scripts = true` 才会运行其中的脚本。
+#[command]
+pub async fn verify_model_package(
+    app_handle: AppHandle,
+    package_path: String,
+    allow_unsigned_scripts: Option<bool>,
+) -> Result<ModelPackageReport, String> {
+    println!("验证模型包: {}", package_path);
+    model_package::verify_package(
+        &app_handle,
+        Path::new(&package_path),
+        allow_unsigned_scripts.unwrap_or(false),
+    )
+    .await
+}
+
+/// 安装模型包（.wvmodel），验证通过后注册为用户模型，卸载使用 `remove_model`
+///
+/// `allow_unsigned_scripts` 的含义与 `verify_model_package` 相同。
+#[command]
+pub async fn install_model_package(
+    app_handle: AppHandle,
+    package_path: String,
+    allow_unsigned_scripts: Option<bool>,
+) -> Result<ModelInfo, String> {
+    println!("安装模型包: {}", package_path);
+    model_package::install_package(
+        &app_handle,
+        Path::new(&package_path),
+        allow_unsigned_scripts.unwrap_or(false),
+    )
+    .await
+}
+
+/// 将已注册的模型导出为模型包，返回模型包路径
+#[command]
+pub async fn export_model_package(
+    app_handle: AppHandle,
+    model_id: String,
+    output_path: String,
+) -> Result<String, String> {
+    println!("导出模型包: {} -> {}", model_id, output_path);
+    let package_path =
+        model_package::export_package(&app_handle, &model_id, Path::new(&output_path)).await?;
+    Ok(package_path.to_string_lossy().into_owned())
+}
+
+/// 检查 Python 环境和所有模型，逐个加载模型并识别示例图片，返回诊断报告
+///
+/// 每个模型使用独立的后端，检查结束后卸载，不影响已加载的模型。
+#[command]
+pub async fn diagnose_models(app_handle: AppHandle) -> Result<DiagnosticsReport, String> {
+    println!("开始诊断模型");
+    diagnostics::diagnose_models(&app_handle).await
+}
+
+/// 获取当前的类别名称语言，以及模型类别文件中可选的语言，未指定 model_id 时查询活跃模型
+#[command]
+pub fn get_label_locales(
+    app_handle: AppHandle,
+    model_id: Option<String>,
+) -> Result<LabelLocales, String> {
+    let model = {
+        let registry = MODEL_REGISTRY.lock().map_err(|_| "无法获取模型注册表锁")?;
+        match model_id {
+            Some(model_id) => registry.get_model(&model_id),
+            None => registry.get_active_model(),
+        }
+        .ok_or("模型不存在")?
+    };
+
+    let available = match &model.labels_path {
+        Some(labels_path) => {
+            LabelSet::load(Path::new(&get_resource_path(&app_handle, labels_path)?))?.locales()
+        }
+        None => Vec::new(),
+    };
+    Ok(LabelLocales {
+        current: labels::current_locale(),
+        available,
+    })
+}
+
+/// 切换识别结果中类别名称的语言并保存到配置文件
+///
+/// 类别文件中没有该语言的名称时，依次回退到同一语种和类别文件的默认语言。
+#[command]
+pub fn set_label_locale(app_handle: AppHandle, locale: String) -> Result<(), String> {
+    let locale = locale.trim();
+    if locale.is_empty() {
+        return Err("语言不能为空".to_string());
+    }
+    println!("切换类别名称语言: {}", locale);
+    labels::set_locale(locale);
+
+    // 保存失败不影响本次切换，只是下次启动时不会恢复
+    if let Err(e) = constants::save_label_locale(&app_handle, locale) {
+        println!("保存类别名称语言失败: {}", e);
+    }
+    Ok(())
+}
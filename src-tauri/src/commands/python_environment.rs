@@ -0,0 +1,38 @@
+use crate::models::inference_result::{PythonCandidate, PythonEnvironmentReport};
+use crate::services::python_env;
+use tauri::{command, AppHandle};
+
+/// 查找本机的 Python 解释器（PATH、py 启动器、conda、pyenv、`.venv`），并检查各自的版本和依赖库
+#[command]
+pub async fn discover_python_interpreters(
+    app_handle: AppHandle,
+) -> Result<Vec<PythonCandidate>, String> {
+    println!("查找Python解释器");
+    Ok(python_env::discover_interpreters(&app_handle).await)
+}
+
+/// 使用指定的 Python 解释器并保存到配置文件，返回该解释器的检查结果
+#[command]
+pub async fn set_python_executable(
+    app_handle: AppHandle,
+    executable: String,
+) -> Result<PythonEnvironmentReport, String> {
+    let executable = executable.trim();
+    if executable.is_empty() {
+        return Err("Python解释器路径不能为空".to_string());
+    }
+    python_env::select_interpreter(&app_handle, executable).await
+}
+
+/// 在应用数据目录创建专用的虚拟环境并安装依赖，成功后使用该环境
+///
+/// 耗时较长，进度通过 `python-setup-progress` 事件发送。
+#[command]
+pub async fn setup_python_venv(
+    app_handle: AppHandle,
+    base_python: Option<String>,
+    wheelhouse: Option<String>,
+) -> Result<PythonEnvironmentReport, String> {
+    println!("创建Python虚拟环境");
+    python_env::setup_managed_venv(&app_handle, base_python, wheelhouse).await
+}
@@ -0,0 +1,42 @@
+use crate::config::models::MODEL_REGISTRY;
+use crate::db::histories_collection::ImageHistoryRepository;
+use crate::services::job_queue::notify_job_queue;
+use crate::utils::network::get_main_mac_address;
+use mongodb::bson::oid::ObjectId;
+use tauri::command;
+
+/// 将图片加入后台识别队列，返回对应的历史记录ID
+///
+/// 任务记录保存在数据库中，应用重启后会继续执行；结束时发送 `recognition-job-finished` 事件。
+#[command]
+pub async fn enqueue_recognition(
+    image_id: String,
+    model_id: Option<String>,
+) -> Result<String, String> {
+    let image_oid =
+        ObjectId::parse_str(&image_id).map_err(|_| format!("无效的图像ID: {}", image_id))?;
+
+    // 获取目标模型，未指定时使用当前活跃模型
+    let model = {
+        let registry = MODEL_REGISTRY.lock().map_err(|_| "无法获取模型注册表锁")?;
+        match &model_id {
+            Some(model_id) => registry
+                .get_model(model_id)
+                .ok_or_else(|| format!("模型ID不存在: {}", model_id))?,
+            None => registry.get_active_model().ok_or("没有活跃的模型")?,
+        }
+    };
+
+    // 与单张识别一致，历史记录中的模型名称使用模型类型
+    let history_id = ImageHistoryRepository::enqueue(
+        &get_main_mac_address(),
+        image_oid,
+        &model.id,
+        &model.model_type,
+    )
+    .await
+    .map_err(|e| format!("数据库错误: {}", e))?;
+
+    notify_job_queue();
+    Ok(history_id.to_hex())
+}
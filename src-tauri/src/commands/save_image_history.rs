@@ -10,7 +10,7 @@ use tauri::command;
 pub async fn save_image_history(
     image_id: String,
     model_name: String,
-    result: Option<ModelResult>,
+    mut result: Option<ModelResult>,
     status: Option<String>,
     error_message: Option<String>,
 ) -> Result<SaveHistoryResult, String> {
@@ -40,7 +40,10 @@ pub async fn save_image_history(
         }
     };
 
-    // 2. 准备结果值
+    // 2. 准备结果值，特征向量已单独保存，不写入历史记录
+    if let Some(model_result) = &mut result {
+        model_result.embedding = None;
+    }
     let result_value = match &result {
         Some(model_result) => {
             // 将ModelResult序列化为serde_json::Value
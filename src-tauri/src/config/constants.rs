@@ -1,5 +1,7 @@
+use crate::services::labels::DEFAULT_LOCALE;
 use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::{env, fs};
 use tauri::{AppHandle, Manager};
@@ -8,6 +10,33 @@ use tauri::{AppHandle, Manager};
 pub struct AppConfig {
     pub upload_dir: String,
     pub python_executable: String,
+    /// 等待推理脚本加载模型的最长时间（秒）
+    #[serde(default = "default_python_startup_timeout")]
+    pub python_startup_timeout_secs: u64,
+    /// 单次推理请求的超时时间（秒）
+    #[serde(default = "default_inference_timeout")]
+    pub inference_timeout_secs: u64,
+    /// 同时保持加载状态的模型进程数上限
+    #[serde(default = "default_max_warm_workers")]
+    pub max_warm_workers: usize,
+    /// 模型进程空闲多久后自动关闭（秒）
+    #[serde(default = "default_worker_idle_timeout")]
+    pub worker_idle_timeout_secs: u64,
+    /// 远程推理服务，设置后所有模型都由远程服务识别
+    #[serde(default)]
+    pub remote_inference: Option<RemoteInferenceConfig>,
+    /// 上次选择的活跃模型，启动时恢复；运行期间以模型注册表为准
+    #[serde(default)]
+    pub active_model_id: Option<String>,
+    /// 识别结果中类别名称使用的语言，如 `zh-CN`、`en`、`la`（拉丁学名）
+    #[serde(default = "default_label_locale")]
+    pub label_locale: String,
+    /// 模型文件完整性校验
+    #[serde(default)]
+    pub model_integrity: ModelIntegrityConfig,
+    /// 相似图片检索使用的索引
+    #[serde(default)]
+    pub similarity_index: SimilarityIndexKind,
     // 数据库配置
     pub mongodb_uri: String,
     pub mongodb_database: String,
@@ -18,6 +47,15 @@ impl Default for AppConfig {
         Self {
             upload_dir: String::from("uploads"),
             python_executable: String::from("python"),
+            python_startup_timeout_secs: default_python_startup_timeout(),
+            inference_timeout_secs: default_inference_timeout(),
+            max_warm_workers: default_max_warm_workers(),
+            worker_idle_timeout_secs: default_worker_idle_timeout(),
+            remote_inference: None,
+            active_model_id: None,
+            label_locale: default_label_locale(),
+            model_integrity: ModelIntegrityConfig::default(),
+            similarity_index: SimilarityIndexKind::default(),
             // 默认MongoDB连接信息
             mongodb_uri: String::from("localhost"),
             mongodb_database: String::from("mongodb"),
@@ -25,6 +63,114 @@ impl Default for AppConfig {
     }
 }
 
+/// 远程推理服务配置
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RemoteInferenceConfig {
+    /// 服务地址，例如 `http://192.168.1.10:8765`
+    pub endpoint: String,
+    /// 以 `Authorization: Bearer <token>` 发送的访问令牌
+    #[serde(default)]
+    pub auth_token: Option<String>,
+    /// 每个请求附带的其它请求头
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// 单次请求的超时时间（秒）
+    #[serde(default = "default_remote_timeout")]
+    pub timeout_secs: u64,
+    /// 连接失败、超时或服务端错误时的重试次数
+    #[serde(default = "default_remote_max_retries")]
+    pub max_retries: u32,
+    /// 先只发送图片摘要，服务端没有该图片时再上传文件
+    #[serde(default = "default_send_hash_first")]
+    pub send_hash_first: bool,
+}
+
+impl RemoteInferenceConfig {
+    pub fn new(endpoint: String) -> Self {
+        Self {
+            endpoint,
+            auth_token: None,
+            headers: HashMap::new(),
+            timeout_secs: default_remote_timeout(),
+            max_retries: default_remote_max_retries(),
+            send_hash_first: default_send_hash_first(),
+        }
+    }
+}
+
+/// 模型文件完整性校验配置
+///
+/// 模型清单记录了文件摘要或带有签名时总会校验，这里的选项决定是否拒绝没有摘要或签名的模型。
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ModelIntegrityConfig {
+    /// 拒绝加载没有记录权重文件或推理脚本摘要的模型
+    #[serde(default)]
+    pub require_digests: bool,
+    /// 拒绝加载没有受信任发布者签名的模型
+    #[serde(default)]
+    pub require_signatures: bool,
+    /// 受信任的发布者公钥
+    #[serde(default)]
+    pub trusted_keys: Vec<TrustedKey>,
+}
+
+/// 相似图片检索的索引类型
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SimilarityIndexKind {
+    /// 向量较少时逐个比较，达到一定数量后使用近似索引
+    #[default]
+    Auto,
+    /// 逐个计算余弦相似度，结果精确
+    BruteForce,
+    /// 随机超平面局部敏感哈希，只比较哈希相近的向量
+    Lsh,
+}
+
+/// 受信任的发布者公钥
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrustedKey {
+    /// 公钥ID，与模型签名中的 `key_id` 对应
+    pub id: String,
+    /// 发布者名称
+    #[serde(default)]
+    pub name: String,
+    /// Base64 编码的 ed25519 公钥
+    pub public_key: String,
+}
+
+fn default_remote_timeout() -> u64 {
+    60
+}
+
+fn default_remote_max_retries() -> u32 {
+    2
+}
+
+fn default_send_hash_first() -> bool {
+    true
+}
+
+fn default_label_locale() -> String {
+    String::from(DEFAULT_LOCALE)
+}
+
+fn default_python_startup_timeout() -> u64 {
+    120
+}
+
+fn default_inference_timeout() -> u64 {
+    60
+}
+
+fn default_max_warm_workers() -> usize {
+    2
+}
+
+fn default_worker_idle_timeout() -> u64 {
+    600
+}
+
 /// 获取配置文件路径 - 使用 Tauri 内置的 app_config_dir
 pub fn get_config_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
     // 使用 Tauri 的 app_config_dir 获取配置目录
@@ -55,6 +201,45 @@ pub fn init_config(app_handle: Option<&AppHandle>) -> &'static AppConfig {
             if let Ok(value) = env::var("PYTHON_EXECUTABLE") {
                 config.python_executable = value;
             }
+            if let Some(value) = env::var("PYTHON_STARTUP_TIMEOUT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+            {
+                config.python_startup_timeout_secs = value;
+            }
+            if let Some(value) = env::var("INFERENCE_TIMEOUT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+            {
+                config.inference_timeout_secs = value;
+            }
+            if let Some(value) = env::var("MAX_WARM_WORKERS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+            {
+                config.max_warm_workers = value;
+            }
+            if let Some(value) = env::var("WORKER_IDLE_TIMEOUT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+            {
+                config.worker_idle_timeout_secs = value;
+            }
+            if let Ok(endpoint) = env::var("REMOTE_INFERENCE_URL") {
+                let mut remote = RemoteInferenceConfig::new(endpoint);
+                remote.auth_token = env::var("REMOTE_INFERENCE_TOKEN").ok();
+                config.remote_inference = Some(remote);
+            }
+            // 界面中选择的活跃模型、类别语言、模型校验设置和Python解释器在开发环境也保留，
+            // 环境变量指定的解释器优先
+            if let Some(saved) = app_handle.and_then(read_config_file) {
+                config.active_model_id = saved.active_model_id;
+                config.label_locale = saved.label_locale;
+                config.model_integrity = saved.model_integrity;
+                if env::var("PYTHON_EXECUTABLE").is_err() {
+                    config.python_executable = saved.python_executable;
+                }
+            }
             if let Ok(value) = env::var("MONGODB_URI") {
                 config.mongodb_uri = value;
             }
@@ -67,33 +252,10 @@ pub fn init_config(app_handle: Option<&AppHandle>) -> &'static AppConfig {
         // 生产环境从配置文件加载
         #[cfg(not(debug_assertions))]
         {
-            if let Some(app_handle) = app_handle {
-                match get_config_path(app_handle) {
-                    Ok(config_path) => {
-                        if config_path.exists() {
-                            match fs::read_to_string(&config_path) {
-                                Ok(content) => match serde_json::from_str::<AppConfig>(&content) {
-                                    Ok(config) => {
-                                        println!("从文件加载配置成功: {:?}", config_path);
-                                        return config;
-                                    }
-                                    Err(e) => {
-                                        println!("解析配置文件失败: {}", e);
-                                    }
-                                },
-                                Err(e) => {
-                                    println!("读取配置文件失败: {}", e);
-                                }
-                            }
-                        } else {
-                            println!("配置文件不存在，使用默认配置");
-                        }
-                    }
-                    Err(e) => {
-                        println!("获取配置路径失败: {}", e);
-                    }
-                }
-            } else {
+            if let Some(config) = app_handle.and_then(read_config_file) {
+                return config;
+            }
+            if app_handle.is_none() {
                 println!("没有提供AppHandle，无法加载配置文件");
             }
 
@@ -103,6 +265,46 @@ pub fn init_config(app_handle: Option<&AppHandle>) -> &'static AppConfig {
     })
 }
 
+/// 从配置文件读取配置，文件不存在或无法解析时返回 None
+fn read_config_file(app_handle: &AppHandle) -> Option<AppConfig> {
+    match load_config_file(app_handle) {
+        Ok(Some(config)) => Some(config),
+        Ok(None) => {
+            println!("配置文件不存在，使用默认配置");
+            None
+        }
+        Err(e) => {
+            println!("{}", e);
+            None
+        }
+    }
+}
+
+/// 读取配置文件，文件不存在时返回 None，存在但无法读取或解析时返回错误
+fn load_config_file(app_handle: &AppHandle) -> Result<Option<AppConfig>, String> {
+    let config_path =
+        get_config_path(app_handle).map_err(|e| format!("获取配置路径失败: {}", e))?;
+    if !config_path.exists() {
+        return Ok(None);
+    }
+
+    let content =
+        fs::read_to_string(&config_path).map_err(|e| format!("读取配置文件失败: {}", e))?;
+    let config = serde_json::from_str::<AppConfig>(&content)
+        .map_err(|e| format!("解析配置文件失败: {}", e))?;
+    println!("从文件加载配置成功: {:?}", config_path);
+    Ok(Some(config))
+}
+
+/// 读取配置文件作为修改的基础，文件不存在时使用默认配置
+///
+/// 文件存在但无法解析时返回错误，不能用默认配置覆盖用户的设置。
+fn read_config_for_update(app_handle: &AppHandle) -> Result<AppConfig, String> {
+    load_config_file(app_handle)
+        .map(Option::unwrap_or_default)
+        .map_err(|e| format!("{}，未修改配置文件", e))
+}
+
 /// 获取应用程序配置
 pub fn get_config() -> &'static AppConfig {
     APP_CONFIG.get().expect("应用配置尚未初始化")
@@ -124,3 +326,26 @@ pub fn save_config(app_handle: &AppHandle, config: &AppConfig) -> Result<(), Str
     println!("配置已保存到: {:?}", config_path);
     Ok(())
 }
+
+/// 将活跃模型写入配置文件，下次启动时恢复
+///
+/// 以配置文件中的内容为基础修改，避免把开发环境的环境变量写入文件。
+pub fn save_active_model(app_handle: &AppHandle, model_id: &str) -> Result<(), String> {
+    let mut config = read_config_for_update(app_handle)?;
+    config.active_model_id = Some(model_id.to_string());
+    save_config(app_handle, &config)
+}
+
+/// 将选择的Python解释器写入配置文件，下次启动时使用
+pub fn save_python_executable(app_handle: &AppHandle, executable: &str) -> Result<(), String> {
+    let mut config = read_config_for_update(app_handle)?;
+    config.python_executable = executable.to_string();
+    save_config(app_handle, &config)
+}
+
+/// 将类别名称语言写入配置文件，下次启动时恢复
+pub fn save_label_locale(app_handle: &AppHandle, locale: &str) -> Result<(), String> {
+    let mut config = read_config_for_update(app_handle)?;
+    config.label_locale = locale.to_string();
+    save_config(app_handle, &config)
+}
@@ -1,80 +1,447 @@
-use crate::models::inference_result::ModelInfo;
+use crate::config::constants;
+use crate::models::inference_result::{
+    BackendKind, EnsembleConfig, ModelInfo, ModelSignature, ModelSource, ModelTask, WorkerProtocol,
+};
+use crate::services::labels::LabelSet;
 use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+/// 内置模型清单目录，相对于资源目录
+pub const BUNDLED_MANIFEST_DIR: &str = "resources/manifests";
+
+/// 用户模型清单目录，相对于应用数据目录
+pub const USER_MANIFEST_DIR: &str = "manifests";
 
 lazy_static! {
     pub static ref MODEL_REGISTRY: Mutex<ModelRegistry> = Mutex::new(ModelRegistry::new());
 }
 
+/// 模型清单文件，JSON 或 TOML 格式，一个文件描述一个模型
+///
+/// 内置清单中的相对路径相对于资源目录，用户清单中的相对路径相对于应用数据目录。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelManifest {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// 模型权重文件，集成模型没有
+    #[serde(default)]
+    pub weights: String,
+    #[serde(default)]
+    pub model_type: String,
+    pub num_classes: u32,
+    /// 推理后端，未声明时按权重文件判断
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<BackendKind>,
+    /// 推理脚本，Python 后端需要
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+    #[serde(default)]
+    pub protocol: WorkerProtocol,
+    /// 任务类型，未声明时为分类
+    #[serde(default, skip_serializing_if = "is_classification")]
+    pub task: ModelTask,
+    /// 类别名称文件
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub labels: Option<String>,
+    /// 权重文件的SHA256哈希值，加载前校验，也用于识别重复的模型
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weights_sha256: Option<String>,
+    /// 推理脚本的SHA256哈希值，启动推理进程前校验
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script_sha256: Option<String>,
+    /// 推理脚本同目录下辅助脚本的合并摘要，启动推理进程前校验
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub helpers_sha256: Option<String>,
+    /// 发布者对模型文件摘要的签名
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<ModelSignature>,
+    /// 没有保存过活跃模型时默认使用此模型
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub default: bool,
+    /// 示例图片及期望的识别结果，安装模型包时用于验证
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub samples: Vec<ModelSample>,
+    /// 集成模型的成员和合并方式，需要同时声明 `backend = "ensemble"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ensemble: Option<EnsembleConfig>,
+}
+
+/// 模型清单中的示例图片
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelSample {
+    /// 图片路径，与清单中其它路径的基准目录相同
+    pub image: String,
+    /// 期望的识别类别
+    pub prediction: String,
+    /// 期望类别的稳定ID，有ID时按ID比较，不受显示语言影响
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class_id: Option<String>,
+    /// 期望的置信度，为空时只比较类别
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f32>,
+}
+
+impl ModelManifest {
+    /// 读取清单文件，按扩展名区分 `.json` 和 `.toml`
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("无法读取模型清单 {}: {}", path.display(), e))?;
+        let manifest: Self = if has_extension(path, "toml") {
+            toml::from_str(&content).map_err(|e| e.to_string())
+        } else {
+            serde_json::from_str(&content).map_err(|e| e.to_string())
+        }
+        .map_err(|e| format!("模型清单格式错误 {}: {}", path.display(), e))?;
+
+        if manifest.id.trim().is_empty() {
+            return Err(format!("模型清单缺少模型ID: {}", path.display()));
+        }
+        let is_ensemble = manifest.backend == Some(BackendKind::Ensemble);
+        if is_ensemble != manifest.ensemble.is_some() {
+            return Err(format!(
+                "集成模型需要同时声明 backend = \"ensemble\" 和 ensemble: {}",
+                path.display()
+            ));
+        }
+        if !is_ensemble && manifest.weights.trim().is_empty() {
+            return Err(format!("模型清单缺少权重文件: {}", path.display()));
+        }
+        Ok(manifest)
+    }
+
+    /// 检查类别文件的类别数与清单声明的 `num_classes` 一致，`root` 为清单中相对路径的基准目录
+    ///
+    /// 没有类别文件或 `num_classes` 为 0 时不检查。
+    pub fn check_labels(&self, root: &Path) -> Result<(), String> {
+        let Some(labels) = &self.labels else {
+            return Ok(());
+        };
+        if self.num_classes == 0 {
+            return Ok(());
+        }
+        let count = LabelSet::load(&root.join(labels))?.len();
+        if count != self.num_classes as usize {
+            return Err(format!(
+                "模型 {} 的类别文件有 {} 个类别，与清单声明的 {} 个不一致",
+                self.id, count, self.num_classes
+            ));
+        }
+        Ok(())
+    }
+
+    /// 写入清单文件，格式与读取时一样按扩展名区分
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let content = if has_extension(path, "toml") {
+            toml::to_string_pretty(self).map_err(|e| e.to_string())
+        } else {
+            serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+        }
+        .map_err(|e| format!("序列化模型清单失败: {}", e))?;
+        fs::write(path, content).map_err(|e| format!("无法写入模型清单 {}: {}", path.display(), e))
+    }
+
+    /// 为清单中的所有文件路径加上目录前缀，用于移动模型文件后更新清单
+    pub fn prefix_paths(&mut self, prefix: &str) {
+        let join = |path: &mut String| *path = format!("{}/{}", prefix, path);
+        join(&mut self.weights);
+        if let Some(script) = &mut self.script {
+            join(script);
+        }
+        if let Some(labels) = &mut self.labels {
+            join(labels);
+        }
+        for sample in &mut self.samples {
+            join(&mut sample.image);
+        }
+    }
+
+    /// 转换为模型信息，base_dir 不为空时为用户模型，相对路径解析为绝对路径
+    pub fn into_model_info(self, base_dir: Option<&Path>) -> ModelInfo {
+        let resolve = |path: String| match base_dir {
+            Some(dir) if !path.is_empty() && !Path::new(&path).is_absolute() => {
+                dir.join(path).to_string_lossy().into_owned()
+            }
+            _ => path,
+        };
+
+        ModelInfo {
+            id: self.id,
+            name: self.name,
+            description: self.description,
+            path: resolve(self.weights),
+            model_type: self.model_type,
+            num_classes: self.num_classes,
+            script_path: self.script.map(&resolve).unwrap_or_default(),
+            is_active: false,
+            protocol: self.protocol,
+            backend: self.backend,
+            labels_path: self.labels.map(&resolve),
+            source: if base_dir.is_some() {
+                ModelSource::User
+            } else {
+                ModelSource::Bundled
+            },
+            weights_sha256: self.weights_sha256,
+            script_sha256: self.script_sha256,
+            helpers_sha256: self.helpers_sha256,
+            signature: self.signature,
+            task: self.task,
+            ensemble: self.ensemble,
+        }
+    }
+}
+
+fn is_classification(task: &ModelTask) -> bool {
+    *task == ModelTask::Classification
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case(extension))
+        .unwrap_or(false)
+}
+
+/// 读取目录中的所有清单文件，按文件名排序，格式错误或类别数不一致的清单跳过
+///
+/// `root` 为清单中相对路径的基准目录。
+fn read_manifest_dir(dir: &Path, root: &Path) -> Vec<(PathBuf, ModelManifest)> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            println!("跳过模型清单目录 {}: {}", dir.display(), e);
+            return Vec::new();
+        }
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| has_extension(path, "json") || has_extension(path, "toml"))
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let manifest = ModelManifest::from_file(&path)
+                .and_then(|manifest| manifest.check_labels(root).map(|_| manifest));
+            match manifest {
+                Ok(manifest) => Some((path, manifest)),
+                Err(e) => {
+                    println!("{}", e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// 从清单目录加载模型注册表，在应用启动时调用
+pub fn init_model_registry(app_handle: &AppHandle) {
+    match MODEL_REGISTRY.lock() {
+        Ok(mut registry) => registry.load(app_handle),
+        Err(_) => println!("无法获取模型注册表锁，模型列表为空"),
+    }
+}
+
 pub struct ModelRegistry {
     models: HashMap<String, ModelInfo>,
     active_model_id: String,
+    /// 用户模型对应的清单文件
+    manifest_paths: HashMap<String, PathBuf>,
 }
 
 impl ModelRegistry {
     pub fn new() -> Self {
-        let mut registry = ModelRegistry {
+        ModelRegistry {
             models: HashMap::new(),
             active_model_id: String::new(),
-        };
+            manifest_paths: HashMap::new(),
+        }
+    }
+
+    /// 重新加载内置清单和用户清单，用户清单可以覆盖同ID的内置模型
+    ///
+    /// 加载后恢复配置中保存的活跃模型，该模型不存在时使用清单中声明的默认模型。
+    pub fn load(&mut self, app_handle: &AppHandle) {
+        // (清单目录, 相对路径的基准目录, 是否为用户模型)
+        let mut sources: Vec<(PathBuf, PathBuf, bool)> = Vec::new();
+        match app_handle.path().resource_dir() {
+            // 内置模型的路径保持相对，使用时按资源目录解析
+            Ok(resource_dir) => {
+                sources.push((resource_dir.join(BUNDLED_MANIFEST_DIR), resource_dir, false))
+            }
+            Err(e) => println!("无法获取资源目录: {}", e),
+        }
+        match app_handle.path().app_data_dir() {
+            Ok(app_data_dir) => {
+                sources.push((app_data_dir.join(USER_MANIFEST_DIR), app_data_dir, true))
+            }
+            Err(e) => println!("无法获取应用数据目录: {}", e),
+        }
 
-        // 初始化默认模型
-        registry.init_default_models();
+        self.models.clear();
+        self.active_model_id.clear();
+        self.manifest_paths.clear();
+        let mut default_model_id = None;
+        for (manifest_dir, root, is_user) in &sources {
+            for (manifest_path, manifest) in read_manifest_dir(manifest_dir, root) {
+                if manifest.default {
+                    default_model_id = Some(manifest.id.clone());
+                }
+                let model = manifest.into_model_info(is_user.then_some(root.as_path()));
+                if self.models.contains_key(&model.id) {
+                    println!(
+                        "模型 {} 被 {} 中的清单覆盖",
+                        model.id,
+                        manifest_dir.display()
+                    );
+                }
+                if model.source == ModelSource::User {
+                    self.manifest_paths.insert(model.id.clone(), manifest_path);
+                }
+                self.models.insert(model.id.clone(), model);
+            }
+        }
+        self.remove_invalid_ensembles();
+        println!("已加载 {} 个模型", self.models.len());
 
-        registry
+        let saved_model_id = constants::get_config().active_model_id.clone();
+        let active_model_id = saved_model_id
+            .into_iter()
+            .chain(default_model_id)
+            .find(|id| self.models.contains_key(id))
+            .or_else(|| self.models.keys().min().cloned());
+        if let Some(model_id) = active_model_id {
+            if let Ok(model) = self.set_active_model(&model_id) {
+                println!("当前活跃模型: {}", model.name);
+            }
+        }
     }
 
-    fn init_default_models(&mut self) {
-        // 添加 CIFAR-10 模型
-        let cifar_id = String::from("CIFAR_MODEL_ID");
-        let cifar_model = ModelInfo {
-            id: cifar_id.clone(),
-            name: "CIFAR-10 识别模型".to_string(),
-            description: "用于识别10种常见物体的模型，包括飞机、汽车、鸟类等".to_string(),
-            path: "resources/models/cifar10_model.pth".to_string(),
-            model_type: "ResNet34".to_string(),
-            num_classes: 10,
-            script_path: "resources/scripts/cifar10_val.py".to_string(),
-            is_active: false,
-        };
-        self.models.insert(cifar_id.clone(), cifar_model);
-
-        // 添加中医药模型
-        let medicine_id = String::from("MEDICINE_MODEL_ID");
-        let medicine_model = ModelInfo {
-            id: medicine_id.clone(),
-            name: "中医药材识别模型".to_string(),
-            description: "用于识别中医药材的专用模型，包含上百种药材".to_string(),
-            path: "resources/models/medicine_model.pth".to_string(),
-            model_type: "MobileNetV3-Small".to_string(),
-            num_classes: 163,
-            script_path: "resources/scripts/medicine_val.py".to_string(),
-            is_active: true,
-        };
-        self.models.insert(medicine_id.clone(), medicine_model);
-        // 添加蘑菇模型
-        let mushroom_id = String::from("MUSHROOM_MODEL_ID");
-        let mushroom_model = ModelInfo {
-            id: mushroom_id.clone(),
-            name: "蘑菇识别模型".to_string(),
-            description: "用于识别常见蘑菇品种的专用模型，可识别100种蘑菇，包括食用菌和有毒品种"
-                .to_string(),
-            path: "resources/models/mushroom_model.pth".to_string(),
-            model_type: "ResNet50".to_string(),
-            num_classes: 100,
-            script_path: "resources/scripts/mushroom_val.py".to_string(),
-            is_active: false,
+    /// 移除成员无效的集成模型，成员必须是已注册的非集成分类模型
+    fn remove_invalid_ensembles(&mut self) {
+        let invalid: Vec<(String, String)> = self
+            .models
+            .values()
+            .filter_map(|model| {
+                let error = self.check_ensemble(model).err()?;
+                Some((model.id.clone(), error))
+            })
+            .collect();
+        for (model_id, error) in invalid {
+            println!("跳过集成模型 {}: {}", model_id, error);
+            self.models.remove(&model_id);
+            self.manifest_paths.remove(&model_id);
+        }
+    }
+
+    /// 检查集成模型的定义，非集成模型总是通过
+    pub fn check_ensemble(&self, model: &ModelInfo) -> Result<(), String> {
+        let Some(ensemble) = &model.ensemble else {
+            return Ok(());
         };
-        self.models.insert(mushroom_id.clone(), mushroom_model);
-        // 设置默认活跃模型
-        self.active_model_id = medicine_id;
+        if model.labels_path.is_none() {
+            return Err("集成模型需要类别文件，用于对齐各成员的类别".to_string());
+        }
+        if model.task != ModelTask::Classification {
+            return Err("集成模型只支持单标签分类".to_string());
+        }
+        if ensemble.members.is_empty() {
+            return Err("集成模型没有成员".to_string());
+        }
+        for (index, member) in ensemble.members.iter().enumerate() {
+            if ensemble.members[..index]
+                .iter()
+                .any(|other| other.model_id == member.model_id)
+            {
+                return Err(format!("成员重复: {}", member.model_id));
+            }
+            if !member.weight.is_finite() || member.weight <= 0.0 {
+                return Err(format!(
+                    "成员 {} 的权重应为正数: {}",
+                    member.model_id, member.weight
+                ));
+            }
+            let member_model = self
+                .models
+                .get(&member.model_id)
+                .ok_or(format!("成员模型不存在: {}", member.model_id))?;
+            if member_model.ensemble.is_some() {
+                return Err(format!("成员不能是集成模型: {}", member.model_id));
+            }
+            if member_model.task != ModelTask::Classification {
+                return Err(format!("成员不是单标签分类模型: {}", member.model_id));
+            }
+        }
+        Ok(())
+    }
+
+    /// 以指定模型为成员的集成模型ID
+    pub fn ensembles_using(&self, model_id: &str) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .models
+            .values()
+            .filter(|model| {
+                model.ensemble.as_ref().is_some_and(|ensemble| {
+                    ensemble
+                        .members
+                        .iter()
+                        .any(|member| member.model_id == model_id)
+                })
+            })
+            .map(|model| model.id.clone())
+            .collect();
+        ids.sort();
+        ids
     }
 
     pub fn get_models(&self) -> Vec<ModelInfo> {
         self.models.values().cloned().collect()
     }
 
+    pub fn get_model(&self, model_id: &str) -> Option<ModelInfo> {
+        self.models.get(model_id).cloned()
+    }
+
+    /// 用户模型的清单文件，内置模型返回 None
+    pub fn get_manifest_path(&self, model_id: &str) -> Option<PathBuf> {
+        self.manifest_paths.get(model_id).cloned()
+    }
+
+    /// 注册新导入的模型，没有活跃模型时将其设为活跃
+    pub fn add_model(&mut self, model: ModelInfo, manifest_path: PathBuf) -> Result<(), String> {
+        if self.models.contains_key(&model.id) {
+            return Err(format!("模型ID已存在: {}", model.id));
+        }
+
+        let model_id = model.id.clone();
+        self.models.insert(model_id.clone(), model);
+        self.manifest_paths.insert(model_id.clone(), manifest_path);
+        if self.active_model_id.is_empty() {
+            self.set_active_model(&model_id)?;
+        }
+        Ok(())
+    }
+
+    /// 替换已注册模型的信息，保留其活跃状态
+    pub fn update_model(&mut self, mut model: ModelInfo) -> Result<ModelInfo, String> {
+        let current = self
+            .models
+            .get_mut(&model.id)
+            .ok_or(format!("模型ID不存在: {}", model.id))?;
+        model.is_active = current.is_active;
+        *current = model;
+        Ok(current.clone())
+    }
+
     pub fn get_active_model(&self) -> Option<ModelInfo> {
         self.models.get(&self.active_model_id).cloned()
     }
@@ -116,6 +483,230 @@ impl ModelRegistry {
         }
 
         self.models.remove(model_id);
+        self.manifest_paths.remove(model_id);
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::inference_result::EnsembleMethod;
+
+    fn write_manifest(name: &str, content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "vision_match_manifest_{}_{}",
+            std::process::id(),
+            name
+        ));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn load(name: &str, content: &str) -> Result<ModelManifest, String> {
+        let path = write_manifest(name, content);
+        let manifest = ModelManifest::from_file(&path);
+        let _ = fs::remove_file(&path);
+        manifest
+    }
+
+    #[test]
+    fn json_manifest_is_parsed_with_defaults() {
+        let manifest = load(
+            "demo.json",
+            r#"{
+                "id": "demo",
+                "name": "演示模型",
+                "weights": "models/demo.pth",
+                "num_classes": 3,
+                "script": "scripts/demo_val.py",
+                "labels": "labels/demo.json"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(manifest.id, "demo");
+        assert_eq!(manifest.num_classes, 3);
+        assert_eq!(manifest.protocol, WorkerProtocol::Json);
+        assert_eq!(manifest.task, ModelTask::Classification);
+        assert!(manifest.backend.is_none() && !manifest.default);
+
+        let info = manifest.into_model_info(Some(Path::new("/data")));
+        assert_eq!(info.source, ModelSource::User);
+        assert_eq!(
+            Path::new(&info.path),
+            Path::new("/data").join("models/demo.pth")
+        );
+        assert_eq!(
+            info.labels_path.map(PathBuf::from),
+            Some(Path::new("/data").join("labels/demo.json"))
+        );
+    }
+
+    #[test]
+    fn toml_manifest_is_parsed() {
+        let manifest = load(
+            "color.toml",
+            r#"
+id = "color"
+name = "颜色识别"
+weights = "models/color.onnx"
+num_classes = 4
+backend = "onnx"
+task = "multi_label"
+protocol = "legacy"
+default = true
+
+[[samples]]
+image = "samples/red.jpg"
+prediction = "红色"
+confidence = 0.9
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(manifest.backend, Some(BackendKind::Onnx));
+        assert_eq!(manifest.task, ModelTask::MultiLabel);
+        assert_eq!(manifest.protocol, WorkerProtocol::Legacy);
+        assert!(manifest.default);
+        assert_eq!(manifest.samples[0].confidence, Some(0.9));
+
+        let info = manifest.into_model_info(None);
+        assert_eq!(info.source, ModelSource::Bundled);
+        assert_eq!(info.path, "models/color.onnx");
+    }
+
+    #[test]
+    fn ensemble_manifest_needs_backend_and_members() {
+        let manifest = load(
+            "ensemble.toml",
+            r#"
+id = "ensemble"
+name = "集成模型"
+num_classes = 3
+backend = "ensemble"
+
+[ensemble]
+method = "majority_vote"
+members = [{ model_id = "a" }, { model_id = "b", weight = 2.0 }]
+"#,
+        )
+        .unwrap();
+        let ensemble = manifest.ensemble.unwrap();
+        assert_eq!(ensemble.method, EnsembleMethod::MajorityVote);
+        assert_eq!(ensemble.members[0].weight, 1.0);
+        assert_eq!(ensemble.members[1].weight, 2.0);
+
+        let missing_backend = r#"
+id = "ensemble"
+name = "集成模型"
+num_classes = 3
+
+[ensemble]
+members = [{ model_id = "a" }]
+"#;
+        assert!(load("no_backend.toml", missing_backend).is_err());
+        let missing_members =
+            r#"{"id": "e", "name": "e", "num_classes": 3, "backend": "ensemble"}"#;
+        assert!(load("no_members.json", missing_members).is_err());
+    }
+
+    #[test]
+    fn invalid_manifests_are_rejected() {
+        let missing_id = r#"{"id": " ", "name": "m", "weights": "m.pth", "num_classes": 1}"#;
+        assert!(load("missing_id.json", missing_id).is_err());
+        let missing_weights = r#"{"id": "m", "name": "m", "num_classes": 1}"#;
+        assert!(load("missing_weights.json", missing_weights).is_err());
+        assert!(load("syntax.json", "{ not json").is_err());
+        assert!(ModelManifest::from_file(Path::new("/nonexistent/manifest.json")).is_err());
+    }
+
+    #[test]
+    fn label_count_must_match_num_classes() {
+        let labels = write_manifest(
+            "labels.json",
+            r#"{"classes": [{"id": "a", "names": {"en": "a"}}, {"id": "b", "names": {"en": "b"}}]}"#,
+        );
+        let root = labels.parent().unwrap();
+        let manifest = |num_classes: u32| {
+            let content = serde_json::json!({
+                "id": "m",
+                "name": "m",
+                "weights": "m.onnx",
+                "num_classes": num_classes,
+                "labels": labels.file_name().unwrap().to_str().unwrap(),
+            });
+            load("labels_manifest.json", &content.to_string()).unwrap()
+        };
+
+        assert!(manifest(2).check_labels(root).is_ok());
+        assert!(manifest(0).check_labels(root).is_ok());
+        assert!(manifest(3).check_labels(root).is_err());
+        let _ = fs::remove_file(&labels);
+        assert!(manifest(2).check_labels(root).is_err());
+    }
+
+    #[test]
+    fn bundled_manifests_match_their_labels() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let manifests = read_manifest_dir(&root.join(BUNDLED_MANIFEST_DIR), root);
+        assert!(!manifests.is_empty());
+        for (path, manifest) in &manifests {
+            assert!(manifest.labels.is_some(), "{} 没有类别文件", path.display());
+        }
+        let files = fs::read_dir(root.join(BUNDLED_MANIFEST_DIR))
+            .unwrap()
+            .count();
+        assert_eq!(manifests.len(), files, "有内置清单未能加载");
+    }
+
+    #[test]
+    fn bundled_script_digests_are_current() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        for (path, manifest) in read_manifest_dir(&root.join(BUNDLED_MANIFEST_DIR), root) {
+            let Some(script) = &manifest.script else {
+                continue;
+            };
+            let expected = manifest
+                .script_sha256
+                .as_deref()
+                .unwrap_or_else(|| panic!("{} 没有记录推理脚本的摘要", path.display()));
+            let actual = crate::utils::file::calculate_path_hash(&root.join(script)).unwrap();
+            assert_eq!(
+                actual,
+                expected.to_ascii_lowercase(),
+                "{} 的 script_sha256 与 {} 不一致，修改脚本后需要更新清单",
+                path.display(),
+                script
+            );
+
+            let script_dir = root.join(script).parent().unwrap().to_path_buf();
+            assert_eq!(
+                manifest
+                    .helpers_sha256
+                    .as_deref()
+                    .map(str::to_ascii_lowercase),
+                Some(crate::services::integrity::helpers_digest(&script_dir).unwrap()),
+                "{} 的 helpers_sha256 与辅助脚本不一致，修改辅助脚本后需要更新清单",
+                path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn saved_manifest_round_trips() {
+        let manifest = load(
+            "round_trip.json",
+            r#"{"id": "m", "name": "m", "weights": "m.onnx", "num_classes": 2, "weights_sha256": "abcd"}"#,
+        )
+        .unwrap();
+        for name in ["saved.json", "saved.toml"] {
+            let path = write_manifest(name, "");
+            manifest.save(&path).unwrap();
+            let saved = ModelManifest::from_file(&path).unwrap();
+            let _ = fs::remove_file(&path);
+            assert_eq!(saved.weights_sha256.as_deref(), Some("abcd"));
+            assert_eq!(saved.weights, "m.onnx");
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use super::db_client::{get_database, DbError};
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{self, doc, oid::ObjectId, DateTime, Document},
+    options::{FindOptions, UpdateOptions},
+};
+use serde::{Deserialize, Serialize};
+
+//
+// 第一部分: 数据模型定义
+//
+
+// 图片特征向量 - 每张图片在每个模型下最多一条
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImageEmbedding {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub image_id: ObjectId,   // images集合中的ObjectId引用
+    pub model_id: String,     // 提取特征向量的模型ID
+    pub vector: Vec<f32>,     // 模型倒数第二层的输出
+    pub created_at: DateTime, // 首次保存时间
+    pub updated_at: DateTime, // 最近一次更新时间
+}
+
+//
+// 第二部分: 仓储实现
+//
+
+/// 图片特征向量操作工具
+pub struct EmbeddingRepository;
+
+impl EmbeddingRepository {
+    const COLLECTION_NAME: &'static str = "embeddings";
+
+    /// 获取特征向量集合
+    fn get_collection() -> Result<mongodb::Collection<Document>, DbError> {
+        let db = get_database()?;
+        Ok(db.collection(Self::COLLECTION_NAME))
+    }
+
+    /// 保存图片在指定模型下的特征向量，已有记录时覆盖
+    pub async fn upsert(image_id: ObjectId, model_id: &str, vector: &[f32]) -> Result<(), DbError> {
+        let collection = Self::get_collection()?;
+
+        let now = bson::DateTime::now();
+        let filter = doc! { "image_id": image_id, "model_id": model_id };
+        let update = doc! {
+            "$set": { "vector": vector, "updated_at": now },
+            "$setOnInsert": { "created_at": now }
+        };
+        let options = UpdateOptions::builder().upsert(true).build();
+
+        collection
+            .update_one(filter, update)
+            .with_options(options)
+            .await?;
+        Ok(())
+    }
+
+    /// 查找图片的所有特征向量，最近更新的在前
+    pub async fn find_by_image(image_id: &str) -> Result<Vec<ImageEmbedding>, DbError> {
+        let collection = Self::get_collection()?;
+
+        let oid = ObjectId::parse_str(image_id)
+            .map_err(|_| DbError::InvalidObjectId(image_id.to_string()))?;
+        let options = FindOptions::builder()
+            .sort(doc! { "updated_at": -1 })
+            .build();
+
+        let cursor = collection
+            .find(doc! { "image_id": oid })
+            .with_options(options)
+            .await?;
+        Self::collect(cursor).await
+    }
+
+    /// 查找指定模型的所有特征向量，用于构建检索索引
+    pub async fn find_by_model(model_id: &str) -> Result<Vec<ImageEmbedding>, DbError> {
+        let collection = Self::get_collection()?;
+
+        let cursor = collection.find(doc! { "model_id": model_id }).await?;
+        Self::collect(cursor).await
+    }
+
+    /// 删除指定模型的所有特征向量，返回删除的数量
+    pub async fn delete_by_model(model_id: &str) -> Result<u64, DbError> {
+        let collection = Self::get_collection()?;
+
+        let result = collection
+            .delete_many(doc! { "model_id": model_id })
+            .await?;
+        Ok(result.deleted_count)
+    }
+
+    async fn collect(cursor: mongodb::Cursor<Document>) -> Result<Vec<ImageEmbedding>, DbError> {
+        let docs: Vec<Document> = cursor.try_collect().await?;
+
+        // 手动转换文档到结构体
+        let mut results = Vec::with_capacity(docs.len());
+        for doc in docs {
+            let embedding: ImageEmbedding =
+                bson::from_document(doc).map_err(DbError::DeserializationError)?;
+            results.push(embedding);
+        }
+
+        Ok(results)
+    }
+}
@@ -2,7 +2,7 @@ use super::db_client::{get_database, DbError};
 use futures::TryStreamExt;
 use mongodb::{
     bson::{self, doc, oid::ObjectId, to_bson, DateTime, Document},
-    options::FindOptions,
+    options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument},
 };
 use serde::{Deserialize, Serialize};
 
@@ -26,6 +26,26 @@ pub struct ImageHistory {
     pub result: Option<serde_json::Value>, // 如果提供，必须是对象，存储识别结果
     pub error_message: Option<String>, // 如果处理失败，记录错误信息
     pub updated_at: Option<DateTime>, // 更新时间
+    // 后台任务队列字段
+    pub model_id: Option<String>,      // 排队任务使用的模型ID
+    pub started_at: Option<DateTime>,  // 开始处理时间
+    pub finished_at: Option<DateTime>, // 处理结束时间
+    pub duration_ms: Option<i64>,      // 处理耗时（毫秒）
+    pub attempts: Option<i32>,         // 任务被领取的次数
+    // 多模型对比时同一组记录共享的ID
+    pub group_id: Option<ObjectId>,
+}
+
+// 一组关联记录中的一条，由 add_group 补全用户、图片和时间
+#[derive(Debug, Clone)]
+pub struct GroupedHistory {
+    pub model_id: String,
+    pub model_name: String,
+    pub status: RecognitionStatus,
+    pub result: Option<serde_json::Value>,
+    pub confidence: Option<f64>,
+    pub error_message: Option<String>,
+    pub duration_ms: Option<i64>,
 }
 
 // 识别状态枚举 - 确保与MongoDB枚举值匹配
@@ -85,6 +105,12 @@ impl ImageHistoryRepository {
             error_message: error_message.map(String::from),
             created_at: bson::DateTime::now(),
             updated_at: None,
+            model_id: None,
+            started_at: None,
+            finished_at: None,
+            duration_ms: None,
+            attempts: None,
+            group_id: None,
         };
 
         // 将结构转换为BSON Document
@@ -98,6 +124,157 @@ impl ImageHistoryRepository {
             .ok_or_else(|| DbError::Other("无法获取插入的ID".to_string()))
     }
 
+    /// 添加待处理的识别任务，由后台任务队列执行
+    pub async fn enqueue(
+        mac_address: &str,
+        image_id: ObjectId,
+        model_id: &str,
+        model_name: &str,
+    ) -> Result<ObjectId, DbError> {
+        let collection = Self::get_collection()?;
+
+        let history = ImageHistory {
+            id: None,
+            mac_address: mac_address.to_string(),
+            image_id,
+            model_name: model_name.to_string(),
+            status: RecognitionStatus::Pending,
+            confidence: None,
+            result: None,
+            error_message: None,
+            created_at: bson::DateTime::now(),
+            updated_at: None,
+            model_id: Some(model_id.to_string()),
+            started_at: None,
+            finished_at: None,
+            duration_ms: None,
+            attempts: Some(0),
+            group_id: None,
+        };
+
+        let doc = mongodb::bson::to_document(&history).map_err(DbError::SerializationError)?;
+
+        let result = collection.insert_one(doc).await?;
+
+        result
+            .inserted_id
+            .as_object_id()
+            .ok_or_else(|| DbError::Other("无法获取插入的ID".to_string()))
+    }
+
+    /// 领取最早创建的待处理任务，原子地标记为处理中
+    pub async fn claim_next_pending() -> Result<Option<ImageHistory>, DbError> {
+        let collection = Self::get_collection()?;
+
+        let now = bson::DateTime::now();
+        let filter = doc! {
+            "status": to_bson(&RecognitionStatus::Pending).map_err(DbError::SerializationError)?
+        };
+        let update = doc! {
+            "$set": {
+                "status": to_bson(&RecognitionStatus::Processing).map_err(DbError::SerializationError)?,
+                "started_at": now,
+                "updated_at": now
+            },
+            "$inc": { "attempts": 1 }
+        };
+        let options = FindOneAndUpdateOptions::builder()
+            .sort(doc! { "created_at": 1 })
+            .return_document(ReturnDocument::After)
+            .build();
+
+        let result = collection
+            .find_one_and_update(filter, update)
+            .with_options(options)
+            .await?;
+
+        match result {
+            Some(doc) => Ok(Some(
+                bson::from_document(doc).map_err(DbError::DeserializationError)?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// 记录任务结束时的状态、结果和耗时
+    pub async fn finish_job(
+        id: ObjectId,
+        status: RecognitionStatus,
+        result: Option<serde_json::Value>,
+        confidence: Option<f64>,
+        error_message: Option<&str>,
+        started_at: DateTime,
+    ) -> Result<i64, DbError> {
+        let collection = Self::get_collection()?;
+
+        let now = bson::DateTime::now();
+        let duration_ms = now.timestamp_millis() - started_at.timestamp_millis();
+        let mut update_doc = doc! {
+            "status": to_bson(&status).map_err(DbError::SerializationError)?,
+            "finished_at": now,
+            "duration_ms": duration_ms,
+            "updated_at": now
+        };
+
+        if let Some(res) = result {
+            update_doc.insert(
+                "result",
+                to_bson(&res).map_err(DbError::SerializationError)?,
+            );
+        }
+
+        if let Some(conf) = confidence {
+            update_doc.insert("confidence", conf);
+        }
+
+        if let Some(err) = error_message {
+            update_doc.insert("error_message", err);
+        }
+
+        collection
+            .update_one(doc! { "_id": id }, doc! { "$set": update_doc })
+            .await?;
+
+        Ok(duration_ms)
+    }
+
+    /// 处理上次退出时仍在处理中的任务
+    ///
+    /// 领取次数达到上限的任务标记为错误，避免反复中断的任务无限重试；其余任务重新排队。
+    /// 返回 (重新排队的数量, 标记为错误的数量)。
+    pub async fn reset_processing(max_attempts: i32) -> Result<(u64, u64), DbError> {
+        let collection = Self::get_collection()?;
+
+        let processing =
+            to_bson(&RecognitionStatus::Processing).map_err(DbError::SerializationError)?;
+        let now = bson::DateTime::now();
+
+        let abandoned = collection
+            .update_many(
+                doc! { "status": processing.clone(), "attempts": { "$gte": max_attempts } },
+                doc! { "$set": {
+                    "status": to_bson(&RecognitionStatus::Error).map_err(DbError::SerializationError)?,
+                    "error_message": format!("任务已中断 {} 次，不再重试", max_attempts),
+                    "finished_at": now,
+                    "updated_at": now
+                } },
+            )
+            .await?;
+
+        let requeued = collection
+            .update_many(
+                doc! { "status": processing },
+                doc! { "$set": {
+                    "status": to_bson(&RecognitionStatus::Pending).map_err(DbError::SerializationError)?,
+                    "started_at": null,
+                    "updated_at": now
+                } },
+            )
+            .await?;
+
+        Ok((requeued.modified_count, abandoned.modified_count))
+    }
+
     /// 根据ID查找历史记录
     pub async fn find_by_id(id: &str) -> Result<Option<ImageHistory>, DbError> {
         let collection = Self::get_collection()?;
@@ -114,6 +291,78 @@ impl ImageHistoryRepository {
         }
     }
 
+    /// 添加一组关联的历史记录（如同一张图片的多模型对比），返回与输入顺序一致的记录ID
+    pub async fn add_group(
+        mac_address: &str,
+        image_id: ObjectId,
+        group_id: ObjectId,
+        entries: Vec<GroupedHistory>,
+    ) -> Result<Vec<ObjectId>, DbError> {
+        let collection = Self::get_collection()?;
+
+        let now = bson::DateTime::now();
+        let mut docs = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let history = ImageHistory {
+                id: None,
+                mac_address: mac_address.to_string(),
+                image_id,
+                model_name: entry.model_name,
+                status: entry.status,
+                confidence: entry.confidence,
+                result: entry.result,
+                error_message: entry.error_message,
+                created_at: now,
+                updated_at: None,
+                model_id: Some(entry.model_id),
+                started_at: None,
+                finished_at: None,
+                duration_ms: entry.duration_ms,
+                attempts: None,
+                group_id: Some(group_id),
+            };
+            docs.push(mongodb::bson::to_document(&history).map_err(DbError::SerializationError)?);
+        }
+
+        let result = collection.insert_many(docs).await?;
+
+        // inserted_ids 的键为输入中的序号
+        let mut ids = Vec::with_capacity(result.inserted_ids.len());
+        for index in 0..result.inserted_ids.len() {
+            let id = result
+                .inserted_ids
+                .get(&index)
+                .and_then(|id| id.as_object_id())
+                .ok_or_else(|| DbError::Other("无法获取插入的ID".to_string()))?;
+            ids.push(id);
+        }
+        Ok(ids)
+    }
+
+    /// 查找同一组的历史记录，按插入顺序排列
+    pub async fn find_by_group(group_id: &str) -> Result<Vec<ImageHistory>, DbError> {
+        let collection = Self::get_collection()?;
+
+        let oid = ObjectId::parse_str(group_id)
+            .map_err(|_| DbError::InvalidObjectId(group_id.to_string()))?;
+
+        let filter = doc! { "group_id": oid };
+        let options = FindOptions::builder().sort(doc! { "_id": 1 }).build();
+
+        let cursor = collection.find(filter).with_options(options).await?;
+        let docs: Vec<Document> = cursor.try_collect().await?;
+
+        // 手动转换文档到结构体
+        let mut results = Vec::with_capacity(docs.len());
+        for doc in docs {
+            let history: ImageHistory =
+                bson::from_document(doc).map_err(DbError::DeserializationError)?;
+            results.push(history);
+        }
+
+        Ok(results)
+    }
+
     /// 根据图像ID查找历史记录
     pub async fn find_by_image_id(image_id: &str) -> Result<Vec<ImageHistory>, DbError> {
         let collection = Self::get_collection()?;
@@ -236,6 +485,62 @@ impl ImageHistoryRepository {
         Ok(results)
     }
 
+    /// 查找结果中包含指定类别的历史记录
+    ///
+    /// 类别可以是ID或名称，匹配分类结果的预测类别、多标签结果的阳性类别和检测结果的目标。
+    pub async fn find_by_label_and_mac(
+        mac_address: &str,
+        label: &str,
+        limit: Option<i64>,
+    ) -> Result<Vec<ImageHistory>, DbError> {
+        let collection = Self::get_collection()?;
+
+        let mut filter = Self::label_filter(label);
+        filter.insert("mac_address", mac_address);
+
+        let options = FindOptions::builder()
+            .sort(doc! { "created_at": -1 })
+            .limit(limit)
+            .build();
+
+        let cursor = collection.find(filter).with_options(options).await?;
+        let docs: Vec<Document> = cursor.try_collect().await?;
+
+        let mut results = Vec::with_capacity(docs.len());
+        for doc in docs {
+            let history: ImageHistory =
+                bson::from_document(doc).map_err(DbError::DeserializationError)?;
+            results.push(history);
+        }
+
+        Ok(results)
+    }
+
+    /// 统计结果中包含指定类别的历史记录数量
+    pub async fn count_by_label_and_mac(mac_address: &str, label: &str) -> Result<u64, DbError> {
+        let collection = Self::get_collection()?;
+
+        let mut filter = Self::label_filter(label);
+        filter.insert("mac_address", mac_address);
+
+        let count = collection.count_documents(filter).await?;
+        Ok(count)
+    }
+
+    /// 结果中包含指定类别（ID或名称）的查询条件
+    fn label_filter(label: &str) -> Document {
+        doc! {
+            "$or": [
+                { "result.class_id": label },
+                { "result.prediction": label },
+                { "result.positive_labels.id": label },
+                { "result.positive_labels.label": label },
+                { "result.detections.class_id": label },
+                { "result.detections.label": label },
+            ]
+        }
+    }
+
     /// 更新历史记录的状态和结果
     pub async fn update_status(
         id: &str,
@@ -1,3 +1,4 @@
 pub mod db_client;
+pub mod embeddings_collection;
 pub mod histories_collection;
 pub mod images_collection;
@@ -5,13 +5,24 @@ pub mod models;
 pub mod services;
 pub mod utils;
 // 核心API
+pub use commands::batch_processing::{cancel_batch, process_batch};
 pub use commands::file_management::save_uploaded_image;
 pub use commands::image_processing::process_image;
-pub use commands::model_management::{get_available_models, switch_model};
+pub use commands::model_comparison::compare_models;
+pub use commands::model_management::{
+    diagnose_models, export_model_package, get_available_models, get_label_locales,
+    get_model_status, import_model, install_model_package, remove_model, set_label_locale,
+    switch_model, update_model_metadata, verify_model_package,
+};
+pub use commands::python_environment::{
+    discover_python_interpreters, set_python_executable, setup_python_venv,
+};
+pub use commands::recognition_jobs::enqueue_recognition;
 pub use commands::save_image_history::save_image_history;
 // 简单的CRUD
 pub use commands::cruds::{
-    delete_history, get_history_by_model, get_history_by_status, get_history_count,
+    count_history_by_label, delete_history, find_similar_images, get_history_by_label,
+    get_history_by_model, get_history_by_status, get_history_count, get_history_group,
     get_user_history,
 };
 //初始化配置文件
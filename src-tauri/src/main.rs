@@ -1,6 +1,11 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 use once_cell::sync::Lazy;
+use tauri::RunEvent;
 use vision_match::config::constants;
+use vision_match::config::models::init_model_registry;
+use vision_match::services::job_queue::run_job_queue;
+use vision_match::services::labels;
+use vision_match::services::worker_pool::{run_idle_reaper, shutdown_worker_pool};
 use vision_match::*;
 
 // 环境变量初始化 - 仅处理环境变量，不加载配置
@@ -28,6 +33,10 @@ async fn main() {
 
             // 初始化配置
             let config = constants::init_config(Some(&app_handle));
+            labels::set_locale(&config.label_locale);
+
+            // 从模型清单加载模型列表，并恢复上次选择的活跃模型
+            init_model_registry(&app_handle);
 
             // 初始化MongoDB连接
             let mongodb_uri = config.mongodb_uri.clone();
@@ -41,20 +50,87 @@ async fn main() {
                 }
             });
 
+            // 定期关闭空闲的模型进程，模型进程运行在 Tauri 的异步运行时上
+            tauri::async_runtime::spawn(run_idle_reaper());
+
+            // 后台识别队列，数据库连接完成后恢复上次中断的任务
+            tauri::async_runtime::spawn(run_job_queue(app_handle.clone()));
+
+            // 收到 Ctrl+C / SIGTERM 时走正常退出流程，确保Python子进程被回收
+            let exit_handle = app_handle.clone();
+            tokio::spawn(async move {
+                wait_for_shutdown_signal().await;
+                println!("收到退出信号，正在关闭应用");
+                exit_handle.exit(0);
+            });
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             process_image,
+            compare_models,
+            process_batch,
+            cancel_batch,
+            enqueue_recognition,
             save_uploaded_image,
             save_image_history,
             get_available_models,
             switch_model,
+            get_model_status,
+            import_model,
+            remove_model,
+            update_model_metadata,
+            verify_model_package,
+            install_model_package,
+            export_model_package,
+            get_label_locales,
+            set_label_locale,
+            diagnose_models,
+            discover_python_interpreters,
+            set_python_executable,
+            setup_python_venv,
             delete_history,
             get_history_by_model,
+            get_history_by_label,
+            count_history_by_label,
+            find_similar_images,
+            get_history_group,
             get_history_by_status,
             get_history_count,
             get_user_history,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app_handle, event| {
+            if let RunEvent::Exit = event {
+                // 应用退出前关闭并回收所有模型进程
+                shutdown_worker_pool();
+            }
+        });
+}
+
+/// 等待进程退出信号
+#[cfg(unix)]
+async fn wait_for_shutdown_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigterm = match signal(SignalKind::terminate()) {
+        Ok(sigterm) => sigterm,
+        Err(e) => {
+            eprintln!("无法监听SIGTERM: {}", e);
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        }
+    };
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = sigterm.recv() => {}
+    }
+}
+
+/// 等待进程退出信号
+#[cfg(not(unix))]
+async fn wait_for_shutdown_signal() {
+    let _ = tokio::signal::ctrl_c().await;
 }
@@ -1,3 +1,4 @@
+use crate::models::inference_result::ModelTask;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 /// API返回的历史记录DTO（数据传输对象）
@@ -11,6 +12,8 @@ pub struct HistoryDto {
     pub image_id: String,
     /// 模型名称
     pub model_name: String,
+    /// 识别结果类型，旧记录没有记录时按分类结果处理
+    pub task: ModelTask,
     /// 识别结果 (如果有)
     pub result: Option<Value>,
     /// 结果置信度
@@ -19,10 +22,18 @@ pub struct HistoryDto {
     pub status: String,
     /// 错误信息(如果有)
     pub error_message: Option<String>,
+    /// 开始处理时间（毫秒时间戳），后台任务才有
+    pub started_at: Option<i64>,
+    /// 处理结束时间（毫秒时间戳）
+    pub finished_at: Option<i64>,
+    /// 处理耗时（毫秒）
+    pub duration_ms: Option<i64>,
+    /// 多模型对比保存的记录所属的组ID
+    pub group_id: Option<String>,
 }
 
 /// 图片信息DTO
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImageDto {
     /// ID - 字符串格式
     pub id: String,
@@ -36,6 +47,19 @@ pub struct ImageDto {
     pub format: Option<String>,
 }
 
+/// 相似图片检索结果
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SimilarImageDto {
+    /// 图片ID - 字符串格式
+    pub image_id: String,
+    /// 提取特征向量的模型ID
+    pub model_id: String,
+    /// 与查询图片的余弦相似度，范围 -1~1
+    pub score: f32,
+    /// 图片信息，图片记录已被删除时为空
+    pub image: Option<ImageDto>,
+}
+
 /// API返回的完整历史记录（含图片信息）
 #[derive(Debug, Serialize, Deserialize)]
 pub struct HistoryWithImageDto {
@@ -1,8 +1,20 @@
+use crate::db::histories_collection::RecognitionStatus;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+/// 识别结果
+///
+/// `task` 区分结果类型：分类结果的主要内容为 `prediction` 和各类别概率；检测结果的主要内容为
+/// `detections`，`prediction`、`confidence` 为得分最高的目标，`class_probabilities` 和
+/// `top_classes` 为各类别目标的最高得分，没有检测到目标时 `prediction` 为空。多标签结果的
+/// 主要内容为 `positive_labels`，`prediction`、`confidence` 为得分最高的阳性类别，没有阳性类别时
+/// `prediction` 为空。
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelResult {
+    /// 结果类型，旧版结果没有该字段，按分类结果处理
+    #[serde(default)]
+    pub task: ModelTask,
+
     /// 模型预测的主要结果类别
     pub prediction: String,
 
@@ -15,6 +27,352 @@ pub struct ModelResult {
     /// 模型类型信息，可选
     #[serde(skip_serializing_if = "Option::is_none")]
     pub model_type: Option<String>,
+
+    /// 主要结果类别的稳定ID，模型带有类别文件时提供
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class_id: Option<String>,
+
+    /// 置信度最高的N个类别，按概率从高到低排列，模型带有类别文件时提供
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub top_classes: Vec<ClassScore>,
+
+    /// 最高置信度低于识别选项中的 `unknown_threshold`，`prediction` 只是最可能的类别
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub uncertain: bool,
+
+    /// 识别时使用的选项，随结果保存到历史记录
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<RecognitionOptions>,
+
+    /// 检测到的目标，按得分从高到低排列，只有检测结果有
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub detections: Vec<Detection>,
+
+    /// 得分达到类别阈值的类别，按得分从高到低排列，只有多标签结果有
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub positive_labels: Vec<ClassScore>,
+
+    /// 类激活热力图（叠加在原图上的PNG），路径相对应用数据目录，请求解释时才有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heatmap_path: Option<String>,
+
+    /// 模型倒数第二层输出的特征向量，请求提取时才有；单独保存，不写入历史记录
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<Vec<f32>>,
+}
+
+/// 模型的任务类型
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelTask {
+    /// 单标签分类，整张图片对应一个类别
+    #[default]
+    Classification,
+    /// 目标检测，图片中的每个目标有各自的类别和位置
+    Detection,
+    /// 多标签分类，各类别独立打分（sigmoid），得分达到类别阈值的都是阳性
+    MultiLabel,
+}
+
+/// 检测到的一个目标
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Detection {
+    /// 模型输出中的类别索引，脚本直接给出名称时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class_index: Option<u32>,
+    /// 稳定的类别ID，模型带有类别文件时提供
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class_id: Option<String>,
+    /// 当前语言下的类别名称
+    pub label: String,
+    pub score: f32,
+    #[serde(rename = "box")]
+    pub bbox: BoundingBox,
+}
+
+/// 目标的外接矩形，坐标为相对图片宽高的比例（0~1），原点在左上角
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoundingBox {
+    pub x_min: f32,
+    pub y_min: f32,
+    pub x_max: f32,
+    pub y_max: f32,
+}
+
+impl BoundingBox {
+    /// 由 `[x_min, y_min, x_max, y_max]` 创建，检查坐标范围和顺序
+    pub fn from_corners([x_min, y_min, x_max, y_max]: [f32; 4]) -> Result<Self, String> {
+        let in_range = [x_min, y_min, x_max, y_max]
+            .iter()
+            .all(|value| (0.0..=1.0).contains(value));
+        if !in_range || x_min > x_max || y_min > y_max {
+            return Err(format!(
+                "目标坐标无效: [{}, {}, {}, {}]，应为 0~1 之间的 [x_min, y_min, x_max, y_max]",
+                x_min, y_min, x_max, y_max
+            ));
+        }
+        Ok(Self {
+            x_min,
+            y_min,
+            x_max,
+            y_max,
+        })
+    }
+}
+
+/// 单次识别的结果选项，未设置的项不生效
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecognitionOptions {
+    /// 最多返回的类别数，不超过后端提供的数量（推理脚本和ONNX后端为10个）
+    #[serde(default)]
+    pub top_k: Option<usize>,
+    /// 候选类别的最低置信度，低于该值的类别不返回，置信度最高的类别总是保留
+    #[serde(default)]
+    pub min_confidence: Option<f32>,
+    /// 最高置信度低于该值时把结果标记为不确定
+    #[serde(default)]
+    pub unknown_threshold: Option<f32>,
+}
+
+/// 识别结果中的一个候选类别
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassScore {
+    /// 模型输出中的类别索引
+    pub index: u32,
+    /// 稳定的类别ID
+    pub id: String,
+    /// 当前语言下的显示名称
+    pub label: String,
+    pub probability: f32,
+}
+
+/// 推理脚本返回的识别结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum WorkerResult {
+    /// 按概率从高到低排列的类别索引，由 Rust 端按类别文件映射为名称
+    Indexed {
+        class_indices: Vec<u32>,
+        probabilities: Vec<f32>,
+    },
+    /// 检测模型的目标列表，类别索引由 Rust 端按类别文件映射为名称
+    Detections { detections: Vec<WorkerDetection> },
+    /// 脚本直接给出类别名称（旧版脚本）
+    Labeled(Box<ModelResult>),
+}
+
+/// 推理脚本返回的一个检测目标
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkerDetection {
+    /// 类别索引，模型有类别文件时使用
+    #[serde(default)]
+    pub class_index: Option<u32>,
+    /// 类别名称，没有类别索引时使用
+    #[serde(default)]
+    pub label: Option<String>,
+    pub score: f32,
+    /// 相对图片宽高的 `[x_min, y_min, x_max, y_max]`
+    #[serde(rename = "box")]
+    pub bbox: [f32; 4],
+}
+
+/// 与推理脚本通信的协议版本
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// 发送给推理脚本的请求，每个请求序列化为一行JSON
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkerRequest {
+    /// 协议版本
+    pub v: u32,
+    /// 请求ID，响应中原样返回
+    pub id: String,
+    #[serde(flatten)]
+    pub command: WorkerCommand,
+}
+
+impl WorkerRequest {
+    pub fn new(id: String, command: WorkerCommand) -> Self {
+        Self {
+            v: PROTOCOL_VERSION,
+            id,
+            command,
+        }
+    }
+}
+
+/// 推理脚本支持的命令
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum WorkerCommand {
+    /// 对单张图片执行推理
+    Infer { image_path: String },
+    /// 对多张图片执行一次批量推理，需要脚本声明 `infer_batch` 能力
+    InferBatch { image_paths: Vec<String> },
+    /// 推理并生成预测类别的类激活图，需要脚本声明 `explain` 能力
+    Explain { image_path: String },
+    /// 推理并返回倒数第二层的特征向量，需要脚本声明 `embed` 能力
+    Embed { image_path: String },
+    /// 心跳检测
+    Ping,
+    /// 查询模型信息
+    Info,
+    /// 请求脚本退出
+    Shutdown,
+}
+
+/// 推理脚本返回的一行消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkerResponse {
+    /// 协议版本
+    pub v: u32,
+    /// 对应的请求ID，与请求无关的消息（如启动失败）为空
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub message: WorkerMessage,
+}
+
+/// 推理脚本返回的消息内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WorkerMessage {
+    /// 模型加载完成，可以开始接收请求
+    Ready { info: WorkerInfo },
+    /// 推理结果
+    Result { result: WorkerResult },
+    /// 批量推理结果，顺序与请求中的图片一致
+    BatchResult { results: Vec<BatchEntry> },
+    /// 推理结果及预测类别的类激活图
+    Explanation {
+        result: WorkerResult,
+        activation_map: ActivationMap,
+    },
+    /// 推理结果及图片的特征向量
+    Embedding {
+        result: WorkerResult,
+        embedding: Vec<f32>,
+    },
+    /// 心跳回复
+    Pong,
+    /// 模型信息
+    Info { info: WorkerInfo },
+    /// 类型化的错误
+    Error {
+        code: WorkerErrorCode,
+        message: String,
+    },
+    /// 脚本确认退出
+    Bye,
+}
+
+/// 推理脚本上报的模型信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkerInfo {
+    pub name: String,
+    pub model_type: String,
+    pub num_classes: u32,
+    pub device: String,
+    pub protocol_version: u32,
+    /// 脚本支持的可选命令，如 `infer_batch`
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+}
+
+/// 支持批量推理的脚本在 capabilities 中声明的能力名
+pub const CAPABILITY_INFER_BATCH: &str = "infer_batch";
+
+/// 能生成类激活图的脚本在 capabilities 中声明的能力名
+pub const CAPABILITY_EXPLAIN: &str = "explain";
+
+/// 能提取特征向量的脚本在 capabilities 中声明的能力名
+pub const CAPABILITY_EMBED: &str = "embed";
+
+/// 类激活图（如 Grad-CAM），表示图片各区域对预测结果的贡献
+///
+/// `values` 按行排列，共 `width * height` 个值，范围 0~1，覆盖整张图片。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivationMap {
+    pub width: u32,
+    pub height: u32,
+    pub values: Vec<f32>,
+}
+
+/// 批量推理中单张图片的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BatchEntry {
+    Result {
+        result: WorkerResult,
+    },
+    Error {
+        code: WorkerErrorCode,
+        message: String,
+    },
+}
+
+/// 推理脚本返回的错误码
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkerErrorCode {
+    /// 请求格式错误
+    InvalidRequest,
+    /// 协议版本不匹配
+    UnsupportedVersion,
+    /// 未知命令
+    UnknownCommand,
+    /// 图片无法读取或预处理失败
+    ImageError,
+    /// 模型推理失败
+    InferenceError,
+    /// 模型加载失败
+    ModelLoadFailed,
+    /// 脚本内部错误
+    InternalError,
+    /// 无法识别的错误码
+    #[serde(other)]
+    Unknown,
+}
+
+/// 推理脚本使用的通信协议
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkerProtocol {
+    /// 带请求ID的版本化JSON行协议
+    #[default]
+    Json,
+    /// 兼容模式：`process_image:<路径>` 命令，返回单行结果
+    Legacy,
+}
+
+/// 模型使用的推理后端
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendKind {
+    /// 常驻Python进程，运行 `script_path` 指定的推理脚本
+    Python,
+    /// 内置的ONNX后端，不需要Python环境
+    Onnx,
+    /// 远程推理服务，需要在配置中设置 `remote_inference`
+    Remote,
+    /// 固定输出的模拟后端，用于开发和测试
+    Mock,
+    /// 集成模型，合并 `ensemble` 中各成员模型的结果，本身没有模型文件
+    Ensemble,
+}
+
+impl BackendKind {
+    /// 按权重文件判断后端：`.onnx` 使用ONNX后端，其余使用Python
+    pub fn from_weights_path(path: &str) -> Self {
+        let is_onnx = std::path::Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.eq_ignore_ascii_case("onnx"))
+            .unwrap_or(false);
+        if is_onnx {
+            BackendKind::Onnx
+        } else {
+            BackendKind::Python
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -27,6 +385,323 @@ pub struct ModelInfo {
     pub num_classes: u32,
     pub script_path: String,
     pub is_active: bool,
+    /// 推理脚本的通信协议，未声明时使用JSON协议
+    #[serde(default)]
+    pub protocol: WorkerProtocol,
+    /// 推理后端，未声明时按模型文件判断：`.onnx` 使用ONNX后端，其余使用Python
+    #[serde(default)]
+    pub backend: Option<BackendKind>,
+    /// 类别名称文件，`.json` 为带类别ID和多语言名称的结构化文件，其它扩展名为每行一个名称的纯文本，
+    /// 类别顺序与模型输出一致
+    #[serde(default)]
+    pub labels_path: Option<String>,
+    /// 模型来源，用户导入的模型可以修改和删除
+    #[serde(default)]
+    pub source: ModelSource,
+    /// 模型权重文件的SHA256哈希值
+    #[serde(default)]
+    pub weights_sha256: Option<String>,
+    /// 推理脚本的SHA256哈希值
+    #[serde(default)]
+    pub script_sha256: Option<String>,
+    /// 推理脚本同目录下辅助脚本的合并摘要
+    #[serde(default)]
+    pub helpers_sha256: Option<String>,
+    /// 发布者对模型文件摘要的签名
+    #[serde(default)]
+    pub signature: Option<ModelSignature>,
+    /// 任务类型，未声明时为分类
+    #[serde(default)]
+    pub task: ModelTask,
+    /// 集成模型的成员和合并方式，只有集成模型有
+    #[serde(default)]
+    pub ensemble: Option<EnsembleConfig>,
+}
+
+/// 集成模型的定义
+///
+/// 各成员模型分别识别同一张图片，结果按集成模型的类别文件以类别ID对齐后合并。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnsembleConfig {
+    /// 合并方式，未声明时按权重平均概率
+    #[serde(default)]
+    pub method: EnsembleMethod,
+    pub members: Vec<EnsembleMember>,
+}
+
+/// 集成模型合并成员结果的方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnsembleMethod {
+    /// 按成员权重对各类别的概率加权平均
+    #[default]
+    WeightedAverage,
+    /// 每个成员按权重为其主要结果投票，票数相同时比较平均概率
+    MajorityVote,
+}
+
+/// 集成模型的一个成员
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnsembleMember {
+    pub model_id: String,
+    /// 成员的权重，未声明时为 1
+    #[serde(default = "default_member_weight")]
+    pub weight: f32,
+}
+
+fn default_member_weight() -> f32 {
+    1.0
+}
+
+/// 发布者对模型文件摘要的 ed25519 签名
+///
+/// 签名内容见 [`signing_message`](crate::services::integrity::signing_message)。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelSignature {
+    /// 签名公钥的ID，对应配置中受信任的发布者公钥
+    pub key_id: String,
+    /// Base64 编码的签名
+    pub signature: String,
+}
+
+impl ModelInfo {
+    /// 模型实际使用的推理后端
+    pub fn backend_kind(&self) -> BackendKind {
+        self.backend
+            .unwrap_or_else(|| BackendKind::from_weights_path(&self.path))
+    }
+}
+
+/// 模型来源
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelSource {
+    /// 随应用安装的内置模型
+    #[default]
+    Bundled,
+    /// 用户导入的模型，保存在应用数据目录中
+    User,
+}
+
+/// 导入模型的参数，文件路径为用户选择的源文件，导入时复制到应用数据目录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelImportRequest {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub model_type: String,
+    /// 模型权重文件
+    pub weights_path: String,
+    /// 推理脚本，Python 后端需要
+    #[serde(default)]
+    pub script_path: Option<String>,
+    /// 类别名称文件
+    #[serde(default)]
+    pub labels_path: Option<String>,
+    /// 推理后端，未声明时按权重文件判断
+    #[serde(default)]
+    pub backend: Option<BackendKind>,
+    #[serde(default)]
+    pub protocol: WorkerProtocol,
+    /// 任务类型，未声明时为分类
+    #[serde(default)]
+    pub task: ModelTask,
+    /// 类别数，为空时取类别名称文件的行数或模型报告的类别数
+    #[serde(default)]
+    pub num_classes: Option<u32>,
+}
+
+/// 修改模型信息的参数，为空的字段保持不变
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelMetadataUpdate {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub model_type: Option<String>,
+}
+
+/// 模型包的验证结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelPackageReport {
+    /// 模型包中的模型信息
+    pub model: ModelInfo,
+    /// 各示例图片的识别结果，模型包没有示例时为空
+    pub samples: Vec<PackageSampleResult>,
+    /// 所有示例都与期望结果一致
+    pub passed: bool,
+}
+
+/// 模型包中一张示例图片的验证结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageSampleResult {
+    pub image: String,
+    pub expected_prediction: String,
+    pub expected_confidence: Option<f32>,
+    pub prediction: Option<String>,
+    pub confidence: Option<f32>,
+    pub passed: bool,
+    pub error: Option<String>,
+}
+
+/// 模型诊断报告，由 `diagnose_models` 命令生成
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticsReport {
+    /// Python 环境检查结果
+    pub python: PythonEnvironmentReport,
+    /// 各已注册模型的检查结果
+    pub models: Vec<ModelDiagnosis>,
+    /// 所有模型都通过检查，且需要时 Python 环境满足要求
+    pub healthy: bool,
+}
+
+/// Python 解释器及依赖库的检查结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PythonEnvironmentReport {
+    /// 配置的 Python 可执行文件
+    pub executable: String,
+    /// 实际运行的解释器路径，无法启动时为空
+    pub resolved_path: Option<String>,
+    /// 解释器的环境目录（`sys.prefix`），虚拟环境为其所在目录
+    pub prefix: Option<String>,
+    pub version: Option<String>,
+    pub required_version: String,
+    pub packages: Vec<PackageCheck>,
+    /// 是否有模型使用 Python 后端，不需要时检查失败不影响整体结果
+    pub required: bool,
+    pub passed: bool,
+    /// 无法启动解释器等整体错误
+    pub error: Option<String>,
+}
+
+/// 一个 Python 依赖库的检查结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageCheck {
+    pub name: String,
+    pub required_version: String,
+    /// 已安装的版本，无法导入时为空
+    pub installed_version: Option<String>,
+    pub passed: bool,
+    pub error: Option<String>,
+}
+
+/// 单个模型的检查结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelDiagnosis {
+    pub model_id: String,
+    pub model_name: String,
+    /// 实际使用的推理后端，配置了远程推理时为 `remote`
+    pub backend: BackendKind,
+    /// 模型需要的本地文件
+    pub files: Vec<FileCheck>,
+    /// 模型加载耗时（毫秒）
+    pub load_ms: Option<u64>,
+    /// 识别示例图片的耗时（毫秒）
+    pub inference_ms: Option<u64>,
+    pub prediction: Option<String>,
+    pub confidence: Option<f32>,
+    pub passed: bool,
+    /// 第一个失败的步骤，之后的步骤不再执行
+    pub failed_stage: Option<DiagnosticStage>,
+    pub error: Option<String>,
+}
+
+/// 模型检查的步骤，按执行顺序排列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticStage {
+    /// 模型文件是否存在
+    Files,
+    /// Python 环境是否满足要求
+    Environment,
+    /// 文件摘要和签名校验
+    Integrity,
+    /// 加载模型
+    Load,
+    /// 识别示例图片
+    Inference,
+}
+
+/// 在本机发现的 Python 解释器
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PythonCandidate {
+    /// 解释器路径，可以直接设置为 `python_executable`
+    pub path: String,
+    pub source: PythonSource,
+    /// 版本和依赖库的检查结果
+    pub environment: PythonEnvironmentReport,
+    /// 是否为当前使用的解释器
+    pub is_current: bool,
+}
+
+/// 发现 Python 解释器的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PythonSource {
+    /// 当前配置的解释器
+    Configured,
+    /// 应用数据目录中由应用创建的虚拟环境
+    ManagedVenv,
+    /// 工作目录及其上级目录中的 `.venv`
+    LocalVenv,
+    /// conda 环境
+    Conda,
+    /// pyenv 安装的版本
+    Pyenv,
+    /// Windows 的 py 启动器登记的解释器
+    PyLauncher,
+    /// PATH 中的解释器
+    Path,
+}
+
+/// 创建 Python 虚拟环境时发送的进度事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PythonSetupEvent {
+    pub stage: PythonSetupStage,
+    /// 当前步骤的说明或 pip 的输出
+    pub message: String,
+}
+
+/// 创建 Python 虚拟环境的步骤
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PythonSetupStage {
+    CreateVenv,
+    InstallPackages,
+    Verify,
+    Finished,
+    Failed,
+}
+
+/// 模型需要的一个文件的检查结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileCheck {
+    pub kind: ModelFileKind,
+    /// 模型信息中记录的路径
+    pub path: String,
+    /// 解析后的绝对路径，找不到文件时为空
+    pub resolved_path: Option<String>,
+    pub exists: bool,
+}
+
+/// 模型文件的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelFileKind {
+    Weights,
+    Script,
+    Labels,
+}
+
+/// 类别名称语言设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabelLocales {
+    /// 当前使用的语言
+    pub current: String,
+    /// 模型类别文件中提供的语言
+    pub available: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -34,6 +709,54 @@ pub struct AvailableModels {
     pub models: Vec<ModelInfo>,
     pub active_model_id: String,
 }
+/// 远程推理服务的接口版本前缀
+pub const REMOTE_API_PREFIX: &str = "/v1";
+
+/// 上传图片时携带图片SHA-256摘要的请求头
+pub const REMOTE_SHA256_HEADER: &str = "x-image-sha256";
+
+/// 按摘要识别服务端已有图片的请求体
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteHashRequest {
+    pub sha256: String,
+}
+
+/// 远程推理服务返回的错误
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteErrorBody {
+    pub code: WorkerErrorCode,
+    pub message: String,
+}
+
+/// 推理服务当前状态，供前端展示
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ServiceStatus {
+    /// 服务尚未启动
+    Stopped,
+    /// 模型正在加载
+    Loading { elapsed_secs: u64 },
+    /// 模型已就绪，兼容模式的脚本没有模型信息
+    Ready { info: Option<WorkerInfo> },
+    /// 模型加载失败
+    Failed { message: String },
+}
+
+/// 推理进程崩溃或卡死时发送给前端的事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkerCrashEvent {
+    /// 崩溃进程对应的模型ID，由工作池填写
+    pub model_id: Option<String>,
+    pub script_path: String,
+    pub model_path: String,
+    /// 崩溃原因
+    pub reason: String,
+    /// 进程退出码，进程被信号终止或仍在运行时为空
+    pub exit_code: Option<i32>,
+    /// 本次是连续第几次重启
+    pub restart_attempt: u32,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SaveHistoryResult {
     pub success: bool,
@@ -44,3 +767,106 @@ pub struct SaveImageResult {
     pub file_path: String, // 文件存储路径
     pub image_id: String,  // 数据库中的图片ID
 }
+
+/// 批量识别任务，任务在后台运行并通过事件报告进度
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchJob {
+    pub job_id: String,
+    /// 待处理的图片数量
+    pub total: usize,
+}
+
+/// 批量识别中每张图片处理完成时发送的事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchProgressEvent {
+    pub job_id: String,
+    /// 图片在任务中的序号，从0开始
+    pub index: usize,
+    pub total: usize,
+    /// 用户选择的原始文件路径
+    pub source_path: String,
+    /// 保存后的图片ID和相对路径，保存失败时为空
+    pub image_id: Option<String>,
+    pub file_path: Option<String>,
+    pub result: Option<ModelResult>,
+    pub error: Option<String>,
+}
+
+/// 批量识别任务结束时发送的事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchFinishedEvent {
+    pub job_id: String,
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    /// 任务是否被取消，取消后剩余图片不再处理
+    pub cancelled: bool,
+}
+
+/// 后台队列中的识别任务结束时发送的事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecognitionJobEvent {
+    /// 任务对应的历史记录ID
+    pub history_id: String,
+    pub image_id: String,
+    pub status: RecognitionStatus,
+    pub result: Option<ModelResult>,
+    pub error_message: Option<String>,
+    /// 处理耗时（毫秒）
+    pub duration_ms: Option<i64>,
+}
+
+/// 多模型对比的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelComparison {
+    /// 识别的图片，相对应用数据目录
+    pub image_path: String,
+    /// 各模型的结果，顺序与请求中的模型一致
+    pub entries: Vec<ModelComparisonEntry>,
+    pub agreement: ComparisonAgreement,
+    /// 保存的历史记录组ID，未保存时为空
+    pub group_id: Option<String>,
+}
+
+/// 多模型对比中一个模型的结果，识别失败时只有 `error`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelComparisonEntry {
+    pub model_id: String,
+    pub model_name: String,
+    pub result: Option<ModelResult>,
+    pub error: Option<String>,
+    /// 识别耗时（毫秒），不含加载模型的时间
+    pub latency_ms: Option<u64>,
+    /// 模型尚未加载时的加载耗时（毫秒）
+    pub load_ms: Option<u64>,
+    /// 保存的历史记录ID
+    pub history_id: Option<String>,
+}
+
+/// 各模型主要结果的一致性
+///
+/// 按类别ID（没有时按名称）统计各模型的 `prediction`，识别失败或没有结果的模型不投票。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComparisonAgreement {
+    /// 识别成功的模型数量
+    pub successful: usize,
+    /// 所有识别成功的模型给出了同一个类别
+    pub unanimous: bool,
+    /// 得票最多的类别，票数相同时取平均置信度高的；没有模型给出结果时为空
+    pub consensus: Option<String>,
+    /// 给出得票最多类别的模型占识别成功模型的比例
+    pub agreement_ratio: f32,
+    /// 各类别的得票，按票数从高到低排列
+    pub votes: Vec<PredictionVote>,
+}
+
+/// 多模型对比中一个类别的得票
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PredictionVote {
+    pub label: String,
+    pub class_id: Option<String>,
+    /// 给出该类别的模型ID
+    pub model_ids: Vec<String>,
+    /// 这些模型的平均置信度
+    pub mean_confidence: f32,
+}
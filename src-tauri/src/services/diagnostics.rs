@@ -0,0 +1,348 @@
+use crate::config::constants;
+use crate::config::models::MODEL_REGISTRY;
+use crate::models::inference_result::{
+    BackendKind, DiagnosticStage, DiagnosticsReport, FileCheck, ModelDiagnosis, ModelFileKind,
+    ModelInfo, PackageCheck, PythonEnvironmentReport,
+};
+use crate::services::inference::{effective_backend, BackendSpec};
+use crate::services::model_store::SAMPLE_IMAGE;
+use crate::services::python_env::{self, hidden_command};
+use crate::utils::path_utils::get_resource_path;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+/// 推理脚本要求的最低 Python 版本
+pub const PYTHON_MIN_VERSION: &str = "3.10";
+
+/// 推理脚本依赖的库：(名称, 导入的模块, 最低版本)
+const PYTHON_PACKAGES: &[(&str, &str, &str)] = &[
+    ("torch", "torch", "2.0.1"),
+    ("torchvision", "torchvision", "0.15.2"),
+    ("Pillow", "PIL", "11.0.0"),
+];
+
+/// 检查 Python 环境的超时时间，首次导入 torch 可能较慢
+const PYTHON_CHECK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 在目标解释器中运行的检查脚本，命令行参数为要导入的模块，结果以一行 JSON 输出
+const PYTHON_PROBE: &str = r#"
+import importlib, json, sys
+packages = {}
+for name in sys.argv[1:]:
+    try:
+        module = importlib.import_module(name)
+        packages[name] = {"version": getattr(module, "__version__", None)}
+    except Exception as e:
+        packages[name] = {"error": "%s: %s" % (type(e).__name__, e)}
+print(json.dumps({"executable": sys.executable, "prefix": sys.prefix, "version": sys.version.split()[0], "packages": packages}))
+"#;
+
+/// 检查脚本的输出
+#[derive(Debug, Deserialize)]
+struct PythonProbe {
+    executable: String,
+    prefix: String,
+    version: String,
+    packages: HashMap<String, ProbedPackage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbedPackage {
+    version: Option<String>,
+    error: Option<String>,
+}
+
+/// 检查 Python 环境和所有已注册的模型
+///
+/// 模型逐个检查：确认文件存在、校验文件，再用独立的后端加载模型并识别示例图片，
+/// 结束后立即卸载，不影响工作池中已加载的模型。集成模型的成员照常从工作池获取。
+pub async fn diagnose_models(app_handle: &AppHandle) -> Result<DiagnosticsReport, String> {
+    let models = MODEL_REGISTRY
+        .lock()
+        .map_err(|_| "无法获取模型注册表锁")?
+        .get_models();
+    let config = constants::get_config();
+    let remote = config.remote_inference.is_some();
+
+    let python_required = !remote
+        && models
+            .iter()
+            .any(|model| model.backend_kind() == BackendKind::Python);
+    let python = check_python(&python_env::current_executable(), python_required).await;
+    let sample_path = get_resource_path(app_handle, SAMPLE_IMAGE)?;
+
+    let mut diagnoses = Vec::with_capacity(models.len());
+    for model in &models {
+        println!("诊断模型: {}", model.name);
+        let diagnosis = diagnose_model(app_handle, model, &python, &sample_path).await;
+        match &diagnosis.error {
+            Some(error) => println!("模型 {} 检查失败: {}", model.name, error),
+            None => println!(
+                "模型 {} 检查通过，加载 {} 毫秒，识别 {} 毫秒",
+                model.name,
+                diagnosis.load_ms.unwrap_or_default(),
+                diagnosis.inference_ms.unwrap_or_default()
+            ),
+        }
+        diagnoses.push(diagnosis);
+    }
+
+    let healthy =
+        (python.passed || !python.required) && diagnoses.iter().all(|diagnosis| diagnosis.passed);
+    Ok(DiagnosticsReport {
+        python,
+        models: diagnoses,
+        healthy,
+    })
+}
+
+/// 依次检查模型的文件、运行环境和校验，通过后加载模型并识别示例图片
+async fn diagnose_model(
+    app_handle: &AppHandle,
+    model: &ModelInfo,
+    python: &PythonEnvironmentReport,
+    sample_path: &str,
+) -> ModelDiagnosis {
+    let backend = effective_backend(model);
+    let mut diagnosis = ModelDiagnosis {
+        model_id: model.id.clone(),
+        model_name: model.name.clone(),
+        backend,
+        files: check_files(app_handle, model, backend),
+        load_ms: None,
+        inference_ms: None,
+        prediction: None,
+        confidence: None,
+        passed: false,
+        failed_stage: None,
+        error: None,
+    };
+    let fail = |diagnosis: &mut ModelDiagnosis, stage, error: String| {
+        diagnosis.failed_stage = Some(stage);
+        diagnosis.error = Some(error);
+    };
+
+    let missing: Vec<&str> = diagnosis
+        .files
+        .iter()
+        .filter(|file| !file.exists)
+        .map(|file| file.path.as_str())
+        .collect();
+    if !missing.is_empty() {
+        let error = format!("找不到模型文件: {}", missing.join(", "));
+        fail(&mut diagnosis, DiagnosticStage::Files, error);
+        return diagnosis;
+    }
+
+    if backend == BackendKind::Python && !python.passed {
+        let error = python
+            .error
+            .clone()
+            .unwrap_or_else(|| "Python 环境不满足要求".to_string());
+        fail(&mut diagnosis, DiagnosticStage::Environment, error);
+        return diagnosis;
+    }
+
+    let spec = match BackendSpec::resolve(app_handle, model) {
+        Ok(spec) => spec,
+        Err(e) => {
+            fail(&mut diagnosis, DiagnosticStage::Files, e);
+            return diagnosis;
+        }
+    };
+    if let Err(e) = spec.verify_async(model).await {
+        fail(&mut diagnosis, DiagnosticStage::Integrity, e);
+        return diagnosis;
+    }
+
+    let started = Instant::now();
+    let backend = match spec.create(app_handle, model) {
+        Ok(backend) => backend,
+        Err(e) => {
+            fail(&mut diagnosis, DiagnosticStage::Load, e);
+            return diagnosis;
+        }
+    };
+    match backend.load().await {
+        Ok(()) => {
+            diagnosis.load_ms = Some(started.elapsed().as_millis() as u64);
+
+            let started = Instant::now();
+            match backend.infer(sample_path).await {
+                Ok(result) => {
+                    diagnosis.inference_ms = Some(started.elapsed().as_millis() as u64);
+                    diagnosis.prediction = Some(result.prediction);
+                    diagnosis.confidence = Some(result.confidence);
+                    diagnosis.passed = true;
+                }
+                Err(e) => fail(&mut diagnosis, DiagnosticStage::Inference, e.to_string()),
+            }
+        }
+        Err(e) => fail(&mut diagnosis, DiagnosticStage::Load, e.to_string()),
+    }
+    backend.unload().await;
+    diagnosis
+}
+
+/// 检查后端需要的本地文件，远程和模拟后端只需要类别文件（如果有）
+fn check_files(app_handle: &AppHandle, model: &ModelInfo, backend: BackendKind) -> Vec<FileCheck> {
+    let mut files = Vec::new();
+    if matches!(backend, BackendKind::Python | BackendKind::Onnx) {
+        files.push((ModelFileKind::Weights, model.path.as_str()));
+    }
+    if backend == BackendKind::Python {
+        files.push((ModelFileKind::Script, model.script_path.as_str()));
+    }
+    if let Some(labels_path) = &model.labels_path {
+        files.push((ModelFileKind::Labels, labels_path.as_str()));
+    }
+
+    files
+        .into_iter()
+        .map(|(kind, path)| {
+            let resolved_path = get_resource_path(app_handle, path).ok();
+            FileCheck {
+                kind,
+                path: path.to_string(),
+                exists: resolved_path.is_some(),
+                resolved_path,
+            }
+        })
+        .collect()
+}
+
+/// 启动配置的 Python 解释器，检查版本和依赖库
+pub async fn check_python(executable: &str, required: bool) -> PythonEnvironmentReport {
+    let mut report = PythonEnvironmentReport {
+        executable: executable.to_string(),
+        resolved_path: None,
+        prefix: None,
+        version: None,
+        required_version: PYTHON_MIN_VERSION.to_string(),
+        packages: PYTHON_PACKAGES
+            .iter()
+            .map(|(name, _, min_version)| PackageCheck {
+                name: name.to_string(),
+                required_version: min_version.to_string(),
+                installed_version: None,
+                passed: false,
+                error: None,
+            })
+            .collect(),
+        required,
+        passed: false,
+        error: None,
+    };
+
+    let probe = match run_probe(executable).await {
+        Ok(probe) => probe,
+        Err(e) => {
+            println!("Python 环境检查失败: {}", e);
+            report.error = Some(e);
+            return report;
+        }
+    };
+
+    let version_ok = version_at_least(&probe.version, PYTHON_MIN_VERSION);
+    for (check, (_, module, _)) in report.packages.iter_mut().zip(PYTHON_PACKAGES) {
+        match probe.packages.get(*module) {
+            Some(ProbedPackage {
+                error: Some(error), ..
+            }) => check.error = Some(error.clone()),
+            Some(package) => {
+                check.installed_version = package.version.clone();
+                check.passed = package
+                    .version
+                    .as_deref()
+                    .is_some_and(|version| version_at_least(version, &check.required_version));
+                if !check.passed {
+                    check.error = Some(format!("需要 {} 或更高版本", check.required_version));
+                }
+            }
+            None => check.error = Some("检查脚本没有返回该库的信息".to_string()),
+        }
+    }
+
+    report.passed = version_ok && report.packages.iter().all(|check| check.passed);
+    if !version_ok {
+        report.error = Some(format!(
+            "Python 版本为 {}，需要 {} 或更高版本",
+            probe.version, PYTHON_MIN_VERSION
+        ));
+    } else if !report.passed {
+        let failed: Vec<&str> = report
+            .packages
+            .iter()
+            .filter(|check| !check.passed)
+            .map(|check| check.name.as_str())
+            .collect();
+        report.error = Some(format!("缺少依赖库或版本过低: {}", failed.join(", ")));
+    }
+    report.resolved_path = Some(probe.executable);
+    report.prefix = Some(probe.prefix);
+    report.version = Some(probe.version);
+    report
+}
+
+/// 在解释器中运行检查脚本并解析输出
+async fn run_probe(executable: &str) -> Result<PythonProbe, String> {
+    let mut command = hidden_command(executable);
+    command
+        .arg("-c")
+        .arg(PYTHON_PROBE)
+        .args(PYTHON_PACKAGES.iter().map(|(_, module, _)| module))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let output = tokio::time::timeout(PYTHON_CHECK_TIMEOUT, command.output())
+        .await
+        .map_err(|_| {
+            format!(
+                "Python 环境检查超过 {} 秒未完成",
+                PYTHON_CHECK_TIMEOUT.as_secs()
+            )
+        })?
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => format!("找不到 Python 可执行文件: {}", executable),
+            _ => format!("无法启动 Python: {}", e),
+        })?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "Python 检查脚本运行失败 ({}): {}",
+            output.status,
+            stderr.trim()
+        ));
+    }
+    stdout
+        .lines()
+        .last()
+        .and_then(|line| serde_json::from_str(line).ok())
+        .ok_or_else(|| format!("无法解析 Python 检查结果: {}", stdout.trim()))
+}
+
+/// 比较版本号的数字部分，忽略 `+cu118` 这类本地版本后缀
+pub fn version_at_least(version: &str, required: &str) -> bool {
+    let parse = |version: &str| -> Vec<u64> {
+        version
+            .split('.')
+            .map_while(|part| {
+                let digits: String = part.chars().take_while(char::is_ascii_digit).collect();
+                digits.parse().ok()
+            })
+            .collect()
+    };
+    let (version, required) = (parse(version), parse(required));
+    let len = version.len().max(required.len());
+    let padded = |parts: &[u64]| -> Vec<u64> {
+        (0..len)
+            .map(|i| parts.get(i).copied().unwrap_or(0))
+            .collect()
+    };
+    padded(&version) >= padded(&required)
+}
@@ -0,0 +1,394 @@
+use crate::config::models::MODEL_REGISTRY;
+use crate::models::inference_result::{
+    EnsembleConfig, EnsembleMethod, ModelInfo, ModelResult, ServiceStatus, WorkerErrorCode,
+    WorkerInfo,
+};
+use crate::services::inference::{acquire_ready, InferenceBackend, TOP_N};
+use crate::services::labels::LabelSet;
+use crate::services::python::ServiceError;
+use futures::future::{join_all, BoxFuture};
+use std::sync::Arc;
+use std::time::Instant;
+use tauri::AppHandle;
+use tokio::sync::watch;
+
+#[derive(Clone)]
+enum EnsembleState {
+    Loading { since: Instant },
+    Ready,
+    Failed { error: String },
+}
+
+/// 集成模型的推理后端，把图片交给各成员模型识别后合并结果
+///
+/// 成员模型与其它模型一样由工作池管理，每次识别时从工作池获取，集成后端本身不加载模型；
+/// 成员结果按集成模型的类别文件以类别ID（没有时按名称）对齐。部分成员识别失败时
+/// 只合并成功的成员，全部失败时返回错误。
+pub struct EnsembleBackend {
+    app_handle: AppHandle,
+    config: EnsembleConfig,
+    labels: Arc<LabelSet>,
+    info: WorkerInfo,
+    state: Arc<watch::Sender<EnsembleState>>,
+}
+
+impl EnsembleBackend {
+    /// 创建后端并在后台加载所有成员模型，需要在异步运行时中调用
+    pub fn new(
+        app_handle: &AppHandle,
+        model: &ModelInfo,
+        labels: Arc<LabelSet>,
+    ) -> Result<Self, String> {
+        let config = model
+            .ensemble
+            .clone()
+            .ok_or_else(|| format!("模型 {} 没有集成模型定义", model.name))?;
+        let info = WorkerInfo {
+            name: model.name.clone(),
+            model_type: model.model_type.clone(),
+            num_classes: labels.len() as u32,
+            device: "ensemble".to_string(),
+            protocol_version: 0,
+            capabilities: Vec::new(),
+        };
+
+        let (state, _) = watch::channel(EnsembleState::Loading {
+            since: Instant::now(),
+        });
+        let state = Arc::new(state);
+
+        let loading_state = Arc::clone(&state);
+        let loading_handle = app_handle.clone();
+        let loading_config = config.clone();
+        let name = model.name.clone();
+        tokio::spawn(async move {
+            let next = match load_members(&loading_handle, &loading_config).await {
+                Ok(()) => {
+                    println!("集成模型 {} 的成员已加载", name);
+                    EnsembleState::Ready
+                }
+                Err(error) => {
+                    println!("集成模型 {} 加载失败: {}", name, error);
+                    EnsembleState::Failed { error }
+                }
+            };
+            // 加载期间已被卸载时不再覆盖状态
+            loading_state.send_if_modified(|current| {
+                if let EnsembleState::Loading { .. } = current {
+                    *current = next;
+                    true
+                } else {
+                    false
+                }
+            });
+        });
+
+        Ok(Self {
+            app_handle: app_handle.clone(),
+            config,
+            labels,
+            info,
+            state,
+        })
+    }
+
+    /// 等待成员加载结束
+    async fn loaded(&self) -> Result<(), ServiceError> {
+        let mut receiver = self.state.subscribe();
+        let state = receiver
+            .wait_for(|state| !matches!(state, EnsembleState::Loading { .. }))
+            .await
+            .map(|state| state.clone())
+            .map_err(|_| ServiceError::ModelLoadFailed("加载任务已退出".to_string()))?;
+
+        match state {
+            EnsembleState::Ready => Ok(()),
+            EnsembleState::Failed { error } => Err(ServiceError::ModelLoadFailed(error)),
+            EnsembleState::Loading { .. } => unreachable!(),
+        }
+    }
+
+    /// 各成员同时识别图片，再按集成方式合并结果
+    async fn run(&self, image_path: &str) -> Result<ModelResult, ServiceError> {
+        self.loaded().await?;
+        let members = member_models(&self.config).map_err(ServiceError::ModelLoadFailed)?;
+
+        let runs = members.iter().map(|(model, _)| async move {
+            let backend = acquire_ready(&self.app_handle, model)
+                .await
+                .map_err(ServiceError::ModelLoadFailed)?;
+            backend.infer(image_path).await
+        });
+        let outcomes = join_all(runs).await;
+
+        let mut results = Vec::with_capacity(members.len());
+        let mut first_error = None;
+        for ((model, weight), outcome) in members.iter().zip(outcomes) {
+            match outcome {
+                Ok(result) => results.push((*weight, result)),
+                Err(e) => {
+                    println!("集成模型的成员 {} 识别失败: {}", model.name, e);
+                    first_error.get_or_insert_with(|| (model.name.clone(), e));
+                }
+            }
+        }
+        if results.is_empty() {
+            return Err(match first_error {
+                Some((name, e)) => ServiceError::Worker {
+                    code: WorkerErrorCode::InferenceError,
+                    message: format!("集成模型的所有成员识别失败，{}: {}", name, e),
+                },
+                None => ServiceError::ModelLoadFailed("集成模型没有成员".to_string()),
+            });
+        }
+
+        merge(self.config.method, &self.labels, &results)
+    }
+}
+
+impl InferenceBackend for EnsembleBackend {
+    fn load(&self) -> BoxFuture<'_, Result<(), ServiceError>> {
+        Box::pin(self.loaded())
+    }
+
+    fn infer<'a>(
+        &'a self,
+        image_path: &'a str,
+    ) -> BoxFuture<'a, Result<ModelResult, ServiceError>> {
+        Box::pin(self.run(image_path))
+    }
+
+    fn unload(&self) -> BoxFuture<'_, ()> {
+        // 成员模型由工作池各自回收
+        self.state.send_replace(EnsembleState::Failed {
+            error: "模型已卸载".to_string(),
+        });
+        Box::pin(async {})
+    }
+
+    fn info(&self) -> BoxFuture<'_, Result<WorkerInfo, ServiceError>> {
+        Box::pin(async move { self.loaded().await.map(|_| self.info.clone()) })
+    }
+
+    fn status(&self) -> ServiceStatus {
+        match &*self.state.borrow() {
+            EnsembleState::Loading { since } => ServiceStatus::Loading {
+                elapsed_secs: since.elapsed().as_secs(),
+            },
+            EnsembleState::Ready => ServiceStatus::Ready {
+                info: Some(self.info.clone()),
+            },
+            EnsembleState::Failed { error } => ServiceStatus::Failed {
+                message: error.clone(),
+            },
+        }
+    }
+}
+
+/// 从注册表中查找成员模型及其权重，注册表锁不能跨越await持有
+fn member_models(config: &EnsembleConfig) -> Result<Vec<(ModelInfo, f32)>, String> {
+    let registry = MODEL_REGISTRY.lock().map_err(|_| "无法获取模型注册表锁")?;
+    config
+        .members
+        .iter()
+        .map(|member| {
+            let model = registry
+                .get_model(&member.model_id)
+                .ok_or_else(|| format!("成员模型不存在: {}", member.model_id))?;
+            Ok((model, member.weight))
+        })
+        .collect()
+}
+
+/// 同时加载所有成员模型，任一成员加载失败时集成模型加载失败
+async fn load_members(app_handle: &AppHandle, config: &EnsembleConfig) -> Result<(), String> {
+    let members = member_models(config)?;
+    let loads = members
+        .iter()
+        .map(|(model, _)| acquire_ready(app_handle, model));
+    for ((model, _), outcome) in members.iter().zip(join_all(loads).await) {
+        outcome.map_err(|e| format!("成员 {} 加载失败: {}", model.name, e))?;
+    }
+    Ok(())
+}
+
+/// 按集成方式合并各成员的 (权重, 结果)
+///
+/// 加权平均时每个类别的概率为各成员概率的加权平均，成员结果中没有的类别概率按 0 计算；
+/// 投票时每个成员按权重为其主要类别投票，类别的概率为得票占总权重的比例，
+/// 票数相同时比较加权平均概率。
+pub fn merge(
+    method: EnsembleMethod,
+    labels: &LabelSet,
+    results: &[(f32, ModelResult)],
+) -> Result<ModelResult, ServiceError> {
+    let total_weight: f32 = results.iter().map(|(weight, _)| weight).sum();
+    let mut average = vec![0.0f32; labels.len()];
+    let mut votes = vec![0.0f32; labels.len()];
+    for (weight, result) in results {
+        for (index, probability) in member_scores(labels, result)? {
+            average[index] += weight * probability;
+        }
+        if let Some(index) = top_class(labels, result)? {
+            votes[index] += weight;
+        }
+    }
+    for value in average.iter_mut().chain(votes.iter_mut()) {
+        *value /= total_weight;
+    }
+
+    let mut ranked: Vec<(usize, f32)> = match method {
+        EnsembleMethod::WeightedAverage => average.iter().copied().enumerate().collect(),
+        EnsembleMethod::MajorityVote => votes
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, share)| *share > 0.0)
+            .collect(),
+    };
+    ranked.sort_by(|a, b| {
+        b.1.total_cmp(&a.1)
+            .then(average[b.0].total_cmp(&average[a.0]))
+    });
+    ranked.truncate(TOP_N);
+
+    labels.result(&ranked)
+}
+
+/// 把成员结果中的各类别概率对齐到集成模型的类别索引
+///
+/// 有 `top_classes` 时按类别ID对齐，否则按 `class_probabilities` 的名称对齐。
+fn member_scores(
+    labels: &LabelSet,
+    result: &ModelResult,
+) -> Result<Vec<(usize, f32)>, ServiceError> {
+    if !result.top_classes.is_empty() {
+        return result
+            .top_classes
+            .iter()
+            .map(|score| {
+                Ok((
+                    align(labels, Some(&score.id), &score.label)?,
+                    score.probability,
+                ))
+            })
+            .collect();
+    }
+    result
+        .class_probabilities
+        .iter()
+        .map(|(label, probability)| Ok((align(labels, None, label)?, *probability)))
+        .collect()
+}
+
+/// 成员结果的主要类别，没有结果时为空
+fn top_class(labels: &LabelSet, result: &ModelResult) -> Result<Option<usize>, ServiceError> {
+    if result.prediction.is_empty() {
+        return Ok(None);
+    }
+    align(labels, result.class_id.as_deref(), &result.prediction).map(Some)
+}
+
+/// 按类别ID或名称在集成模型的类别表中查找类别
+fn align(labels: &LabelSet, id: Option<&str>, label: &str) -> Result<usize, ServiceError> {
+    id.and_then(|id| labels.index_of(id))
+        .or_else(|| labels.find(label))
+        .ok_or_else(|| ServiceError::Worker {
+            code: WorkerErrorCode::InferenceError,
+            message: format!(
+                "成员模型的类别 {} 不在集成模型的类别文件中",
+                id.unwrap_or(label)
+            ),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::labels::LabelFile;
+
+    fn labels() -> LabelSet {
+        let file: LabelFile = serde_json::from_value(serde_json::json!({
+            "classes": [
+                { "id": "cat", "names": { "zh-CN": "猫", "en": "cat" } },
+                { "id": "dog", "names": { "zh-CN": "狗", "en": "dog" } },
+                { "id": "fox", "names": { "zh-CN": "狐狸", "en": "fox" } }
+            ]
+        }))
+        .unwrap();
+        LabelSet::from_file(file).unwrap()
+    }
+
+    /// 只有类别名称、没有类别ID的成员结果（旧版脚本）
+    fn named(labels: &LabelSet, ranked: &[(&str, f32)]) -> ModelResult {
+        let mut result = labels.result(&[(0, 1.0)]).unwrap();
+        result.prediction = ranked[0].0.to_string();
+        result.confidence = ranked[0].1;
+        result.class_id = None;
+        result.top_classes.clear();
+        result.class_probabilities = ranked
+            .iter()
+            .map(|(name, probability)| (name.to_string(), *probability))
+            .collect();
+        result
+    }
+
+    fn members(labels: &LabelSet) -> Vec<(f32, ModelResult)> {
+        vec![
+            (1.0, labels.result(&[(0, 0.6), (1, 0.3), (2, 0.1)]).unwrap()),
+            (1.0, labels.result(&[(1, 0.5), (0, 0.4), (2, 0.1)]).unwrap()),
+            (2.0, named(labels, &[("dog", 0.9), ("fox", 0.1)])),
+        ]
+    }
+
+    fn probability(result: &ModelResult, id: &str) -> f32 {
+        result
+            .top_classes
+            .iter()
+            .find(|score| score.id == id)
+            .map(|score| score.probability)
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn weighted_average_combines_ids_and_names() {
+        let labels = labels();
+        let result = merge(EnsembleMethod::WeightedAverage, &labels, &members(&labels)).unwrap();
+
+        assert_eq!(result.class_id.as_deref(), Some("dog"));
+        assert!((result.confidence - 0.65).abs() < 1e-6);
+        assert!((probability(&result, "cat") - 0.25).abs() < 1e-6);
+        assert!((probability(&result, "fox") - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn majority_vote_uses_weighted_share() {
+        let labels = labels();
+        let result = merge(EnsembleMethod::MajorityVote, &labels, &members(&labels)).unwrap();
+
+        assert_eq!(result.class_id.as_deref(), Some("dog"));
+        assert!((result.confidence - 0.75).abs() < 1e-6);
+        assert!((probability(&result, "cat") - 0.25).abs() < 1e-6);
+        // 没有得票的类别不出现在结果中
+        assert_eq!(result.top_classes.len(), 2);
+    }
+
+    #[test]
+    fn vote_tie_is_broken_by_average() {
+        let labels = labels();
+        let results = &members(&labels)[..2];
+        let result = merge(EnsembleMethod::MajorityVote, &labels, results).unwrap();
+
+        // cat 与 dog 各得一票，cat 的平均概率 0.5 高于 dog 的 0.4
+        assert_eq!(result.class_id.as_deref(), Some("cat"));
+        assert!((result.confidence - 0.5).abs() < 1e-6);
+        assert_eq!(result.top_classes[1].id, "dog");
+    }
+
+    #[test]
+    fn unknown_member_class_is_rejected() {
+        let labels = labels();
+        let results = vec![(1.0, named(&labels, &[("wolf", 0.8), ("cat", 0.2)]))];
+        assert!(merge(EnsembleMethod::WeightedAverage, &labels, &results).is_err());
+    }
+}
@@ -0,0 +1,75 @@
+use crate::models::inference_result::ActivationMap;
+use image::imageops::FilterType;
+use image::{GrayImage, ImageFormat, Rgb, RgbImage};
+use std::path::Path;
+
+/// 热力图叠加在原图上的不透明度
+const OVERLAY_OPACITY: f32 = 0.45;
+
+/// 热力图文件名：原图文件名加上模型ID，同一张图片在不同模型下的热力图互不覆盖
+pub fn heatmap_file_name(image_path: &str, model_id: &str) -> String {
+    let stem = Path::new(image_path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "image".to_string());
+    let model_id: String = model_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}_cam_{}.png", stem, model_id)
+}
+
+/// 与原图位于同一目录的热力图路径，分隔符统一为 `/`，用于记录到识别结果
+pub fn heatmap_relative_path(image_path: &str, model_id: &str) -> String {
+    let image_path = image_path.replace('\\', "/");
+    let file_name = heatmap_file_name(&image_path, model_id);
+    match image_path.rsplit_once('/') {
+        Some((dir, _)) => format!("{}/{}", dir, file_name),
+        None => file_name,
+    }
+}
+
+/// 把类激活图放大到原图尺寸，按 jet 色带着色后叠加在原图上，保存为PNG
+pub fn save_overlay(
+    image_path: &Path,
+    map: &ActivationMap,
+    output_path: &Path,
+) -> Result<(), String> {
+    let image = image::open(image_path)
+        .map_err(|e| format!("无法读取图像 {}: {}", image_path.display(), e))?
+        .to_rgb8();
+
+    let heat = GrayImage::from_raw(
+        map.width,
+        map.height,
+        map.values
+            .iter()
+            .map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8)
+            .collect(),
+    )
+    .ok_or_else(|| format!("类激活图尺寸错误: {}x{}", map.width, map.height))?;
+    let heat = image::imageops::resize(&heat, image.width(), image.height(), FilterType::Triangle);
+
+    let overlay = RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        let Rgb(base) = *image.get_pixel(x, y);
+        let color = jet(heat.get_pixel(x, y)[0] as f32 / 255.0);
+        Rgb(std::array::from_fn(|i| {
+            (base[i] as f32 * (1.0 - OVERLAY_OPACITY) + color[i] * OVERLAY_OPACITY).round() as u8
+        }))
+    });
+    overlay
+        .save_with_format(output_path, ImageFormat::Png)
+        .map_err(|e| format!("无法保存热力图 {}: {}", output_path.display(), e))
+}
+
+/// jet 色带：0 为深蓝，0.5 为绿，1 为深红
+fn jet(value: f32) -> [f32; 3] {
+    let channel = |offset: f32| (1.5 - (4.0 * value - offset).abs()).clamp(0.0, 1.0) * 255.0;
+    [channel(3.0), channel(2.0), channel(1.0)]
+}
@@ -0,0 +1,572 @@
+use crate::config::constants;
+use crate::models::inference_result::{
+    ActivationMap, BackendKind, ModelInfo, ModelResult, ModelTask, RecognitionOptions,
+    ServiceStatus, WorkerErrorCode, WorkerInfo, WorkerProtocol,
+};
+use crate::services::ensemble::EnsembleBackend;
+use crate::services::integrity;
+use crate::services::labels::{detection_result, LabelSet};
+use crate::services::mock::MockBackend;
+use crate::services::onnx::OnnxBackend;
+use crate::services::python::{PythonService, ServiceError, WORKER_CRASHED_EVENT};
+use crate::services::python_env;
+use crate::services::remote::RemoteBackend;
+use crate::services::worker_pool::WORKER_POOL;
+use crate::utils::path_utils::get_resource_path;
+use futures::future::BoxFuture;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// 结果中保留的类别数量，与推理脚本一致
+pub const TOP_N: usize = 10;
+
+/// 推理后端，一个实例对应一个已加载（或正在加载）的模型
+///
+/// 后端创建时即开始在后台加载模型，由 [`WorkerPool`](crate::services::worker_pool::WorkerPool)
+/// 按模型ID管理生命周期。
+pub trait InferenceBackend: Send + Sync {
+    /// 等待模型加载完成
+    fn load(&self) -> BoxFuture<'_, Result<(), ServiceError>>;
+
+    /// 识别一张图片
+    fn infer<'a>(&'a self, image_path: &'a str)
+        -> BoxFuture<'a, Result<ModelResult, ServiceError>>;
+
+    /// 识别多张图片，返回与输入顺序一致的结果；外层错误表示整批失败
+    ///
+    /// 默认逐张识别，支持批量计算的后端可以覆盖。
+    fn infer_batch<'a>(
+        &'a self,
+        image_paths: &'a [String],
+    ) -> BoxFuture<'a, Result<Vec<Result<ModelResult, ServiceError>>, ServiceError>> {
+        Box::pin(async move {
+            let mut results = Vec::with_capacity(image_paths.len());
+            for path in image_paths {
+                results.push(self.infer(path).await);
+            }
+            Ok(results)
+        })
+    }
+
+    /// 识别一张图片，并生成预测类别的类激活图
+    ///
+    /// 默认不支持，能计算梯度或激活图的后端可以覆盖。
+    fn explain<'a>(
+        &'a self,
+        _image_path: &'a str,
+    ) -> BoxFuture<'a, Result<(ModelResult, ActivationMap), ServiceError>> {
+        Box::pin(async { Err(ServiceError::Unsupported("生成热力图")) })
+    }
+
+    /// 识别一张图片，并提取模型倒数第二层输出的特征向量，用于相似图片检索
+    ///
+    /// 默认不支持。
+    fn embed<'a>(
+        &'a self,
+        _image_path: &'a str,
+    ) -> BoxFuture<'a, Result<(ModelResult, Vec<f32>), ServiceError>> {
+        Box::pin(async { Err(ServiceError::Unsupported("提取特征向量")) })
+    }
+
+    /// 卸载模型并释放资源，之后的请求会失败
+    fn unload(&self) -> BoxFuture<'_, ()>;
+
+    /// 查询已加载模型的信息，模型未就绪时返回错误
+    fn info(&self) -> BoxFuture<'_, Result<WorkerInfo, ServiceError>>;
+
+    /// 当前加载状态
+    fn status(&self) -> ServiceStatus;
+}
+
+impl InferenceBackend for PythonService {
+    fn load(&self) -> BoxFuture<'_, Result<(), ServiceError>> {
+        Box::pin(async move { self.wait_ready().await.map(|_| ()) })
+    }
+
+    fn infer<'a>(
+        &'a self,
+        image_path: &'a str,
+    ) -> BoxFuture<'a, Result<ModelResult, ServiceError>> {
+        Box::pin(self.process_image(image_path))
+    }
+
+    fn infer_batch<'a>(
+        &'a self,
+        image_paths: &'a [String],
+    ) -> BoxFuture<'a, Result<Vec<Result<ModelResult, ServiceError>>, ServiceError>> {
+        Box::pin(self.process_batch(image_paths))
+    }
+
+    fn explain<'a>(
+        &'a self,
+        image_path: &'a str,
+    ) -> BoxFuture<'a, Result<(ModelResult, ActivationMap), ServiceError>> {
+        Box::pin(self.explain_image(image_path))
+    }
+
+    fn embed<'a>(
+        &'a self,
+        image_path: &'a str,
+    ) -> BoxFuture<'a, Result<(ModelResult, Vec<f32>), ServiceError>> {
+        Box::pin(self.embed_image(image_path))
+    }
+
+    fn unload(&self) -> BoxFuture<'_, ()> {
+        Box::pin(self.shutdown())
+    }
+
+    fn info(&self) -> BoxFuture<'_, Result<WorkerInfo, ServiceError>> {
+        Box::pin(PythonService::info(self))
+    }
+
+    fn status(&self) -> ServiceStatus {
+        PythonService::status(self)
+    }
+}
+
+/// 创建后端所需的参数，参数变化时工作池会重新创建后端
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendSpec {
+    pub kind: BackendKind,
+    pub model_path: String,
+    pub script_path: Option<String>,
+    pub protocol: WorkerProtocol,
+    /// 运行推理脚本的Python解释器，切换解释器后重新启动模型进程
+    pub python_executable: Option<String>,
+    /// 远程推理服务地址
+    pub endpoint: Option<String>,
+    /// 类别名称文件，用于把类别索引映射为当前语言的名称
+    pub labels_path: Option<String>,
+}
+
+impl BackendSpec {
+    /// 根据模型信息确定后端类型并解析资源路径
+    ///
+    /// 配置了远程推理服务时，除集成模型外的所有模型都使用远程后端，集成模型的成员各自使用远程后端。
+    pub fn resolve(app_handle: &AppHandle, model: &ModelInfo) -> Result<Self, String> {
+        Self::for_kind(app_handle, model, effective_backend(model))
+    }
+
+    /// 按指定的后端类型解析资源路径，不考虑远程推理配置
+    pub fn for_kind(
+        app_handle: &AppHandle,
+        model: &ModelInfo,
+        kind: BackendKind,
+    ) -> Result<Self, String> {
+        let remote = constants::get_config().remote_inference.as_ref();
+        let labels_path = match &model.labels_path {
+            Some(path) => Some(get_resource_path(app_handle, path)?),
+            None => None,
+        };
+
+        let spec = match kind {
+            BackendKind::Python => Self {
+                kind,
+                model_path: get_resource_path(app_handle, &model.path)?,
+                script_path: Some(get_resource_path(app_handle, &model.script_path)?),
+                protocol: model.protocol,
+                python_executable: Some(python_env::current_executable()),
+                endpoint: None,
+                labels_path,
+            },
+            BackendKind::Onnx => Self {
+                kind,
+                model_path: get_resource_path(app_handle, &model.path)?,
+                script_path: None,
+                protocol: model.protocol,
+                python_executable: None,
+                endpoint: None,
+                labels_path,
+            },
+            BackendKind::Remote => Self {
+                kind,
+                model_path: model.path.clone(),
+                script_path: None,
+                protocol: model.protocol,
+                python_executable: None,
+                endpoint: Some(remote.ok_or("未配置远程推理服务")?.endpoint.clone()),
+                labels_path,
+            },
+            // 模拟后端和集成模型不读取模型文件
+            BackendKind::Mock | BackendKind::Ensemble => Self {
+                kind,
+                model_path: model.path.clone(),
+                script_path: None,
+                protocol: model.protocol,
+                python_executable: None,
+                endpoint: None,
+                labels_path,
+            },
+        };
+        Ok(spec)
+    }
+
+    /// 校验本地模型文件的摘要和签名，远程和模拟后端不读取本地模型文件
+    pub fn verify(&self, model: &ModelInfo, config: &constants::AppConfig) -> Result<(), String> {
+        let script_path = match self.kind {
+            BackendKind::Python => self.script_path.as_deref().map(Path::new),
+            BackendKind::Onnx => None,
+            BackendKind::Remote | BackendKind::Mock | BackendKind::Ensemble => return Ok(()),
+        };
+        integrity::verify_model(
+            model,
+            Path::new(&self.model_path),
+            script_path,
+            &config.model_integrity,
+        )
+        .map_err(|e| {
+            println!("模型 {} 校验失败: {}", model.name, e);
+            format!("模型 {} 校验失败: {}", model.name, e)
+        })
+    }
+
+    /// 在阻塞线程上校验模型文件，计算大文件摘要时不占用异步运行时的工作线程
+    pub async fn verify_async(&self, model: &ModelInfo) -> Result<(), String> {
+        let (spec, model) = (self.clone(), model.clone());
+        tokio::task::spawn_blocking(move || spec.verify(&model, constants::get_config()))
+            .await
+            .map_err(|e| format!("模型校验任务失败: {}", e))?
+    }
+
+    /// 创建后端并开始加载模型，调用方需先用 `verify_async` 校验模型文件
+    pub fn create(
+        &self,
+        app_handle: &AppHandle,
+        model: &ModelInfo,
+    ) -> Result<Arc<dyn InferenceBackend>, String> {
+        let config = constants::get_config();
+        let labels = match &self.labels_path {
+            Some(labels_path) => Some(Arc::new(LabelSet::load(Path::new(labels_path))?)),
+            None => None,
+        };
+        match self.kind {
+            BackendKind::Python => {
+                let script_path = self.script_path.clone().ok_or("缺少推理脚本路径")?;
+                let python_executable = self
+                    .python_executable
+                    .clone()
+                    .unwrap_or_else(python_env::current_executable);
+                println!(
+                    "启动模型进程，使用Python: {}, 模型: {}",
+                    python_executable, model.name
+                );
+                println!("使用脚本路径: {}", script_path);
+                println!("使用模型路径: {}", self.model_path);
+
+                let mut service = PythonService::new(
+                    python_executable,
+                    script_path,
+                    self.model_path.clone(),
+                    self.protocol,
+                    Duration::from_secs(config.python_startup_timeout_secs),
+                    Duration::from_secs(config.inference_timeout_secs),
+                )
+                .map_err(|e| e.to_string())?;
+
+                // 进程崩溃时通知前端，并标明是哪个模型
+                let crash_handle = app_handle.clone();
+                let model_id = model.id.clone();
+                service.on_crash(move |mut event| {
+                    event.model_id = Some(model_id.clone());
+                    if let Err(e) = crash_handle.emit(WORKER_CRASHED_EVENT, event) {
+                        println!("发送崩溃事件失败: {}", e);
+                    }
+                });
+                if let Some(labels) = labels {
+                    service.set_labels(labels);
+                }
+                service.set_task(model.task);
+                Ok(Arc::new(service))
+            }
+            BackendKind::Onnx => {
+                if model.task == ModelTask::Detection {
+                    return Err("ONNX后端暂不支持检测模型，请使用Python推理脚本".to_string());
+                }
+                println!("加载ONNX模型: {}, 路径: {}", model.name, self.model_path);
+                Ok(Arc::new(OnnxBackend::new(
+                    model,
+                    self.model_path.clone().into(),
+                    labels,
+                )))
+            }
+            BackendKind::Remote => {
+                let remote = config
+                    .remote_inference
+                    .as_ref()
+                    .ok_or("未配置远程推理服务")?;
+                println!(
+                    "使用远程推理服务: {}, 模型: {}",
+                    remote.endpoint, model.name
+                );
+                Ok(RemoteBackend::new(remote, &model.id, labels)?)
+            }
+            BackendKind::Mock => {
+                println!("使用模拟后端: {}", model.name);
+                let mut backend = match labels {
+                    Some(labels) => {
+                        MockBackend::new(model.name.clone(), model.model_type.clone(), labels)
+                    }
+                    None => MockBackend::from_model(model)?,
+                };
+                backend.set_task(model.task);
+                Ok(Arc::new(backend))
+            }
+            BackendKind::Ensemble => {
+                let labels = labels.ok_or("集成模型需要类别文件")?;
+                println!("加载集成模型: {}", model.name);
+                Ok(Arc::new(EnsembleBackend::new(app_handle, model, labels)?))
+            }
+        }
+    }
+}
+
+/// 模型实际使用的推理后端，配置了远程推理服务时除集成模型外都使用远程后端
+pub fn effective_backend(model: &ModelInfo) -> BackendKind {
+    match model.backend_kind() {
+        BackendKind::Ensemble => BackendKind::Ensemble,
+        _ if constants::get_config().remote_inference.is_some() => BackendKind::Remote,
+        kind => kind,
+    }
+}
+
+/// 获取模型对应的推理后端，由模型声明的后端类型决定使用哪种实现
+///
+/// 返回的布尔值表示是否为新创建的后端，调用方按需等待加载完成。
+/// 需要新建后端时先在阻塞线程上校验模型文件，校验期间不持有进程池锁。
+pub async fn acquire_backend(
+    app_handle: &AppHandle,
+    model: &ModelInfo,
+) -> Result<(Arc<dyn InferenceBackend>, bool), String> {
+    let spec = BackendSpec::resolve(app_handle, model)?;
+    {
+        let mut pool = WORKER_POOL.lock().map_err(|_| "无法获取模型进程池锁")?;
+        if let Some(backend) = pool.get(&model.id, &spec) {
+            return Ok((backend, false));
+        }
+    }
+
+    spec.verify_async(model).await?;
+
+    let mut pool = WORKER_POOL.lock().map_err(|_| "无法获取模型进程池锁")?;
+    pool.acquire(app_handle, model, spec)
+}
+
+/// 获取模型对应的推理后端，并等待模型加载完成
+///
+/// 用于后台任务：即使模型由其他请求启动、仍在加载中，也等待其就绪而不是返回“加载中”。
+pub async fn acquire_ready(
+    app_handle: &AppHandle,
+    model: &ModelInfo,
+) -> Result<Arc<dyn InferenceBackend>, String> {
+    let (backend, _) = acquire_backend(app_handle, model).await?;
+    backend.load().await.map_err(|e| e.to_string())?;
+    Ok(backend)
+}
+
+/// 对模型输出做softmax，取概率最高的类别并按类别表整理为识别结果
+pub fn result_from_logits(logits: &[f32], labels: &LabelSet) -> Result<ModelResult, ServiceError> {
+    if logits.is_empty() || logits.len() != labels.len() {
+        return Err(ServiceError::Worker {
+            code: WorkerErrorCode::InferenceError,
+            message: format!(
+                "模型输出 {} 个类别，但有 {} 个类别名称",
+                logits.len(),
+                labels.len()
+            ),
+        });
+    }
+
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let exps: Vec<f32> = logits.iter().map(|value| (value - max).exp()).collect();
+    let sum: f32 = exps.iter().sum();
+
+    let mut ranked: Vec<(usize, f32)> = exps
+        .into_iter()
+        .map(|value| value / sum)
+        .enumerate()
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranked.truncate(TOP_N);
+
+    labels.result(&ranked)
+}
+
+/// 对多标签模型的输出逐个做sigmoid，按类别阈值整理为多标签结果
+pub fn result_from_multi_label_logits(
+    logits: &[f32],
+    labels: &LabelSet,
+) -> Result<ModelResult, ServiceError> {
+    if logits.is_empty() || logits.len() != labels.len() {
+        return Err(ServiceError::Worker {
+            code: WorkerErrorCode::InferenceError,
+            message: format!(
+                "模型输出 {} 个类别，但有 {} 个类别名称",
+                logits.len(),
+                labels.len()
+            ),
+        });
+    }
+
+    let scores: Vec<(usize, f32)> = logits
+        .iter()
+        .map(|value| 1.0 / (1.0 + (-value).exp()))
+        .enumerate()
+        .collect();
+    labels.multi_label_result(&scores, TOP_N)
+}
+
+/// 检查识别选项的取值范围
+pub fn validate_options(options: &RecognitionOptions) -> Result<(), String> {
+    if options.top_k == Some(0) {
+        return Err("top_k 至少为 1".to_string());
+    }
+    let thresholds = [
+        ("min_confidence", options.min_confidence),
+        ("unknown_threshold", options.unknown_threshold),
+    ];
+    for (name, value) in thresholds {
+        if let Some(value) = value {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(format!("{} 应在 0 到 1 之间: {}", name, value));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 按识别选项筛选候选类别并标记不确定的结果，使用的选项记录在结果中
+///
+/// 置信度最高的类别总是保留，`prediction` 和 `confidence` 不变。检测结果按同样的规则
+/// 筛选目标，再由保留的目标重新汇总各类别的得分。多标签结果的阳性类别由类别阈值决定，
+/// 不受选项影响。
+pub fn apply_options(mut result: ModelResult, options: &RecognitionOptions) -> ModelResult {
+    let keep = |rank: usize, probability: f32| {
+        rank == 0
+            || (options.top_k.is_none_or(|top_k| rank < top_k)
+                && options
+                    .min_confidence
+                    .is_none_or(|min_confidence| probability >= min_confidence))
+    };
+
+    if result.task == ModelTask::Detection {
+        let detections = result
+            .detections
+            .into_iter()
+            .enumerate()
+            .filter(|(rank, detection)| keep(*rank, detection.score))
+            .map(|(_, detection)| detection)
+            .collect();
+        result = ModelResult {
+            model_type: result.model_type,
+            ..detection_result(detections)
+        };
+    } else {
+        result.top_classes = result
+            .top_classes
+            .into_iter()
+            .enumerate()
+            .filter(|(rank, score)| keep(*rank, score.probability))
+            .map(|(_, score)| score)
+            .collect();
+
+        let mut ranked: Vec<(String, f32)> = result.class_probabilities.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        result.class_probabilities = ranked
+            .into_iter()
+            .enumerate()
+            .filter(|(rank, (_, probability))| keep(*rank, *probability))
+            .map(|(_, entry)| entry)
+            .collect();
+    }
+
+    result.uncertain = options
+        .unknown_threshold
+        .is_some_and(|threshold| result.confidence < threshold);
+    result.options = Some(options.clone());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(
+        top_k: Option<usize>,
+        min_confidence: Option<f32>,
+        unknown_threshold: Option<f32>,
+    ) -> RecognitionOptions {
+        RecognitionOptions {
+            top_k,
+            min_confidence,
+            unknown_threshold,
+        }
+    }
+
+    /// 模拟后端识别一个临时文件得到的结果
+    async fn mock_result(num_classes: usize) -> ModelResult {
+        let names = (0..num_classes).map(|i| format!("类别{}", i)).collect();
+        let labels = Arc::new(LabelSet::from_names(names).unwrap());
+        let backend = MockBackend::new("mock".to_string(), "mock".to_string(), labels);
+
+        let path =
+            std::env::temp_dir().join(format!("vision_match_inference_{}.bin", std::process::id()));
+        std::fs::write(&path, b"vision-match test image").unwrap();
+        let result = backend.infer(path.to_str().unwrap()).await;
+        let _ = std::fs::remove_file(&path);
+        result.unwrap()
+    }
+
+    #[test]
+    fn validate_options_checks_ranges() {
+        assert!(validate_options(&RecognitionOptions::default()).is_ok());
+        assert!(validate_options(&options(Some(1), Some(0.0), Some(1.0))).is_ok());
+        assert!(validate_options(&options(Some(0), None, None)).is_err());
+        assert!(validate_options(&options(None, Some(1.5), None)).is_err());
+        assert!(validate_options(&options(None, None, Some(-0.1))).is_err());
+    }
+
+    #[tokio::test]
+    async fn top_k_limits_candidates_of_mock_result() {
+        let result = mock_result(20).await;
+        assert_eq!(result.top_classes.len(), TOP_N);
+        let (prediction, confidence) = (result.prediction.clone(), result.confidence);
+
+        let result = apply_options(result, &options(Some(3), None, None));
+        assert_eq!(result.top_classes.len(), 3);
+        assert_eq!(result.class_probabilities.len(), 3);
+        assert_eq!(result.top_classes[0].label, prediction);
+        assert_eq!(result.confidence, confidence);
+        assert!(!result.uncertain);
+        assert_eq!(result.options, Some(options(Some(3), None, None)));
+    }
+
+    #[tokio::test]
+    async fn top_class_is_always_kept() {
+        let result = mock_result(20).await;
+        let prediction = result.prediction.clone();
+
+        let result = apply_options(result, &options(Some(1), Some(1.0), Some(1.0)));
+        assert_eq!(result.top_classes.len(), 1);
+        assert_eq!(result.prediction, prediction);
+        assert!(result.class_probabilities.contains_key(&prediction));
+        assert!(result.uncertain);
+    }
+
+    #[test]
+    fn min_confidence_filters_low_candidates() {
+        let labels = LabelSet::from_names(vec!["a".into(), "b".into(), "c".into()]).unwrap();
+        let result = labels.result(&[(0, 0.6), (1, 0.3), (2, 0.1)]).unwrap();
+
+        let result = apply_options(result, &options(None, Some(0.2), Some(0.5)));
+        let ids: Vec<&str> = result
+            .top_classes
+            .iter()
+            .map(|score| score.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["class_0", "class_1"]);
+        assert_eq!(result.class_probabilities.len(), 2);
+        assert!(!result.uncertain);
+    }
+}
@@ -0,0 +1,520 @@
+use crate::config::constants::ModelIntegrityConfig;
+use crate::models::inference_result::{ModelInfo, ModelSignature};
+use crate::utils::file::calculate_path_hash;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use lazy_static::lazy_static;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use thiserror::Error;
+
+/// 签名内容的版本标识，签名格式变化时修改
+const SIGNING_CONTEXT: &str = "vision-match-model-v1";
+
+/// Python 推理脚本导入的辅助脚本，与推理脚本放在同一目录
+pub const HELPER_SCRIPTS: &[&str] = &["worker_protocol.py", "grad_cam.py", "features.py"];
+
+lazy_static! {
+    /// 已计算过的文件摘要，文件元数据不变时不再重新计算
+    static ref DIGEST_CACHE: Mutex<HashMap<PathBuf, CachedDigest>> = Mutex::new(HashMap::new());
+}
+
+struct CachedDigest {
+    stamp: FileStamp,
+    sha256: String,
+}
+
+/// 判断文件是否变化的元数据
+///
+/// 修改时间可以由写文件的进程任意设置，只比较大小和修改时间时，写入同样大小的内容后
+/// 还原修改时间就能绕过校验。Unix 下同时比较设备号、inode 和状态变更时间（ctime），
+/// 替换文件或修改内容都会改变它们，而 ctime 无法由普通进程设置。其它平台没有可靠的
+/// 对应字段，带签名的模型在这些平台上不使用缓存，每次加载都重新计算摘要。
+#[derive(PartialEq, Eq)]
+struct FileStamp {
+    len: u64,
+    modified: SystemTime,
+    #[cfg(unix)]
+    inode: (u64, u64),
+    #[cfg(unix)]
+    changed: (i64, i64),
+}
+
+impl FileStamp {
+    /// 无法获取修改时间时返回 None，此类文件不缓存
+    fn of(metadata: &std::fs::Metadata) -> Option<Self> {
+        #[cfg(unix)]
+        use std::os::unix::fs::MetadataExt;
+
+        Some(Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok()?,
+            #[cfg(unix)]
+            inode: (metadata.dev(), metadata.ino()),
+            #[cfg(unix)]
+            changed: (metadata.ctime(), metadata.ctime_nsec()),
+        })
+    }
+}
+
+/// 清空摘要缓存，导入或安装模型后调用，避免沿用同一路径上旧文件的结果
+pub fn clear_digest_cache() {
+    if let Ok(mut cache) = DIGEST_CACHE.lock() {
+        cache.clear();
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum IntegrityError {
+    #[error("无法读取{kind} {path}: {message}")]
+    Read {
+        kind: &'static str,
+        path: String,
+        message: String,
+    },
+
+    #[error("{kind}的SHA-256与模型清单不一致，文件可能已被篡改或损坏: {path}（期望 {expected}，实际 {actual}）")]
+    DigestMismatch {
+        kind: &'static str,
+        path: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("模型清单没有记录{0}的SHA-256，已拒绝加载")]
+    MissingDigest(&'static str),
+
+    #[error("模型没有发布者签名，已拒绝加载")]
+    MissingSignature,
+
+    #[error("签名公钥 {0} 不在受信任的发布者列表中")]
+    UntrustedKey(String),
+
+    #[error("受信任的发布者公钥 {0} 格式错误")]
+    InvalidKey(String),
+
+    #[error("模型签名格式错误")]
+    MalformedSignature,
+
+    #[error("模型签名无效，文件摘要与发布者 {0} 签名的内容不一致")]
+    BadSignature(String),
+}
+
+/// 签名的内容：模型ID与权重文件、推理脚本和辅助脚本的摘要，没有脚本时后两项为空
+pub fn signing_message(
+    model_id: &str,
+    weights_sha256: &str,
+    script_sha256: Option<&str>,
+    helpers_sha256: Option<&str>,
+) -> String {
+    format!(
+        "{}\n{}\n{}\n{}\n{}\n",
+        SIGNING_CONTEXT,
+        model_id,
+        weights_sha256.to_ascii_lowercase(),
+        script_sha256.unwrap_or_default().to_ascii_lowercase(),
+        helpers_sha256.unwrap_or_default().to_ascii_lowercase()
+    )
+}
+
+/// 计算推理脚本所在目录中辅助脚本的合并摘要
+///
+/// 按 [`HELPER_SCRIPTS`] 的顺序每个脚本一行 `文件名 SHA-256`，目录中没有的脚本摘要为空，
+/// 结果为这些行的 SHA-256。
+pub fn helpers_digest(script_dir: &Path) -> io::Result<String> {
+    helpers_digest_with(script_dir, calculate_path_hash)
+}
+
+fn helpers_digest_with(
+    script_dir: &Path,
+    digest: impl Fn(&Path) -> io::Result<String>,
+) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    for name in HELPER_SCRIPTS {
+        let path = script_dir.join(name);
+        let sha256 = if path.exists() {
+            digest(&path)?
+        } else {
+            String::new()
+        };
+        hasher.update(format!("{} {}\n", name, sha256).as_bytes());
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// 在加载模型前校验文件摘要和发布者签名
+///
+/// 清单记录了摘要的文件总会校验；没有摘要或签名时按配置决定是否拒绝。
+/// `script_path` 为空表示该后端不运行推理脚本，此时也不校验辅助脚本。
+pub fn verify_model(
+    model: &ModelInfo,
+    weights_path: &Path,
+    script_path: Option<&Path>,
+    config: &ModelIntegrityConfig,
+) -> Result<(), IntegrityError> {
+    // 见 FileStamp：没有 ctime 的平台上签名模型总是重新计算摘要
+    let cached = cfg!(unix) || model.signature.is_none();
+    let digest = |path: &Path| {
+        if cached {
+            file_digest(path)
+        } else {
+            calculate_path_hash(path)
+        }
+    };
+    verify_digest(
+        "权重文件",
+        weights_path,
+        model.weights_sha256.as_deref(),
+        config.require_digests,
+        || digest(weights_path),
+    )?;
+    if let Some(script_path) = script_path {
+        verify_digest(
+            "推理脚本",
+            script_path,
+            model.script_sha256.as_deref(),
+            config.require_digests,
+            || digest(script_path),
+        )?;
+        let script_dir = script_path.parent().unwrap_or(Path::new(""));
+        verify_digest(
+            "辅助脚本",
+            script_dir,
+            model.helpers_sha256.as_deref(),
+            config.require_digests,
+            || helpers_digest_with(script_dir, digest),
+        )?;
+    }
+
+    match &model.signature {
+        Some(signature) => verify_signature(model, signature, config),
+        None if config.require_signatures => Err(IntegrityError::MissingSignature),
+        None => Ok(()),
+    }
+}
+
+/// 校验签名覆盖了模型记录的文件摘要，且由受信任的发布者签发
+pub fn verify_signature(
+    model: &ModelInfo,
+    signature: &ModelSignature,
+    config: &ModelIntegrityConfig,
+) -> Result<(), IntegrityError> {
+    let weights_sha256 = model
+        .weights_sha256
+        .as_deref()
+        .ok_or(IntegrityError::MissingDigest("权重文件"))?;
+    if !model.script_path.is_empty() {
+        if model.script_sha256.is_none() {
+            return Err(IntegrityError::MissingDigest("推理脚本"));
+        }
+        if model.helpers_sha256.is_none() {
+            return Err(IntegrityError::MissingDigest("辅助脚本"));
+        }
+    }
+
+    let trusted = config
+        .trusted_keys
+        .iter()
+        .find(|key| key.id == signature.key_id)
+        .ok_or_else(|| IntegrityError::UntrustedKey(signature.key_id.clone()))?;
+    let key_bytes: [u8; 32] = BASE64
+        .decode(trusted.public_key.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| IntegrityError::InvalidKey(trusted.id.clone()))?;
+    let key = VerifyingKey::from_bytes(&key_bytes)
+        .map_err(|_| IntegrityError::InvalidKey(trusted.id.clone()))?;
+    let signature_bytes: [u8; 64] = BASE64
+        .decode(signature.signature.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(IntegrityError::MalformedSignature)?;
+
+    let message = signing_message(
+        &model.id,
+        weights_sha256,
+        model.script_sha256.as_deref(),
+        model.helpers_sha256.as_deref(),
+    );
+    key.verify(message.as_bytes(), &Signature::from_bytes(&signature_bytes))
+        .map_err(|_| {
+            let publisher = if trusted.name.is_empty() {
+                trusted.id.clone()
+            } else {
+                trusted.name.clone()
+            };
+            IntegrityError::BadSignature(publisher)
+        })
+}
+
+/// 比较 `actual` 计算出的摘要与清单记录的摘要，`path` 用于错误信息
+fn verify_digest(
+    kind: &'static str,
+    path: &Path,
+    expected: Option<&str>,
+    required: bool,
+    actual: impl FnOnce() -> io::Result<String>,
+) -> Result<(), IntegrityError> {
+    let Some(expected) = expected else {
+        return if required {
+            Err(IntegrityError::MissingDigest(kind))
+        } else {
+            Ok(())
+        };
+    };
+
+    let actual = actual().map_err(|e| IntegrityError::Read {
+        kind,
+        path: path.display().to_string(),
+        message: e.to_string(),
+    })?;
+    if actual.eq_ignore_ascii_case(expected.trim()) {
+        Ok(())
+    } else {
+        Err(IntegrityError::DigestMismatch {
+            kind,
+            path: path.display().to_string(),
+            expected: expected.trim().to_ascii_lowercase(),
+            actual,
+        })
+    }
+}
+
+/// 计算文件摘要，文件元数据（见 [`FileStamp`]）未变化时使用上次的结果
+///
+/// 无法获取修改时间的文件每次都重新计算。
+pub fn file_digest(path: &Path) -> io::Result<String> {
+    let stamp = FileStamp::of(&std::fs::metadata(path)?);
+    if let (Some(stamp), Ok(cache)) = (&stamp, DIGEST_CACHE.lock()) {
+        if let Some(cached) = cache.get(path) {
+            if cached.stamp == *stamp {
+                return Ok(cached.sha256.clone());
+            }
+        }
+    }
+
+    let sha256 = calculate_path_hash(path)?;
+    if let (Some(stamp), Ok(mut cache)) = (stamp, DIGEST_CACHE.lock()) {
+        cache.insert(
+            path.to_path_buf(),
+            CachedDigest {
+                stamp,
+                sha256: sha256.clone(),
+            },
+        );
+    }
+    Ok(sha256)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::constants::TrustedKey;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    /// 已知内容 "abc" 的SHA-256
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    fn temp_file(name: &str, content: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "vision_match_integrity_{}_{}",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn model(weights_sha256: Option<&str>, signature: Option<ModelSignature>) -> ModelInfo {
+        serde_json::from_value(serde_json::json!({
+            "id": "demo",
+            "name": "演示模型",
+            "description": "",
+            "path": "models/demo.onnx",
+            "model_type": "demo",
+            "num_classes": 2,
+            "script_path": "",
+            "is_active": false,
+            "weights_sha256": weights_sha256,
+            "signature": signature,
+        }))
+        .unwrap()
+    }
+
+    fn publisher() -> (SigningKey, ModelIntegrityConfig) {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let config = ModelIntegrityConfig {
+            require_digests: true,
+            require_signatures: true,
+            trusted_keys: vec![TrustedKey {
+                id: "publisher".to_string(),
+                name: "发布者".to_string(),
+                public_key: BASE64.encode(key.verifying_key().to_bytes()),
+            }],
+        };
+        (key, config)
+    }
+
+    fn sign(key: &SigningKey, model_id: &str, weights_sha256: &str) -> ModelSignature {
+        let message = signing_message(model_id, weights_sha256, None, None);
+        ModelSignature {
+            key_id: "publisher".to_string(),
+            signature: BASE64.encode(key.sign(message.as_bytes()).to_bytes()),
+        }
+    }
+
+    #[test]
+    fn signing_message_is_normalized() {
+        assert_eq!(
+            signing_message("demo", "ABCD", Some("Ef01"), Some("23AB")),
+            "vision-match-model-v1\ndemo\nabcd\nef01\n23ab\n"
+        );
+        assert_eq!(
+            signing_message("demo", "abcd", None, None),
+            "vision-match-model-v1\ndemo\nabcd\n\n\n"
+        );
+    }
+
+    #[test]
+    fn digests_are_checked_against_files() {
+        let path = temp_file("digest", b"abc");
+        let config = ModelIntegrityConfig::default();
+
+        let upper = ABC_SHA256.to_ascii_uppercase();
+        assert!(verify_model(&model(Some(&upper), None), &path, None, &config).is_ok());
+        assert!(matches!(
+            verify_model(&model(Some(&"0".repeat(64)), None), &path, None, &config),
+            Err(IntegrityError::DigestMismatch { .. })
+        ));
+        assert!(verify_model(&model(None, None), &path, None, &config).is_ok());
+
+        let strict = ModelIntegrityConfig {
+            require_digests: true,
+            ..config
+        };
+        assert!(matches!(
+            verify_model(&model(None, None), &path, None, &strict),
+            Err(IntegrityError::MissingDigest(_))
+        ));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn helper_scripts_are_checked_with_the_script() {
+        let dir = std::env::temp_dir().join(format!(
+            "vision_match_integrity_{}_helpers",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("model_val.py");
+        std::fs::write(&script, b"abc").unwrap();
+        std::fs::write(dir.join("worker_protocol.py"), b"protocol").unwrap();
+        std::fs::write(dir.join("grad_cam.py"), b"grad_cam").unwrap();
+
+        let mut model = model(None, None);
+        model.script_sha256 = Some(ABC_SHA256.to_string());
+        model.helpers_sha256 = Some(helpers_digest(&dir).unwrap());
+        let config = ModelIntegrityConfig::default();
+        assert!(verify_model(&model, &script, Some(&script), &config).is_ok());
+
+        // 修改或新增辅助脚本都会改变合并摘要
+        std::fs::write(dir.join("grad_cam.py"), b"tampered").unwrap();
+        assert!(matches!(
+            verify_model(&model, &script, Some(&script), &config),
+            Err(IntegrityError::DigestMismatch {
+                kind: "辅助脚本",
+                ..
+            })
+        ));
+        std::fs::write(dir.join("grad_cam.py"), b"grad_cam").unwrap();
+        std::fs::write(dir.join("features.py"), b"features").unwrap();
+        assert!(verify_model(&model, &script, Some(&script), &config).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rewritten_file_with_restored_mtime_is_rehashed() {
+        let path = temp_file("rewritten", b"abc");
+        let config = ModelIntegrityConfig::default();
+        assert!(verify_model(&model(Some(ABC_SHA256), None), &path, None, &config).is_ok());
+
+        // 写入同样长度的内容后还原修改时间，只比较大小和修改时间时会沿用缓存
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        std::fs::write(&path, b"abd").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        assert!(matches!(
+            verify_model(&model(Some(ABC_SHA256), None), &path, None, &config),
+            Err(IntegrityError::DigestMismatch { .. })
+        ));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn trusted_signature_is_accepted() {
+        let path = temp_file("signed", b"abc");
+        let (key, config) = publisher();
+        let signature = sign(&key, "demo", ABC_SHA256);
+
+        assert!(verify_model(
+            &model(Some(ABC_SHA256), Some(signature)),
+            &path,
+            None,
+            &config
+        )
+        .is_ok());
+        assert!(matches!(
+            verify_model(&model(Some(ABC_SHA256), None), &path, None, &config),
+            Err(IntegrityError::MissingSignature)
+        ));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn signature_must_cover_model_and_trusted_key() {
+        let (key, config) = publisher();
+        let digest = "0".repeat(64);
+
+        // 签名对应另一个模型ID
+        let other = sign(&key, "other", &digest);
+        assert!(matches!(
+            verify_signature(&model(Some(&digest), None), &other, &config),
+            Err(IntegrityError::BadSignature(name)) if name == "发布者"
+        ));
+
+        let stranger = SigningKey::from_bytes(&[9u8; 32]);
+        let forged = sign(&stranger, "demo", &digest);
+        assert!(matches!(
+            verify_signature(&model(Some(&digest), None), &forged, &config),
+            Err(IntegrityError::BadSignature(_))
+        ));
+
+        let unknown = ModelSignature {
+            key_id: "unknown".to_string(),
+            ..sign(&key, "demo", &digest)
+        };
+        assert!(matches!(
+            verify_signature(&model(Some(&digest), None), &unknown, &config),
+            Err(IntegrityError::UntrustedKey(_))
+        ));
+
+        let malformed = ModelSignature {
+            key_id: "publisher".to_string(),
+            signature: "not base64".to_string(),
+        };
+        assert!(matches!(
+            verify_signature(&model(Some(&digest), None), &malformed, &config),
+            Err(IntegrityError::MalformedSignature)
+        ));
+    }
+}
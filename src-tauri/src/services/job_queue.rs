@@ -0,0 +1,173 @@
+use crate::config::models::MODEL_REGISTRY;
+use crate::db::db_client::get_database;
+use crate::db::histories_collection::{ImageHistory, ImageHistoryRepository, RecognitionStatus};
+use crate::db::images_collection::ImageRepository;
+use crate::models::inference_result::{ModelInfo, ModelResult, RecognitionJobEvent};
+use crate::services::inference::acquire_ready;
+use crate::services::python::ServiceError;
+use crate::utils::path_utils::get_app_data_path;
+use lazy_static::lazy_static;
+use mongodb::bson::DateTime;
+use std::path::Path;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Notify;
+
+lazy_static! {
+    /// 有新任务入队时唤醒队列
+    static ref JOB_QUEUE_WAKER: Notify = Notify::new();
+}
+
+/// 队列中的任务结束时发送的事件名
+pub const RECOGNITION_JOB_EVENT: &str = "recognition-job-finished";
+
+/// 任务被领取的次数上限，超过后启动时不再重新排队
+const MAX_JOB_ATTEMPTS: i32 = 3;
+
+/// 队列为空时重新检查数据库的间隔，用于发现其他途径写入的待处理记录
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 数据库未连接或出错时的重试间隔
+const DB_RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 任务失败的两种情况
+enum JobFailure {
+    /// 推理脚本拒绝了该图片，记为 Failed
+    Failed(String),
+    /// 图片、模型或进程等系统问题，记为 Error
+    Error(String),
+}
+
+/// 通知队列有新任务
+pub fn notify_job_queue() {
+    JOB_QUEUE_WAKER.notify_one();
+}
+
+/// 运行后台识别队列，应在应用启动时运行一次
+///
+/// 启动时先处理上次退出时遗留的处理中任务，然后按创建时间依次执行待处理任务。
+pub async fn run_job_queue(app_handle: AppHandle) {
+    // 数据库连接在启动时异步建立，等待连接完成
+    while get_database().is_err() {
+        tokio::time::sleep(DB_RETRY_INTERVAL).await;
+    }
+
+    match ImageHistoryRepository::reset_processing(MAX_JOB_ATTEMPTS).await {
+        Ok((requeued, abandoned)) if requeued > 0 || abandoned > 0 => println!(
+            "恢复中断的识别任务: 重新排队 {} 个, 放弃 {} 个",
+            requeued, abandoned
+        ),
+        Ok(_) => {}
+        Err(e) => println!("恢复中断的识别任务失败: {}", e),
+    }
+
+    loop {
+        match ImageHistoryRepository::claim_next_pending().await {
+            Ok(Some(job)) => run_job(&app_handle, job).await,
+            Ok(None) => {
+                tokio::select! {
+                    _ = JOB_QUEUE_WAKER.notified() => {}
+                    _ = tokio::time::sleep(IDLE_POLL_INTERVAL) => {}
+                }
+            }
+            Err(e) => {
+                println!("领取识别任务失败: {}", e);
+                tokio::time::sleep(DB_RETRY_INTERVAL).await;
+            }
+        }
+    }
+}
+
+/// 执行一个任务并记录结果
+async fn run_job(app_handle: &AppHandle, job: ImageHistory) {
+    let Some(history_id) = job.id else {
+        return;
+    };
+    let started_at = job.started_at.unwrap_or_else(DateTime::now);
+    println!("开始识别任务: {}", history_id);
+
+    let outcome = recognize(app_handle, &job).await;
+    let (status, result, error_message) = match outcome {
+        Ok(result) => (RecognitionStatus::Success, Some(result), None),
+        Err(JobFailure::Failed(e)) => (RecognitionStatus::Failed, None, Some(e)),
+        Err(JobFailure::Error(e)) => (RecognitionStatus::Error, None, Some(e)),
+    };
+
+    let duration_ms = match ImageHistoryRepository::finish_job(
+        history_id,
+        status.clone(),
+        result.as_ref().and_then(|r| serde_json::to_value(r).ok()),
+        result.as_ref().map(|r| r.confidence as f64),
+        error_message.as_deref(),
+        started_at,
+    )
+    .await
+    {
+        Ok(duration_ms) => Some(duration_ms),
+        Err(e) => {
+            println!("保存识别任务结果失败: {}", e);
+            None
+        }
+    };
+
+    println!("识别任务 {} 结束: {:?}", history_id, status);
+    let event = RecognitionJobEvent {
+        history_id: history_id.to_hex(),
+        image_id: job.image_id.to_hex(),
+        status,
+        result,
+        error_message,
+        duration_ms,
+    };
+    if let Err(e) = app_handle.emit(RECOGNITION_JOB_EVENT, event) {
+        println!("发送识别任务事件失败: {}", e);
+    }
+}
+
+/// 识别任务对应的图片
+async fn recognize(app_handle: &AppHandle, job: &ImageHistory) -> Result<ModelResult, JobFailure> {
+    let model = resolve_model(job).map_err(JobFailure::Error)?;
+
+    let image = ImageRepository::find_by_id(&job.image_id.to_hex())
+        .await
+        .map_err(|e| JobFailure::Error(format!("查询图片失败: {}", e)))?
+        .ok_or_else(|| JobFailure::Error("图片记录不存在".to_string()))?;
+    let storage_path = image
+        .storage_path
+        .ok_or_else(|| JobFailure::Error("图片没有存储路径".to_string()))?;
+    let image_abs_path = get_app_data_path(app_handle, &storage_path).map_err(JobFailure::Error)?;
+    if !Path::new(&image_abs_path).exists() {
+        return Err(JobFailure::Error(format!(
+            "图像文件不存在: {}",
+            image_abs_path
+        )));
+    }
+
+    let backend = acquire_ready(app_handle, &model)
+        .await
+        .map_err(JobFailure::Error)?;
+    let mut result = backend.infer(&image_abs_path).await.map_err(|e| match e {
+        ServiceError::Worker { .. } => JobFailure::Failed(e.to_string()),
+        _ => JobFailure::Error(e.to_string()),
+    })?;
+
+    result.model_type = Some(model.model_type.clone());
+    Ok(result)
+}
+
+/// 确定任务使用的模型：优先使用记录的模型ID，旧记录按模型名称（模型类型）匹配
+fn resolve_model(job: &ImageHistory) -> Result<ModelInfo, String> {
+    let registry = MODEL_REGISTRY.lock().map_err(|_| "无法获取模型注册表锁")?;
+
+    if let Some(model_id) = &job.model_id {
+        return registry
+            .get_model(model_id)
+            .ok_or_else(|| format!("模型ID不存在: {}", model_id));
+    }
+
+    registry
+        .get_models()
+        .into_iter()
+        .find(|model| model.model_type == job.model_name)
+        .ok_or_else(|| format!("找不到模型: {}", job.model_name))
+}
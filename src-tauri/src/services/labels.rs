@@ -0,0 +1,527 @@
+use crate::models::inference_result::{
+    BoundingBox, ClassScore, Detection, ModelResult, ModelTask, WorkerErrorCode,
+};
+use crate::services::python::ServiceError;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::RwLock;
+
+/// 默认的类别名称语言
+pub const DEFAULT_LOCALE: &str = "zh-CN";
+
+/// 多标签模型中类别文件没有声明阈值时使用的阈值
+pub const DEFAULT_MULTI_LABEL_THRESHOLD: f32 = 0.5;
+
+lazy_static! {
+    /// 当前显示类别名称使用的语言，启动时从配置恢复，运行中可以切换
+    static ref LABEL_LOCALE: RwLock<String> = RwLock::new(DEFAULT_LOCALE.to_string());
+}
+
+/// 当前的类别名称语言
+pub fn current_locale() -> String {
+    LABEL_LOCALE
+        .read()
+        .map(|locale| locale.clone())
+        .unwrap_or_else(|_| DEFAULT_LOCALE.to_string())
+}
+
+/// 切换类别名称语言，之后的识别结果使用新语言
+pub fn set_locale(locale: &str) {
+    if let Ok(mut current) = LABEL_LOCALE.write() {
+        *current = locale.to_string();
+    }
+}
+
+/// 类别文件中的一个类别，数组下标即模型输出的类别索引
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassLabel {
+    /// 稳定的类别ID，模型重新训练、类别顺序变化时保持不变
+    pub id: String,
+    /// 各语言的显示名称，如 `zh-CN`、`en`，`la` 为拉丁学名
+    pub names: BTreeMap<String, String>,
+    /// 多标签模型中该类别判为阳性的最低得分，为空时使用类别文件的默认阈值
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f32>,
+}
+
+/// 结构化的类别文件（`.json`）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabelFile {
+    /// 缺少所选语言的名称时使用的语言
+    #[serde(default = "default_locale")]
+    pub default_locale: String,
+    /// 多标签模型的默认阈值
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_threshold: Option<f32>,
+    pub classes: Vec<ClassLabel>,
+}
+
+fn default_locale() -> String {
+    DEFAULT_LOCALE.to_string()
+}
+
+/// 模型的类别表，把类别索引映射为稳定ID和各语言名称
+#[derive(Debug, Clone)]
+pub struct LabelSet {
+    classes: Vec<ClassLabel>,
+    default_locale: String,
+    default_threshold: f32,
+    indexes: HashMap<String, usize>,
+}
+
+impl LabelSet {
+    /// 读取类别文件
+    ///
+    /// `.json` 为结构化类别文件；其它扩展名按纯文本处理，每行一个类别名称，
+    /// 类别ID为 `class_<索引>`。
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("无法读取类别名称文件 {}: {}", path.display(), e))?;
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+
+        let result = if is_json {
+            serde_json::from_str::<LabelFile>(&content)
+                .map_err(|e| format!("类别文件格式错误: {}", e))
+                .and_then(Self::from_file)
+        } else {
+            let names = content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(String::from)
+                .collect();
+            Self::from_names(names)
+        };
+        result.map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// 由结构化类别文件创建，类别ID不能为空或重复，阈值必须在 0~1 之间
+    pub fn from_file(file: LabelFile) -> Result<Self, String> {
+        if file.classes.is_empty() {
+            return Err("类别列表为空".to_string());
+        }
+        let thresholds = file.default_threshold.iter().chain(
+            file.classes
+                .iter()
+                .filter_map(|class| class.threshold.as_ref()),
+        );
+        for threshold in thresholds {
+            if !(0.0..=1.0).contains(threshold) {
+                return Err(format!("类别阈值 {} 超出 0~1 的范围", threshold));
+            }
+        }
+        let mut indexes = HashMap::with_capacity(file.classes.len());
+        for (index, class) in file.classes.iter().enumerate() {
+            if class.id.trim().is_empty() {
+                return Err(format!("第 {} 个类别缺少ID", index));
+            }
+            if indexes.insert(class.id.clone(), index).is_some() {
+                return Err(format!("类别ID重复: {}", class.id));
+            }
+        }
+        Ok(Self {
+            classes: file.classes,
+            default_locale: file.default_locale,
+            default_threshold: file
+                .default_threshold
+                .unwrap_or(DEFAULT_MULTI_LABEL_THRESHOLD),
+            indexes,
+        })
+    }
+
+    /// 由按输出顺序排列的名称创建，名称作为默认语言的显示名称
+    pub fn from_names(names: Vec<String>) -> Result<Self, String> {
+        let classes = names
+            .into_iter()
+            .enumerate()
+            .map(|(index, name)| ClassLabel {
+                id: format!("class_{}", index),
+                names: BTreeMap::from([(DEFAULT_LOCALE.to_string(), name)]),
+                threshold: None,
+            })
+            .collect();
+        Self::from_file(LabelFile {
+            default_locale: default_locale(),
+            default_threshold: None,
+            classes,
+        })
+    }
+
+    /// 类别数量
+    pub fn len(&self) -> usize {
+        self.classes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+
+    /// 类别文件中出现的所有语言
+    pub fn locales(&self) -> Vec<String> {
+        let mut locales: Vec<String> = self
+            .classes
+            .iter()
+            .flat_map(|class| class.names.keys().cloned())
+            .collect();
+        locales.sort();
+        locales.dedup();
+        locales
+    }
+
+    /// 多标签模型中类别判为阳性的最低得分
+    pub fn threshold(&self, index: usize) -> f32 {
+        self.classes[index]
+            .threshold
+            .unwrap_or(self.default_threshold)
+    }
+
+    /// 按类别ID查找索引
+    pub fn index_of(&self, id: &str) -> Option<usize> {
+        self.indexes.get(id).copied()
+    }
+
+    /// 按类别ID或任一语言的名称查找索引，用于对齐没有类别ID的结果
+    pub fn find(&self, key: &str) -> Option<usize> {
+        self.index_of(key).or_else(|| {
+            self.classes
+                .iter()
+                .position(|class| class.names.values().any(|name| name == key))
+        })
+    }
+
+    /// 类别在指定语言下的显示名称
+    ///
+    /// 依次尝试完全匹配、同一语种（如 `en-US` 与 `en`）和类别文件的默认语言，
+    /// 都没有时使用类别ID。
+    pub fn name(&self, index: usize, locale: &str) -> &str {
+        let class = &self.classes[index];
+        let language = |tag: &str| tag.split(['-', '_']).next().unwrap_or(tag).to_lowercase();
+        class
+            .names
+            .iter()
+            .find(|(tag, _)| tag.eq_ignore_ascii_case(locale))
+            .or_else(|| {
+                let wanted = language(locale);
+                class.names.iter().find(|(tag, _)| language(tag) == wanted)
+            })
+            .map(|(_, name)| name)
+            .or_else(|| class.names.get(&self.default_locale))
+            .map_or(&class.id, |name| name)
+    }
+
+    /// 由按概率排列的 (类别索引, 概率) 构建识别结果，名称使用当前语言
+    ///
+    /// 不同类别在当前语言下同名时，`class_probabilities` 中的键附加类别ID以免互相覆盖。
+    pub fn result(&self, ranked: &[(usize, f32)]) -> Result<ModelResult, ServiceError> {
+        if let Some((index, _)) = ranked.iter().find(|(index, _)| *index >= self.len()) {
+            return Err(ServiceError::Worker {
+                code: WorkerErrorCode::InferenceError,
+                message: format!("类别索引 {} 超出范围，模型共 {} 个类别", index, self.len()),
+            });
+        }
+        let Some(&(top_index, confidence)) = ranked.first() else {
+            return Err(ServiceError::Worker {
+                code: WorkerErrorCode::InferenceError,
+                message: "推理结果中没有类别".to_string(),
+            });
+        };
+
+        let locale = current_locale();
+        let top_classes: Vec<ClassScore> = ranked
+            .iter()
+            .map(|&(index, probability)| ClassScore {
+                index: index as u32,
+                id: self.classes[index].id.clone(),
+                label: self.name(index, &locale).to_string(),
+                probability,
+            })
+            .collect();
+
+        Ok(ModelResult {
+            task: ModelTask::Classification,
+            prediction: self.name(top_index, &locale).to_string(),
+            confidence,
+            class_probabilities: probability_map(&top_classes),
+            model_type: None,
+            class_id: Some(self.classes[top_index].id.clone()),
+            top_classes,
+            uncertain: false,
+            options: None,
+            detections: Vec::new(),
+            positive_labels: Vec::new(),
+            heatmap_path: None,
+            embedding: None,
+        })
+    }
+
+    /// 由各类别独立的得分构建多标签结果，得分达到类别阈值的为阳性类别
+    ///
+    /// `top_classes` 保留得分最高的 `top_n` 个类别和所有阳性类别；`prediction`、`confidence`
+    /// 为得分最高的阳性类别，没有阳性类别时 `prediction` 为空、置信度为 0。
+    pub fn multi_label_result(
+        &self,
+        scores: &[(usize, f32)],
+        top_n: usize,
+    ) -> Result<ModelResult, ServiceError> {
+        let mut ranked = scores.to_vec();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        if let Some((index, _)) = ranked.iter().find(|(index, _)| *index >= self.len()) {
+            return Err(ServiceError::Worker {
+                code: WorkerErrorCode::InferenceError,
+                message: format!("类别索引 {} 超出范围，模型共 {} 个类别", index, self.len()),
+            });
+        }
+        let positive = |index: usize, score: f32| score >= self.threshold(index);
+        let kept: Vec<(usize, f32)> = ranked
+            .iter()
+            .enumerate()
+            .filter(|(rank, &(index, score))| *rank < top_n || positive(index, score))
+            .map(|(_, &entry)| entry)
+            .collect();
+
+        let result = self.result(&kept)?;
+        let positive_labels: Vec<ClassScore> = result
+            .top_classes
+            .iter()
+            .filter(|score| positive(score.index as usize, score.probability))
+            .cloned()
+            .collect();
+        let best = positive_labels.first();
+        Ok(ModelResult {
+            task: ModelTask::MultiLabel,
+            prediction: best.map(|score| score.label.clone()).unwrap_or_default(),
+            confidence: best.map_or(0.0, |score| score.probability),
+            class_id: best.map(|score| score.id.clone()),
+            positive_labels,
+            ..result
+        })
+    }
+
+    /// 由类别索引创建一个检测目标，名称使用当前语言
+    pub fn detection(
+        &self,
+        index: usize,
+        score: f32,
+        bbox: BoundingBox,
+    ) -> Result<Detection, ServiceError> {
+        if index >= self.len() {
+            return Err(ServiceError::Worker {
+                code: WorkerErrorCode::InferenceError,
+                message: format!("类别索引 {} 超出范围，模型共 {} 个类别", index, self.len()),
+            });
+        }
+        Ok(Detection {
+            class_index: Some(index as u32),
+            class_id: Some(self.classes[index].id.clone()),
+            label: self.name(index, &current_locale()).to_string(),
+            score,
+            bbox,
+        })
+    }
+
+    /// 按类别ID把其它地方（如远程服务）生成的结果换成当前语言的名称
+    ///
+    /// 结果中没有类别ID或ID不在本类别表中时保持不变。
+    pub fn localize(&self, result: ModelResult) -> ModelResult {
+        if result.task == ModelTask::Detection {
+            let locale = current_locale();
+            let detections = result
+                .detections
+                .into_iter()
+                .map(|mut detection| {
+                    let index = detection
+                        .class_id
+                        .as_deref()
+                        .and_then(|id| self.index_of(id));
+                    if let Some(index) = index {
+                        detection.label = self.name(index, &locale).to_string();
+                    }
+                    detection
+                })
+                .collect();
+            return ModelResult {
+                model_type: result.model_type,
+                ..detection_result(detections)
+            };
+        }
+
+        let ranked: Option<Vec<(usize, f32)>> = result
+            .top_classes
+            .iter()
+            .map(|score| Some((self.index_of(&score.id)?, score.probability)))
+            .collect();
+        let rebuild = |ranked: &[(usize, f32)]| match result.task {
+            ModelTask::MultiLabel => self.multi_label_result(ranked, ranked.len()),
+            _ => self.result(ranked),
+        };
+        match ranked {
+            Some(ranked) if !ranked.is_empty() => match rebuild(&ranked) {
+                Ok(localized) => ModelResult {
+                    model_type: result.model_type,
+                    ..localized
+                },
+                Err(_) => result,
+            },
+            _ => result,
+        }
+    }
+}
+
+/// 由检测目标汇总识别结果，目标按得分从高到低排列
+///
+/// `prediction`、`confidence` 取得分最高的目标；`top_classes` 和 `class_probabilities`
+/// 为每个类别的最高得分。没有目标时 `prediction` 为空、置信度为 0。
+pub fn detection_result(mut detections: Vec<Detection>) -> ModelResult {
+    detections.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    let mut top_classes: Vec<ClassScore> = Vec::new();
+    for detection in &detections {
+        let id = detection
+            .class_id
+            .clone()
+            .unwrap_or_else(|| detection.label.clone());
+        if top_classes.iter().all(|score| score.id != id) {
+            top_classes.push(ClassScore {
+                index: detection.class_index.unwrap_or_default(),
+                id,
+                label: detection.label.clone(),
+                probability: detection.score,
+            });
+        }
+    }
+
+    let best = detections.first();
+    ModelResult {
+        task: ModelTask::Detection,
+        prediction: best.map(|d| d.label.clone()).unwrap_or_default(),
+        confidence: best.map_or(0.0, |d| d.score),
+        class_probabilities: probability_map(&top_classes),
+        model_type: None,
+        class_id: best.and_then(|d| d.class_id.clone()),
+        top_classes,
+        uncertain: false,
+        options: None,
+        detections,
+        positive_labels: Vec::new(),
+        heatmap_path: None,
+        embedding: None,
+    }
+}
+
+/// 类别名称到概率的映射，不同类别同名时键附加类别ID以免互相覆盖
+fn probability_map(top_classes: &[ClassScore]) -> HashMap<String, f32> {
+    let mut name_counts: HashMap<&str, usize> = HashMap::new();
+    for score in top_classes {
+        *name_counts.entry(score.label.as_str()).or_default() += 1;
+    }
+    top_classes
+        .iter()
+        .map(|score| {
+            let key = if name_counts[score.label.as_str()] > 1 {
+                format!("{} ({})", score.label, score.id)
+            } else {
+                score.label.clone()
+            };
+            (key, score.probability)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels() -> LabelSet {
+        let file: LabelFile = serde_json::from_value(serde_json::json!({
+            "default_locale": "zh-CN",
+            "default_threshold": 0.4,
+            "classes": [
+                { "id": "amanita_muscaria", "names": { "zh-CN": "毒蝇伞", "en": "Fly agaric", "la": "Amanita muscaria" }, "threshold": 0.7 },
+                { "id": "boletus_edulis", "names": { "zh-CN": "美味牛肝菌", "en": "Porcini" } },
+                { "id": "unnamed", "names": { "la": "Species incognita" } }
+            ]
+        }))
+        .unwrap();
+        LabelSet::from_file(file).unwrap()
+    }
+
+    #[test]
+    fn names_create_positional_ids() {
+        let labels = LabelSet::from_names(vec!["猫".to_string(), "狗".to_string()]).unwrap();
+        assert_eq!(labels.len(), 2);
+        assert_eq!(labels.index_of("class_1"), Some(1));
+        assert_eq!(labels.name(0, DEFAULT_LOCALE), "猫");
+        assert_eq!(labels.locales(), vec![DEFAULT_LOCALE.to_string()]);
+    }
+
+    #[test]
+    fn index_of_matches_ids_only() {
+        let labels = labels();
+        assert_eq!(labels.index_of("boletus_edulis"), Some(1));
+        assert_eq!(labels.index_of("Porcini"), None);
+    }
+
+    #[test]
+    fn find_matches_ids_and_names_in_any_locale() {
+        let labels = labels();
+        assert_eq!(labels.find("boletus_edulis"), Some(1));
+        assert_eq!(labels.find("美味牛肝菌"), Some(1));
+        assert_eq!(labels.find("Fly agaric"), Some(0));
+        assert_eq!(labels.find("Amanita muscaria"), Some(0));
+        assert_eq!(labels.find("porcini"), None);
+    }
+
+    #[test]
+    fn name_falls_back_by_language_then_default_locale_then_id() {
+        let labels = labels();
+        assert_eq!(labels.name(0, "en"), "Fly agaric");
+        assert_eq!(labels.name(0, "EN-us"), "Fly agaric");
+        assert_eq!(labels.name(0, "la"), "Amanita muscaria");
+        assert_eq!(labels.name(1, "fr"), "美味牛肝菌");
+        assert_eq!(labels.name(2, "en"), "unnamed");
+        assert_eq!(labels.locales(), vec!["en", "la", "zh-CN"]);
+    }
+
+    #[test]
+    fn thresholds_use_class_then_file_default() {
+        let labels = labels();
+        assert_eq!(labels.threshold(0), 0.7);
+        assert_eq!(labels.threshold(1), 0.4);
+    }
+
+    #[test]
+    fn invalid_label_files_are_rejected() {
+        let file = |classes: serde_json::Value| -> LabelFile {
+            serde_json::from_value(serde_json::json!({ "classes": classes })).unwrap()
+        };
+        let duplicate = file(serde_json::json!([
+            { "id": "a", "names": {} },
+            { "id": "a", "names": {} }
+        ]));
+        assert!(LabelSet::from_file(duplicate).is_err());
+        assert!(
+            LabelSet::from_file(file(serde_json::json!([{ "id": " ", "names": {} }]))).is_err()
+        );
+        assert!(LabelSet::from_file(file(serde_json::json!([
+            { "id": "a", "names": {}, "threshold": 1.5 }
+        ])))
+        .is_err());
+        assert!(LabelSet::from_file(file(serde_json::json!([]))).is_err());
+    }
+
+    #[test]
+    fn result_rejects_out_of_range_index() {
+        let labels = labels();
+        assert!(labels.result(&[(3, 0.9)]).is_err());
+        assert!(labels.result(&[]).is_err());
+
+        let result = labels.result(&[(1, 0.8), (0, 0.2)]).unwrap();
+        assert_eq!(result.class_id.as_deref(), Some("boletus_edulis"));
+        assert_eq!(result.top_classes[1].id, "amanita_muscaria");
+        assert_eq!(result.class_probabilities.len(), 2);
+    }
+}
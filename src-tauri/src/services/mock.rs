@@ -0,0 +1,220 @@
+use crate::models::inference_result::{
+    ActivationMap, BoundingBox, ModelInfo, ModelResult, ModelTask, ServiceStatus, WorkerErrorCode,
+    WorkerInfo,
+};
+use crate::services::inference::{
+    result_from_logits, result_from_multi_label_logits, InferenceBackend,
+};
+use crate::services::labels::{detection_result, LabelSet};
+use crate::services::python::ServiceError;
+use futures::future::BoxFuture;
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// 模拟推理后端，不加载任何模型
+///
+/// 结果只由图片文件内容决定：同一张图片总是得到相同的类别和置信度，
+/// 用于在没有模型文件和Python环境时开发界面或测试识别流程。
+/// 检测模型同样按文件内容生成 1~3 个目标，多标签模型的各类别得分以 0 为中心；
+/// 类激活图是以文件内容决定的位置为中心的光斑；特征向量为缩小到 4×4 的图片像素，
+/// 内容相近的图片得到相近的向量。
+pub struct MockBackend {
+    labels: Arc<LabelSet>,
+    task: ModelTask,
+    info: WorkerInfo,
+    unloaded: AtomicBool,
+}
+
+impl MockBackend {
+    pub fn new(name: String, model_type: String, labels: Arc<LabelSet>) -> Self {
+        let info = WorkerInfo {
+            name,
+            model_type,
+            num_classes: labels.len() as u32,
+            device: "mock".to_string(),
+            protocol_version: 0,
+            capabilities: Vec::new(),
+        };
+        Self {
+            labels,
+            task: ModelTask::Classification,
+            info,
+            unloaded: AtomicBool::new(false),
+        }
+    }
+
+    /// 按模型声明的类别数生成 `class_0`、`class_1` ... 作为类别名称
+    pub fn from_model(model: &ModelInfo) -> Result<Self, String> {
+        let labels = (0..model.num_classes.max(1))
+            .map(|i| format!("class_{}", i))
+            .collect();
+        let labels = Arc::new(LabelSet::from_names(labels)?);
+        Ok(Self::new(
+            model.name.clone(),
+            model.model_type.clone(),
+            labels,
+        ))
+    }
+
+    /// 设置模拟的任务类型
+    pub fn set_task(&mut self, task: ModelTask) {
+        self.task = task;
+    }
+
+    fn ensure_loaded(&self) -> Result<(), ServiceError> {
+        if self.unloaded.load(Ordering::Relaxed) {
+            return Err(ServiceError::ModelLoadFailed("模型已卸载".to_string()));
+        }
+        Ok(())
+    }
+
+    async fn digest(&self, image_path: &str) -> Result<Vec<u8>, ServiceError> {
+        self.ensure_loaded()?;
+        let data = tokio::fs::read(image_path)
+            .await
+            .map_err(|e| ServiceError::Worker {
+                code: WorkerErrorCode::ImageError,
+                message: format!("无法读取图像: {}", e),
+            })?;
+        Ok(Sha256::digest(&data).to_vec())
+    }
+
+    async fn run(&self, image_path: &str) -> Result<ModelResult, ServiceError> {
+        let digest = self.digest(image_path).await?;
+        if self.task == ModelTask::Detection {
+            return self.detect(&digest);
+        }
+
+        // 用文件摘要的字节作为各类别的得分
+        let logits: Vec<f32> = (0..self.labels.len())
+            .map(|i| (digest[i % digest.len()] ^ (i / digest.len()) as u8) as f32 / 32.0)
+            .collect();
+        if self.task == ModelTask::MultiLabel {
+            let logits: Vec<f32> = logits.iter().map(|value| value - 4.0).collect();
+            return result_from_multi_label_logits(&logits, &self.labels);
+        }
+        result_from_logits(&logits, &self.labels)
+    }
+
+    async fn run_explain(
+        &self,
+        image_path: &str,
+    ) -> Result<(ModelResult, ActivationMap), ServiceError> {
+        const SIZE: u32 = 14;
+        let result = self.run(image_path).await?;
+        let digest = self.digest(image_path).await?;
+
+        let center_x = 0.2 + digest[30] as f32 / 255.0 * 0.6;
+        let center_y = 0.2 + digest[31] as f32 / 255.0 * 0.6;
+        let values = (0..SIZE * SIZE)
+            .map(|i| {
+                let x = ((i % SIZE) as f32 + 0.5) / SIZE as f32 - center_x;
+                let y = ((i / SIZE) as f32 + 0.5) / SIZE as f32 - center_y;
+                (-(x * x + y * y) / 0.04).exp()
+            })
+            .collect();
+        let map = ActivationMap {
+            width: SIZE,
+            height: SIZE,
+            values,
+        };
+        Ok((result, map))
+    }
+
+    async fn run_embed(&self, image_path: &str) -> Result<(ModelResult, Vec<f32>), ServiceError> {
+        const SIZE: u32 = 4;
+        let result = self.run(image_path).await?;
+
+        let path = image_path.to_string();
+        let thumbnail = tokio::task::spawn_blocking(move || {
+            image::open(&path).map(|image| image.thumbnail_exact(SIZE, SIZE).to_rgb8())
+        })
+        .await
+        .map_err(|e| ServiceError::Protocol(format!("提取特征向量失败: {}", e)))?
+        .map_err(|e| ServiceError::Worker {
+            code: WorkerErrorCode::ImageError,
+            message: format!("无法解码图像: {}", e),
+        })?;
+
+        // 减去均值后，整体亮度不同但构图相近的图片仍然相似
+        let pixels: Vec<f32> = thumbnail
+            .into_raw()
+            .iter()
+            .map(|&v| v as f32 / 255.0)
+            .collect();
+        let mean = pixels.iter().sum::<f32>() / pixels.len() as f32;
+        let embedding = pixels.iter().map(|value| value - mean).collect();
+        Ok((result, embedding))
+    }
+
+    /// 用文件摘要的字节生成目标的类别、得分和位置，每个目标使用 5 个字节
+    fn detect(&self, digest: &[u8]) -> Result<ModelResult, ServiceError> {
+        let count = 1 + digest[0] as usize % 3;
+        let detections = digest[1..]
+            .chunks_exact(5)
+            .take(count)
+            .map(|bytes| {
+                let unit = |byte: u8| byte as f32 / 255.0;
+                let x_min = unit(bytes[2]) * 0.5;
+                let y_min = unit(bytes[3]) * 0.5;
+                let bbox = BoundingBox {
+                    x_min,
+                    y_min,
+                    x_max: x_min + 0.1 + unit(bytes[4]) * 0.4,
+                    y_max: y_min + 0.1 + unit(bytes[2] ^ bytes[3]) * 0.4,
+                };
+                let score = 0.3 + unit(bytes[1]) * 0.7;
+                self.labels
+                    .detection(bytes[0] as usize % self.labels.len(), score, bbox)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(detection_result(detections))
+    }
+}
+
+impl InferenceBackend for MockBackend {
+    fn load(&self) -> BoxFuture<'_, Result<(), ServiceError>> {
+        Box::pin(async move { self.ensure_loaded() })
+    }
+
+    fn infer<'a>(
+        &'a self,
+        image_path: &'a str,
+    ) -> BoxFuture<'a, Result<ModelResult, ServiceError>> {
+        Box::pin(self.run(image_path))
+    }
+
+    fn explain<'a>(
+        &'a self,
+        image_path: &'a str,
+    ) -> BoxFuture<'a, Result<(ModelResult, ActivationMap), ServiceError>> {
+        Box::pin(self.run_explain(image_path))
+    }
+
+    fn embed<'a>(
+        &'a self,
+        image_path: &'a str,
+    ) -> BoxFuture<'a, Result<(ModelResult, Vec<f32>), ServiceError>> {
+        Box::pin(self.run_embed(image_path))
+    }
+
+    fn unload(&self) -> BoxFuture<'_, ()> {
+        self.unloaded.store(true, Ordering::Relaxed);
+        Box::pin(async {})
+    }
+
+    fn info(&self) -> BoxFuture<'_, Result<WorkerInfo, ServiceError>> {
+        Box::pin(async move { self.ensure_loaded().map(|_| self.info.clone()) })
+    }
+
+    fn status(&self) -> ServiceStatus {
+        if self.unloaded.load(Ordering::Relaxed) {
+            ServiceStatus::Stopped
+        } else {
+            ServiceStatus::Ready {
+                info: Some(self.info.clone()),
+            }
+        }
+    }
+}
@@ -1 +1,16 @@
+pub mod diagnostics;
+pub mod ensemble;
+pub mod explain;
+pub mod inference;
+pub mod integrity;
+pub mod job_queue;
+pub mod labels;
+pub mod mock;
+pub mod model_package;
+pub mod model_store;
+pub mod onnx;
 pub mod python;
+pub mod python_env;
+pub mod remote;
+pub mod similarity;
+pub mod worker_pool;
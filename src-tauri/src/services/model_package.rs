@@ -0,0 +1,810 @@
+use crate::config::constants::{self, ModelIntegrityConfig};
+use crate::config::models::{ModelManifest, ModelSample, MODEL_REGISTRY, USER_MANIFEST_DIR};
+use crate::models::inference_result::{
+    BackendKind, ModelInfo, ModelPackageReport, ModelResult, PackageSampleResult,
+};
+use crate::services::inference::{acquire_ready, BackendSpec};
+use crate::services::integrity;
+use crate::services::model_store::{
+    copy_worker_protocol, find_model_by_digest, hash_file, hash_helpers, register_model,
+    smoke_test, user_model_dir, ImportGuard, SAMPLE_IMAGE, USER_MODEL_DIR,
+};
+use crate::services::python::ServiceError;
+use crate::utils::file::calculate_path_hash;
+use crate::utils::path_utils::get_resource_path;
+use mongodb::bson::oid::ObjectId;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use tauri::{AppHandle, Manager};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// 模型包的扩展名
+pub const PACKAGE_EXTENSION: &str = "wvmodel";
+
+/// 模型包中的清单文件，其中的路径相对于模型包根目录
+const PACKAGE_MANIFEST: &str = "manifest.json";
+
+/// 示例图片在模型包中的目录
+const PACKAGE_SAMPLE_DIR: &str = "samples";
+
+/// 验证示例时允许的置信度误差，不同设备上的计算结果略有差异
+const CONFIDENCE_TOLERANCE: f32 = 0.05;
+
+/// 验证模型包：解压到临时目录，检查文件哈希值和签名，并用包中的示例图片试运行
+///
+/// 模型包没有示例时用内置示例图片试运行，只检查能否正常识别。
+/// 没有签名的 Python 模型包只有在 `allow_unsigned_scripts` 为 true 时才会运行其中的脚本。
+pub async fn verify_package(
+    app_handle: &AppHandle,
+    package_path: &Path,
+    allow_unsigned_scripts: bool,
+) -> Result<ModelPackageReport, String> {
+    let staging_dir =
+        std::env::temp_dir().join(format!("vision-match-package-{}", ObjectId::new().to_hex()));
+
+    let result = async {
+        let mut manifest = unpack(package_path, &staging_dir).await?;
+        prepare_helpers(app_handle, &mut manifest, &staging_dir).await?;
+        check_trust(
+            &manifest,
+            allow_unsigned_scripts,
+            &constants::get_config().model_integrity,
+        )?;
+        check_samples(app_handle, &manifest, &staging_dir).await
+    }
+    .await;
+
+    remove_staging_dir(&staging_dir);
+    result
+}
+
+/// 安装模型包，验证通过后作为用户模型注册
+///
+/// 模型ID与已有模型相同、权重与已有模型相同、签名不可信或示例结果不一致时拒绝安装，
+/// 没有签名的 Python 模型包需要 `allow_unsigned_scripts`。
+/// 安装的模型与导入的模型一样，通过 `remove_model` 卸载。
+pub async fn install_package(
+    app_handle: &AppHandle,
+    package_path: &Path,
+    allow_unsigned_scripts: bool,
+) -> Result<ModelInfo, String> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("无法获取应用数据目录: {}", e))?;
+    // 解压到模型目录旁，验证通过后直接改名
+    let staging_dir = app_data_dir
+        .join(USER_MODEL_DIR)
+        .join(format!(".package-{}", ObjectId::new().to_hex()));
+
+    let staged = async {
+        let mut manifest = unpack(package_path, &staging_dir).await?;
+        let model_dir = user_model_dir(&app_data_dir, &manifest.id)
+            .ok_or(format!("无效的模型ID: {}", manifest.id))?;
+        let digest = manifest.weights_sha256.clone().unwrap_or_default();
+        let guard = ImportGuard::acquire(&[&manifest.id, &digest])?;
+
+        let registered = MODEL_REGISTRY
+            .lock()
+            .map_err(|_| "无法获取模型注册表锁")?
+            .get_model(&manifest.id);
+        if let Some(existing) = registered {
+            return Err(format!("模型ID已存在: {} ({})", manifest.id, existing.name));
+        }
+        if let Some(existing) = find_model_by_digest(app_handle, &digest).await {
+            return Err(format!("该模型已存在: {}", existing.name));
+        }
+
+        prepare_helpers(app_handle, &mut manifest, &staging_dir).await?;
+        check_trust(
+            &manifest,
+            allow_unsigned_scripts,
+            &constants::get_config().model_integrity,
+        )?;
+        let report = check_samples(app_handle, &manifest, &staging_dir).await?;
+        if !report.passed {
+            return Err(format!("示例验证失败: {}", describe_failures(&report)));
+        }
+        Ok((manifest, model_dir, guard))
+    }
+    .await;
+
+    let (mut manifest, model_dir, _guard) = match staged {
+        Ok(staged) => staged,
+        Err(e) => {
+            remove_staging_dir(&staging_dir);
+            return Err(e);
+        }
+    };
+
+    // 清理上次安装失败时留下的文件
+    if model_dir.exists() {
+        if let Err(e) = fs::remove_dir_all(&model_dir) {
+            remove_staging_dir(&staging_dir);
+            return Err(format!("无法清理模型目录: {}", e));
+        }
+    }
+    if let Err(e) = fs::rename(&staging_dir, &model_dir) {
+        remove_staging_dir(&staging_dir);
+        return Err(format!("无法移动模型文件: {}", e));
+    }
+    integrity::clear_digest_cache();
+
+    // 清单中的路径改为相对于应用数据目录
+    manifest.prefix_paths(&format!("{}/{}", USER_MODEL_DIR, manifest.id));
+    let manifest_dir = app_data_dir.join(USER_MANIFEST_DIR);
+    let manifest_path = manifest_dir.join(format!("{}.json", manifest.id));
+    let saved = fs::create_dir_all(&manifest_dir)
+        .map_err(|e| format!("无法创建清单目录: {}", e))
+        .and_then(|_| manifest.save(&manifest_path));
+    if let Err(e) = saved {
+        remove_staging_dir(&model_dir);
+        return Err(e);
+    }
+
+    let model = manifest.into_model_info(Some(&app_data_dir));
+    let model = register_model(model, manifest_path, &model_dir)?;
+
+    println!("模型包安装成功: {} ({})", model.name, model.id);
+    Ok(model)
+}
+
+/// 将已注册的模型导出为模型包，返回模型包路径
+///
+/// 模型清单中有示例时一并导出；没有时用内置示例图片识别一次，把结果作为期望输出，
+/// 识别失败时导出的模型包不含示例。
+pub async fn export_package(
+    app_handle: &AppHandle,
+    model_id: &str,
+    output_path: &Path,
+) -> Result<PathBuf, String> {
+    let (model, manifest_path) = {
+        let registry = MODEL_REGISTRY.lock().map_err(|_| "无法获取模型注册表锁")?;
+        let model = registry
+            .get_model(model_id)
+            .ok_or(format!("模型ID不存在: {}", model_id))?;
+        (model, registry.get_manifest_path(model_id))
+    };
+    if model.backend_kind() == BackendKind::Ensemble {
+        return Err("集成模型不能导出为模型包，请分别导出各成员模型".to_string());
+    }
+
+    let mut entries: Vec<(String, PathBuf)> = Vec::new();
+    let weights_path = PathBuf::from(get_resource_path(app_handle, &model.path)?);
+    let weights = add_entry(&mut entries, weights_path.clone())?;
+    let script_path = if model.script_path.is_empty() {
+        None
+    } else {
+        Some(PathBuf::from(get_resource_path(
+            app_handle,
+            &model.script_path,
+        )?))
+    };
+    let script = match &script_path {
+        Some(script_path) => Some(add_entry(&mut entries, script_path.clone())?),
+        None => None,
+    };
+    // 辅助脚本随推理脚本一并导出，安装时不使用安装方应用中可能不同版本的辅助脚本
+    if let Some(script_dir) = script_path.as_deref().and_then(Path::parent) {
+        for name in integrity::HELPER_SCRIPTS {
+            let helper = script_dir.join(name);
+            if helper.is_file() {
+                add_entry(&mut entries, helper)?;
+            }
+        }
+    }
+    let labels = match &model.labels_path {
+        Some(labels_path) => Some(add_entry(
+            &mut entries,
+            get_resource_path(app_handle, labels_path)?.into(),
+        )?),
+        None => None,
+    };
+
+    // 用户模型清单中的示例路径相对于应用数据目录
+    let mut samples: Vec<(PathBuf, ModelSample)> = Vec::new();
+    if let Some(manifest_path) = manifest_path {
+        let app_data_dir = app_handle
+            .path()
+            .app_data_dir()
+            .map_err(|e| format!("无法获取应用数据目录: {}", e))?;
+        for sample in ModelManifest::from_file(&manifest_path)?.samples {
+            samples.push((app_data_dir.join(&sample.image), sample));
+        }
+    }
+    if samples.is_empty() {
+        samples.extend(record_sample(app_handle, &model).await);
+    }
+    let samples = samples
+        .into_iter()
+        .map(|(source, mut sample)| {
+            let file_name = file_name(&source)?;
+            sample.image = format!("{}/{}", PACKAGE_SAMPLE_DIR, file_name);
+            entries.push((sample.image.clone(), source));
+            Ok(sample)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let manifest = ModelManifest {
+        id: model.id.clone(),
+        name: model.name.clone(),
+        description: model.description.clone(),
+        weights,
+        model_type: model.model_type.clone(),
+        num_classes: model.num_classes,
+        backend: model.backend,
+        script,
+        protocol: model.protocol,
+        task: model.task,
+        labels,
+        weights_sha256: Some(hash_file(weights_path).await?),
+        script_sha256: match &script_path {
+            Some(script_path) => Some(hash_file(script_path.clone()).await?),
+            None => None,
+        },
+        helpers_sha256: match &script_path {
+            Some(script_path) => Some(hash_helpers(script_path.clone()).await?),
+            None => None,
+        },
+        // 模型ID和文件摘要不变，发布者的签名在导出后仍然有效
+        signature: model.signature.clone(),
+        default: false,
+        samples,
+        ensemble: None,
+    };
+
+    let mut output_path = output_path.to_path_buf();
+    if output_path.extension().is_none() {
+        output_path.set_extension(PACKAGE_EXTENSION);
+    }
+    let package_path = output_path.clone();
+    tokio::task::spawn_blocking(move || write_package(&package_path, &manifest, &entries))
+        .await
+        .map_err(|e| format!("导出模型包失败: {}", e))??;
+
+    println!("模型包已导出: {}", output_path.display());
+    Ok(output_path)
+}
+
+/// 在阻塞线程中解压模型包并检查清单
+async fn unpack(package_path: &Path, target_dir: &Path) -> Result<ModelManifest, String> {
+    let package_path = package_path.to_path_buf();
+    let target_dir = target_dir.to_path_buf();
+    tokio::task::spawn_blocking(move || unpack_blocking(&package_path, &target_dir))
+        .await
+        .map_err(|e| format!("解压模型包失败: {}", e))?
+}
+
+fn unpack_blocking(package_path: &Path, target_dir: &Path) -> Result<ModelManifest, String> {
+    let file = File::open(package_path)
+        .map_err(|e| format!("无法打开模型包 {}: {}", package_path.display(), e))?;
+    let mut archive = ZipArchive::new(file).map_err(|e| format!("模型包格式错误: {}", e))?;
+    fs::create_dir_all(target_dir).map_err(|e| format!("无法创建解压目录: {}", e))?;
+
+    for index in 0..archive.len() {
+        let mut entry = archive
+            .by_index(index)
+            .map_err(|e| format!("模型包格式错误: {}", e))?;
+        let name = entry.name().to_string();
+        let relative = entry
+            .enclosed_name()
+            .ok_or(format!("模型包包含非法路径: {}", name))?;
+        let path = target_dir.join(relative);
+
+        if entry.is_dir() {
+            fs::create_dir_all(&path).map_err(|e| format!("无法解压 {}: {}", name, e))?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("无法解压 {}: {}", name, e))?;
+        }
+        let mut output = File::create(&path).map_err(|e| format!("无法解压 {}: {}", name, e))?;
+        io::copy(&mut entry, &mut output).map_err(|e| format!("无法解压 {}: {}", name, e))?;
+    }
+
+    let mut manifest = ModelManifest::from_file(&target_dir.join(PACKAGE_MANIFEST))?;
+    validate_files(&manifest, target_dir)?;
+
+    manifest.weights_sha256 = Some(check_digest(
+        target_dir,
+        &manifest.weights,
+        manifest.weights_sha256.as_deref(),
+        "权重文件",
+    )?);
+    if let Some(script) = &manifest.script {
+        manifest.script_sha256 = Some(check_digest(
+            target_dir,
+            script,
+            manifest.script_sha256.as_deref(),
+            "推理脚本",
+        )?);
+    }
+    Ok(manifest)
+}
+
+/// 计算模型包中文件的哈希值，清单记录了哈希值时必须一致
+fn check_digest(
+    package_dir: &Path,
+    path: &str,
+    expected: Option<&str>,
+    kind: &str,
+) -> Result<String, String> {
+    let digest = calculate_path_hash(&package_dir.join(path))
+        .map_err(|e| format!("无法读取 {}: {}", path, e))?;
+    match expected {
+        Some(expected) if !expected.eq_ignore_ascii_case(&digest) => {
+            Err(format!("{}的哈希值与清单不一致，模型包可能已损坏", kind))
+        }
+        _ => Ok(digest),
+    }
+}
+
+/// 补齐模型包中缺少的辅助脚本并计算其合并摘要，清单记录了摘要时必须一致
+async fn prepare_helpers(
+    app_handle: &AppHandle,
+    manifest: &mut ModelManifest,
+    package_dir: &Path,
+) -> Result<(), String> {
+    let Some(script) = &manifest.script else {
+        return Ok(());
+    };
+    if manifest_backend(manifest) == BackendKind::Python {
+        copy_worker_protocol(app_handle, package_dir)?;
+    }
+    let digest = hash_helpers(package_dir.join(script)).await?;
+    match &manifest.helpers_sha256 {
+        Some(expected) if !expected.eq_ignore_ascii_case(&digest) => {
+            Err("辅助脚本的哈希值与清单不一致，模型包可能已损坏".to_string())
+        }
+        _ => {
+            manifest.helpers_sha256 = Some(digest);
+            Ok(())
+        }
+    }
+}
+
+/// 检查清单引用的文件都在模型包中
+fn validate_files(manifest: &ModelManifest, package_dir: &Path) -> Result<(), String> {
+    let mut paths = vec![&manifest.weights];
+    paths.extend(&manifest.script);
+    paths.extend(&manifest.labels);
+    paths.extend(manifest.samples.iter().map(|sample| &sample.image));
+
+    for path in paths {
+        let inside = Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        if !inside {
+            return Err(format!("模型包清单中的路径无效: {}", path));
+        }
+        if !package_dir.join(path).is_file() {
+            return Err(format!("模型包中缺少文件: {}", path));
+        }
+    }
+
+    match manifest_backend(manifest) {
+        BackendKind::Python if manifest.script.is_none() => {
+            Err("Python 模型的模型包需要包含推理脚本".to_string())
+        }
+        BackendKind::Remote => Err("远程推理服务中的模型不能打包".to_string()),
+        BackendKind::Ensemble => Err("集成模型不能打包，请分别打包各成员模型".to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// 在运行模型包中的任何代码之前检查签名
+///
+/// 带签名的模型包必须由受信任的发布者签发；没有签名时按 `require_signatures` 拒绝，
+/// Python 模型包的推理脚本会在本机运行，需要用户明确允许运行未签名的脚本。
+fn check_trust(
+    manifest: &ModelManifest,
+    allow_unsigned_scripts: bool,
+    config: &ModelIntegrityConfig,
+) -> Result<(), String> {
+    match &manifest.signature {
+        Some(signature) => {
+            let model = manifest.clone().into_model_info(None);
+            integrity::verify_signature(&model, signature, config).map_err(|e| e.to_string())
+        }
+        None if config.require_signatures => {
+            Err(integrity::IntegrityError::MissingSignature.to_string())
+        }
+        None if manifest_backend(manifest) == BackendKind::Python && !allow_unsigned_scripts => {
+            Err("模型包没有发布者签名，其中的Python推理脚本会在本机运行，请确认信任该模型包后允许运行未签名的脚本".to_string())
+        }
+        None => Ok(()),
+    }
+}
+
+fn manifest_backend(manifest: &ModelManifest) -> BackendKind {
+    manifest
+        .backend
+        .unwrap_or_else(|| BackendKind::from_weights_path(&manifest.weights))
+}
+
+/// 用清单中的示例图片逐张识别，并与期望结果比较
+async fn check_samples(
+    app_handle: &AppHandle,
+    manifest: &ModelManifest,
+    package_dir: &Path,
+) -> Result<ModelPackageReport, String> {
+    let model = manifest.clone().into_model_info(Some(package_dir));
+    if manifest.samples.is_empty() {
+        smoke_test(app_handle, &model)
+            .await
+            .map_err(|e| format!("模型试运行失败: {}", e))?;
+        return Ok(ModelPackageReport {
+            model,
+            samples: Vec::new(),
+            passed: true,
+        });
+    }
+
+    let spec = BackendSpec::for_kind(app_handle, &model, model.backend_kind())?;
+    spec.verify_async(&model).await?;
+    let backend = spec.create(app_handle, &model)?;
+    if let Err(e) = backend.load().await {
+        backend.unload().await;
+        return Err(e.to_string());
+    }
+
+    let mut samples = Vec::with_capacity(manifest.samples.len());
+    for sample in &manifest.samples {
+        let image_path = package_dir.join(&sample.image);
+        let result = backend.infer(&image_path.to_string_lossy()).await;
+        samples.push(compare_sample(sample, result));
+    }
+    backend.unload().await;
+
+    let passed = samples.iter().all(|sample| sample.passed);
+    Ok(ModelPackageReport {
+        model,
+        samples,
+        passed,
+    })
+}
+
+fn compare_sample(
+    sample: &ModelSample,
+    result: Result<ModelResult, ServiceError>,
+) -> PackageSampleResult {
+    let (prediction, confidence, passed, error) = match result {
+        Ok(result) => {
+            let confidence_matches = sample
+                .confidence
+                .map(|expected| (expected - result.confidence).abs() <= CONFIDENCE_TOLERANCE)
+                .unwrap_or(true);
+            let class_matches = match (&sample.class_id, &result.class_id) {
+                (Some(expected), Some(actual)) => expected == actual,
+                _ => result.prediction == sample.prediction,
+            };
+            let passed = class_matches && confidence_matches;
+            (
+                Some(result.prediction),
+                Some(result.confidence),
+                passed,
+                None,
+            )
+        }
+        Err(e) => (None, None, false, Some(e.to_string())),
+    };
+
+    PackageSampleResult {
+        image: sample.image.clone(),
+        expected_prediction: sample.prediction.clone(),
+        expected_confidence: sample.confidence,
+        prediction,
+        confidence,
+        passed,
+        error,
+    }
+}
+
+fn describe_failures(report: &ModelPackageReport) -> String {
+    report
+        .samples
+        .iter()
+        .filter(|sample| !sample.passed)
+        .map(|sample| match (&sample.prediction, &sample.error) {
+            (_, Some(error)) => format!("{}: {}", sample.image, error),
+            (Some(prediction), None) => format!(
+                "{}: 期望 {}，实际 {} ({:.2})",
+                sample.image,
+                sample.expected_prediction,
+                prediction,
+                sample.confidence.unwrap_or_default()
+            ),
+            (None, None) => sample.image.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join("；")
+}
+
+/// 用内置示例图片识别一次，生成导出时的示例
+async fn record_sample(
+    app_handle: &AppHandle,
+    model: &ModelInfo,
+) -> Option<(PathBuf, ModelSample)> {
+    let sample_path = get_resource_path(app_handle, SAMPLE_IMAGE).ok()?;
+    let result = async {
+        let backend = acquire_ready(app_handle, model).await?;
+        backend.infer(&sample_path).await.map_err(|e| e.to_string())
+    }
+    .await;
+
+    match result {
+        Ok(result) => Some((
+            PathBuf::from(&sample_path),
+            ModelSample {
+                image: sample_path,
+                prediction: result.prediction,
+                class_id: result.class_id,
+                confidence: Some(result.confidence),
+            },
+        )),
+        Err(e) => {
+            println!("无法生成示例结果，导出的模型包将不含示例: {}", e);
+            None
+        }
+    }
+}
+
+/// 登记一个放在模型包根目录的文件，返回其在包中的路径
+fn add_entry(entries: &mut Vec<(String, PathBuf)>, source: PathBuf) -> Result<String, String> {
+    let name = file_name(&source)?;
+    if name == PACKAGE_MANIFEST || entries.iter().any(|(entry, _)| *entry == name) {
+        return Err(format!("模型包中的文件名重复: {}", name));
+    }
+    entries.push((name.clone(), source));
+    Ok(name)
+}
+
+fn file_name(path: &Path) -> Result<String, String> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(String::from)
+        .ok_or(format!("无效的文件名: {}", path.display()))
+}
+
+fn write_package(
+    package_path: &Path,
+    manifest: &ModelManifest,
+    entries: &[(String, PathBuf)],
+) -> Result<(), String> {
+    let result = (|| {
+        let manifest_json =
+            serde_json::to_string_pretty(manifest).map_err(|e| format!("序列化清单失败: {}", e))?;
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        let file = File::create(package_path)
+            .map_err(|e| format!("无法创建模型包 {}: {}", package_path.display(), e))?;
+        let mut writer = ZipWriter::new(file);
+        writer
+            .start_file(PACKAGE_MANIFEST, options)
+            .map_err(|e| format!("无法写入模型包: {}", e))?;
+        writer
+            .write_all(manifest_json.as_bytes())
+            .map_err(|e| format!("无法写入模型包: {}", e))?;
+
+        for (name, source) in entries {
+            let mut input =
+                File::open(source).map_err(|e| format!("无法读取 {}: {}", source.display(), e))?;
+            let size = input.metadata().map(|meta| meta.len()).unwrap_or(0);
+            writer
+                .start_file(name.as_str(), options.large_file(size >= u32::MAX as u64))
+                .map_err(|e| format!("无法写入模型包: {}", e))?;
+            io::copy(&mut input, &mut writer).map_err(|e| format!("无法写入模型包: {}", e))?;
+        }
+        writer
+            .finish()
+            .map_err(|e| format!("无法写入模型包: {}", e))?;
+        Ok(())
+    })();
+
+    // 写入失败时删除不完整的模型包
+    if result.is_err() {
+        let _ = fs::remove_file(package_path);
+    }
+    result
+}
+
+fn remove_staging_dir(dir: &Path) {
+    if dir.exists() {
+        if let Err(e) = fs::remove_dir_all(dir) {
+            println!("清理临时目录失败 {}: {}", dir.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::inference_result::ModelSignature;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "vision_match_package_{}_{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn manifest(value: serde_json::Value) -> ModelManifest {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn onnx_manifest(weights: &str) -> ModelManifest {
+        manifest(serde_json::json!({
+            "id": "color",
+            "name": "颜色识别模型",
+            "weights": weights,
+            "num_classes": 2,
+        }))
+    }
+
+    #[test]
+    fn manifest_paths_must_stay_inside_the_package() {
+        let dir = temp_dir("paths");
+        fs::write(dir.join("color.onnx"), b"weights").unwrap();
+        fs::create_dir_all(dir.join("sub")).unwrap();
+
+        assert!(validate_files(&onnx_manifest("color.onnx"), &dir).is_ok());
+        for path in ["../color.onnx", "sub/../color.onnx", "./color.onnx"] {
+            let error = validate_files(&onnx_manifest(path), &dir).unwrap_err();
+            assert!(error.contains("路径无效"), "{}: {}", path, error);
+        }
+        let absolute = dir.join("color.onnx").to_string_lossy().into_owned();
+        assert!(validate_files(&onnx_manifest(&absolute), &dir).is_err());
+        assert!(validate_files(&onnx_manifest("missing.onnx"), &dir)
+            .unwrap_err()
+            .contains("缺少文件"));
+
+        let python = manifest(serde_json::json!({
+            "id": "color",
+            "name": "颜色识别模型",
+            "weights": "color.onnx",
+            "backend": "python",
+            "num_classes": 2,
+        }));
+        assert!(validate_files(&python, &dir).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn archive_entries_outside_the_package_are_rejected() {
+        let dir = temp_dir("zip_slip");
+        let package_path = dir.join("evil.wvmodel");
+        let mut writer = ZipWriter::new(File::create(&package_path).unwrap());
+        writer
+            .start_file("../evil.txt", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"evil").unwrap();
+        writer.finish().unwrap();
+
+        let target = dir.join("unpacked");
+        let error = unpack_blocking(&package_path, &target).unwrap_err();
+        assert!(error.contains("非法路径"), "{}", error);
+        assert!(!dir.join("evil.txt").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn exported_package_unpacks_with_the_same_manifest() {
+        let dir = temp_dir("round_trip");
+        let weights = dir.join("color.onnx");
+        let labels = dir.join("colors.txt");
+        let sample = dir.join("sample.jpg");
+        fs::write(&weights, b"weights").unwrap();
+        fs::write(&labels, "红\n绿\n").unwrap();
+        fs::write(&sample, b"jpeg").unwrap();
+
+        let mut exported = manifest(serde_json::json!({
+            "id": "color",
+            "name": "颜色识别模型",
+            "weights": "color.onnx",
+            "num_classes": 2,
+            "labels": "colors.txt",
+            "samples": [
+                { "image": "samples/sample.jpg", "prediction": "绿", "confidence": 0.86 }
+            ],
+        }));
+        exported.weights_sha256 = Some(calculate_path_hash(&weights).unwrap());
+        let entries = vec![
+            ("color.onnx".to_string(), weights.clone()),
+            ("colors.txt".to_string(), labels),
+            ("samples/sample.jpg".to_string(), sample),
+        ];
+        let package_path = dir.join("color.wvmodel");
+        write_package(&package_path, &exported, &entries).unwrap();
+
+        let target = dir.join("unpacked");
+        let unpacked = unpack_blocking(&package_path, &target).unwrap();
+        assert_eq!(unpacked.id, "color");
+        assert_eq!(unpacked.labels.as_deref(), Some("colors.txt"));
+        assert_eq!(unpacked.weights_sha256, exported.weights_sha256);
+        assert_eq!(unpacked.samples.len(), 1);
+        assert_eq!(
+            fs::read(target.join("samples/sample.jpg")).unwrap(),
+            b"jpeg"
+        );
+
+        // 清单中的摘要与权重文件不一致时拒绝
+        exported.weights_sha256 = Some("0".repeat(64));
+        write_package(&package_path, &exported, &entries).unwrap();
+        let error = unpack_blocking(&package_path, &dir.join("tampered")).unwrap_err();
+        assert!(error.contains("哈希值"), "{}", error);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn unsigned_python_packages_need_explicit_consent() {
+        let config = ModelIntegrityConfig::default();
+        let python = manifest(serde_json::json!({
+            "id": "color",
+            "name": "颜色识别模型",
+            "weights": "color.pth",
+            "script": "color_val.py",
+            "num_classes": 2,
+        }));
+        assert!(check_trust(&python, false, &config).is_err());
+        assert!(check_trust(&python, true, &config).is_ok());
+        assert!(check_trust(&onnx_manifest("color.onnx"), false, &config).is_ok());
+
+        let strict = ModelIntegrityConfig {
+            require_signatures: true,
+            ..ModelIntegrityConfig::default()
+        };
+        assert!(check_trust(&python, true, &strict).is_err());
+        assert!(check_trust(&onnx_manifest("color.onnx"), true, &strict).is_err());
+
+        let mut signed = python.clone();
+        signed.signature = Some(ModelSignature {
+            key_id: "unknown".to_string(),
+            signature: String::new(),
+        });
+        assert!(check_trust(&signed, true, &config).is_err());
+    }
+
+    fn result(prediction: &str, confidence: f32, class_id: Option<&str>) -> ModelResult {
+        serde_json::from_value(serde_json::json!({
+            "prediction": prediction,
+            "confidence": confidence,
+            "class_probabilities": { prediction: confidence },
+            "class_id": class_id,
+        }))
+        .unwrap()
+    }
+
+    fn sample(prediction: &str, confidence: Option<f32>, class_id: Option<&str>) -> ModelSample {
+        ModelSample {
+            image: "samples/sample.jpg".to_string(),
+            prediction: prediction.to_string(),
+            class_id: class_id.map(String::from),
+            confidence,
+        }
+    }
+
+    #[test]
+    fn samples_compare_class_and_confidence() {
+        let expected = sample("绿", Some(0.86), None);
+        assert!(compare_sample(&expected, Ok(result("绿", 0.84, None))).passed);
+        assert!(!compare_sample(&expected, Ok(result("绿", 0.70, None))).passed);
+        assert!(!compare_sample(&expected, Ok(result("红", 0.86, None))).passed);
+        assert!(compare_sample(&sample("绿", None, None), Ok(result("绿", 0.1, None))).passed);
+
+        // 有类别ID时按ID比较，不受显示语言影响
+        let by_id = sample("绿", None, Some("green"));
+        assert!(compare_sample(&by_id, Ok(result("Green", 0.9, Some("green")))).passed);
+        assert!(!compare_sample(&by_id, Ok(result("绿", 0.9, Some("red")))).passed);
+
+        let failed = compare_sample(&expected, Err(ServiceError::Protocol("断开".to_string())));
+        assert!(!failed.passed);
+        assert!(failed.prediction.is_none());
+        assert!(failed.error.unwrap().contains("断开"));
+    }
+}
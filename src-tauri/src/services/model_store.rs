@@ -0,0 +1,492 @@
+use crate::config::constants;
+use crate::config::models::{ModelManifest, MODEL_REGISTRY, USER_MANIFEST_DIR};
+use crate::models::inference_result::{
+    BackendKind, ModelImportRequest, ModelInfo, ModelMetadataUpdate, ModelSource, ModelTask,
+};
+use crate::services::inference::BackendSpec;
+use crate::services::integrity;
+use crate::services::labels::LabelSet;
+use crate::services::python::ServiceError;
+use crate::services::worker_pool::WORKER_POOL;
+use crate::utils::file::calculate_path_hash;
+use crate::utils::path_utils::get_resource_path;
+use lazy_static::lazy_static;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+lazy_static! {
+    /// 正在导入或安装的模型，按模型ID和权重摘要记录
+    static ref PENDING_IMPORTS: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+/// 导入模型的文件目录，相对于应用数据目录，每个模型一个子目录
+pub const USER_MODEL_DIR: &str = "models";
+
+/// 导入时试运行使用的示例图片，相对于资源目录
+pub const SAMPLE_IMAGE: &str = "resources/samples/sample.jpg";
+
+/// 内置推理脚本及其辅助脚本所在的目录，相对于资源目录
+const BUNDLED_SCRIPT_DIR: &str = "resources/scripts";
+
+/// 导入或安装期间占用模型ID和权重摘要，同一模型的并发导入不会互相清理模型目录
+///
+/// 离开作用域时释放。
+pub struct ImportGuard {
+    keys: Vec<String>,
+}
+
+impl ImportGuard {
+    pub fn acquire(keys: &[&str]) -> Result<Self, String> {
+        let mut pending = PENDING_IMPORTS.lock().map_err(|_| "无法获取导入状态锁")?;
+        if keys.iter().any(|key| pending.contains(*key)) {
+            return Err("该模型正在导入，请稍后再试".to_string());
+        }
+        let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+        pending.extend(keys.iter().cloned());
+        Ok(Self { keys })
+    }
+}
+
+impl Drop for ImportGuard {
+    fn drop(&mut self) {
+        if let Ok(mut pending) = PENDING_IMPORTS.lock() {
+            for key in &self.keys {
+                pending.remove(key);
+            }
+        }
+    }
+}
+
+/// 注册已保存清单的模型，失败时删除清单和模型目录，不留下重启后才出现的模型
+pub fn register_model(
+    model: ModelInfo,
+    manifest_path: PathBuf,
+    model_dir: &Path,
+) -> Result<ModelInfo, String> {
+    let model_id = model.id.clone();
+    let registered = MODEL_REGISTRY
+        .lock()
+        .map_err(|_| "无法获取模型注册表锁".to_string())
+        .and_then(|mut registry| {
+            registry.add_model(model, manifest_path.clone())?;
+            registry
+                .get_model(&model_id)
+                .ok_or(format!("模型ID不存在: {}", model_id))
+        });
+
+    if registered.is_err() {
+        if let Err(e) = fs::remove_file(&manifest_path) {
+            println!("删除模型清单失败: {}", e);
+        }
+        if let Err(e) = fs::remove_dir_all(model_dir) {
+            println!("清理模型目录失败: {}", e);
+        }
+    }
+    registered
+}
+
+/// 导入用户模型
+///
+/// 将权重、脚本和类别名称文件复制到应用数据目录，用示例图片试运行成功后写入清单并注册。
+/// 权重文件内容与已有模型相同时拒绝导入。
+pub async fn import_model(
+    app_handle: &AppHandle,
+    request: ModelImportRequest,
+) -> Result<ModelInfo, String> {
+    let name = request.name.trim().to_string();
+    if name.is_empty() {
+        return Err("模型名称不能为空".to_string());
+    }
+
+    let weights_path = PathBuf::from(&request.weights_path);
+    let script_path = request.script_path.as_ref().map(PathBuf::from);
+    let labels_path = request.labels_path.as_ref().map(PathBuf::from);
+    ensure_file(&weights_path, "模型文件")?;
+    if let Some(script_path) = &script_path {
+        ensure_file(script_path, "推理脚本")?;
+    }
+    if let Some(labels_path) = &labels_path {
+        ensure_file(labels_path, "类别名称文件")?;
+    }
+
+    let backend = request
+        .backend
+        .unwrap_or_else(|| BackendKind::from_weights_path(&request.weights_path));
+    match backend {
+        BackendKind::Python if script_path.is_none() => {
+            return Err("Python 模型需要提供推理脚本".to_string())
+        }
+        BackendKind::Remote => return Err("远程推理服务中的模型无需导入".to_string()),
+        BackendKind::Ensemble => return Err("集成模型通过模型清单定义，无需导入".to_string()),
+        BackendKind::Onnx if request.task == ModelTask::Detection => {
+            return Err("ONNX后端暂不支持检测模型，请使用Python推理脚本".to_string())
+        }
+        _ => {}
+    }
+
+    let label_count = match &labels_path {
+        Some(labels_path) => Some(LabelSet::load(labels_path)?.len() as u32),
+        None => None,
+    };
+    if let (Some(num_classes), Some(label_count)) = (request.num_classes, label_count) {
+        if num_classes != label_count {
+            return Err(format!(
+                "类别名称文件有 {} 个类别，与声明的 {} 个不一致",
+                label_count, num_classes
+            ));
+        }
+    }
+
+    // 按权重文件内容识别重复导入，同一模型同时只能有一个导入在进行
+    let digest = hash_file(weights_path.clone()).await?;
+    let model_id = format!("user-{}", &digest[..16]);
+    let _guard = ImportGuard::acquire(&[&model_id, &digest])?;
+    if let Some(existing) = find_model_by_digest(app_handle, &digest).await {
+        return Err(format!("该模型已存在: {}", existing.name));
+    }
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("无法获取应用数据目录: {}", e))?;
+    let relative_dir = format!("{}/{}", USER_MODEL_DIR, model_id);
+    let model_dir = app_data_dir.join(&relative_dir);
+
+    // 清理上次导入失败时留下的文件
+    if model_dir.exists() {
+        tokio::fs::remove_dir_all(&model_dir)
+            .await
+            .map_err(|e| format!("无法清理模型目录: {}", e))?;
+    }
+    tokio::fs::create_dir_all(&model_dir)
+        .await
+        .map_err(|e| format!("无法创建模型目录: {}", e))?;
+    integrity::clear_digest_cache();
+
+    let staged = async {
+        let weights = copy_into(&weights_path, &model_dir, &relative_dir).await?;
+        let script = match &script_path {
+            Some(script_path) => Some(copy_into(script_path, &model_dir, &relative_dir).await?),
+            None => None,
+        };
+        if backend == BackendKind::Python {
+            copy_worker_protocol(app_handle, &model_dir)?;
+        }
+        let labels = match &labels_path {
+            Some(labels_path) => Some(copy_into(labels_path, &model_dir, &relative_dir).await?),
+            None => None,
+        };
+        // 记录复制后脚本的摘要，之后每次启动推理进程前校验
+        let script_sha256 = match &script {
+            Some(script) => Some(hash_file(app_data_dir.join(script)).await?),
+            None => None,
+        };
+        let helpers_sha256 = match &script {
+            Some(script) => Some(hash_helpers(app_data_dir.join(script)).await?),
+            None => None,
+        };
+
+        let mut manifest = ModelManifest {
+            id: model_id.clone(),
+            name,
+            description: request.description.trim().to_string(),
+            weights,
+            model_type: request.model_type.trim().to_string(),
+            num_classes: request.num_classes.or(label_count).unwrap_or(0),
+            backend: request.backend,
+            script,
+            protocol: request.protocol,
+            task: request.task,
+            labels,
+            weights_sha256: Some(digest.clone()),
+            script_sha256,
+            helpers_sha256,
+            signature: None,
+            default: false,
+            samples: Vec::new(),
+            ensemble: None,
+        };
+
+        let model = manifest.clone().into_model_info(Some(&app_data_dir));
+        let reported_classes = smoke_test(app_handle, &model)
+            .await
+            .map_err(|e| format!("模型试运行失败: {}", e))?;
+        if manifest.num_classes == 0 {
+            manifest.num_classes = reported_classes;
+        } else if reported_classes != 0 && reported_classes != manifest.num_classes {
+            return Err(format!(
+                "模型输出 {} 个类别，与声明的 {} 个不一致",
+                reported_classes, manifest.num_classes
+            ));
+        }
+
+        let manifest_dir = app_data_dir.join(USER_MANIFEST_DIR);
+        fs::create_dir_all(&manifest_dir).map_err(|e| format!("无法创建清单目录: {}", e))?;
+        let manifest_path = manifest_dir.join(format!("{}.json", model_id));
+        manifest.save(&manifest_path)?;
+        Ok::<_, String>((manifest, manifest_path))
+    }
+    .await;
+
+    let (manifest, manifest_path) = match staged {
+        Ok(staged) => staged,
+        Err(e) => {
+            if let Err(remove_error) = tokio::fs::remove_dir_all(&model_dir).await {
+                println!("清理模型目录失败: {}", remove_error);
+            }
+            return Err(e);
+        }
+    };
+
+    let model = manifest.into_model_info(Some(&app_data_dir));
+    let model = register_model(model, manifest_path, &model_dir)?;
+
+    println!("模型导入成功: {} ({})", model.name, model.id);
+    Ok(model)
+}
+
+/// 删除用户模型，同时删除其清单和导入时复制的文件
+pub fn remove_user_model(app_handle: &AppHandle, model_id: &str) -> Result<(), String> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("无法获取应用数据目录: {}", e))?;
+
+    let (was_active, active_model) = {
+        let mut registry = MODEL_REGISTRY.lock().map_err(|_| "无法获取模型注册表锁")?;
+        let model = registry
+            .get_model(model_id)
+            .ok_or(format!("模型ID不存在: {}", model_id))?;
+        if model.source != ModelSource::User {
+            return Err("内置模型不能删除".to_string());
+        }
+        let ensembles = registry.ensembles_using(model_id);
+        if !ensembles.is_empty() {
+            return Err(format!(
+                "模型是集成模型 {} 的成员，请先删除集成模型",
+                ensembles.join(", ")
+            ));
+        }
+
+        // 先删除清单，失败时模型保持注册，避免重启后又出现
+        if let Some(manifest_path) = registry.get_manifest_path(model_id) {
+            fs::remove_file(&manifest_path).map_err(|e| format!("无法删除模型清单: {}", e))?;
+        }
+        registry.remove_model(model_id)?;
+        (model.is_active, registry.get_active_model())
+    };
+
+    WORKER_POOL
+        .lock()
+        .map_err(|_| "无法获取模型进程池锁")?
+        .remove(model_id);
+
+    if let Some(model_dir) = user_model_dir(&app_data_dir, model_id) {
+        if model_dir.exists() {
+            if let Err(e) = fs::remove_dir_all(&model_dir) {
+                println!("删除模型文件失败: {}", e);
+            }
+        }
+    }
+
+    if was_active {
+        if let Some(active_model) = active_model {
+            if let Err(e) = constants::save_active_model(app_handle, &active_model.id) {
+                println!("保存活跃模型失败: {}", e);
+            }
+        }
+    }
+
+    println!("已删除模型: {}", model_id);
+    Ok(())
+}
+
+/// 修改用户模型的名称、描述或类型，并写回清单文件
+pub fn update_model_metadata(
+    model_id: &str,
+    update: ModelMetadataUpdate,
+) -> Result<ModelInfo, String> {
+    let mut registry = MODEL_REGISTRY.lock().map_err(|_| "无法获取模型注册表锁")?;
+    let mut model = registry
+        .get_model(model_id)
+        .ok_or(format!("模型ID不存在: {}", model_id))?;
+    let manifest_path = registry
+        .get_manifest_path(model_id)
+        .ok_or("内置模型不能修改")?;
+    let mut manifest = ModelManifest::from_file(&manifest_path)?;
+
+    if let Some(name) = update.name {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("模型名称不能为空".to_string());
+        }
+        manifest.name = name.to_string();
+        model.name = manifest.name.clone();
+    }
+    if let Some(description) = update.description {
+        manifest.description = description.trim().to_string();
+        model.description = manifest.description.clone();
+    }
+    if let Some(model_type) = update.model_type {
+        manifest.model_type = model_type.trim().to_string();
+        model.model_type = manifest.model_type.clone();
+    }
+
+    manifest.save(&manifest_path)?;
+    registry.update_model(model)
+}
+
+/// 用示例图片试运行模型，返回模型报告的类别数（未知时为 0）
+///
+/// 试运行使用独立的后端，不占用工作池，结束后立即卸载。
+pub async fn smoke_test(app_handle: &AppHandle, model: &ModelInfo) -> Result<u32, String> {
+    let sample_path = get_resource_path(app_handle, SAMPLE_IMAGE)?;
+    let spec = BackendSpec::for_kind(app_handle, model, model.backend_kind())?;
+    spec.verify_async(model).await?;
+    let backend = spec.create(app_handle, model)?;
+
+    let result = async {
+        backend.load().await?;
+        let result = backend.infer(&sample_path).await?;
+        println!(
+            "模型试运行结果: {} ({:.2})",
+            result.prediction, result.confidence
+        );
+        // 旧协议的脚本不支持查询模型信息
+        let num_classes = backend
+            .info()
+            .await
+            .map(|info| info.num_classes)
+            .unwrap_or(0);
+        Ok::<_, ServiceError>(num_classes)
+    }
+    .await;
+
+    backend.unload().await;
+    result.map_err(|e| e.to_string())
+}
+
+/// 查找权重文件内容相同的已注册模型
+///
+/// 优先使用清单记录的哈希值；内置模型的清单中没有记录，按需计算并使用摘要缓存，
+/// 找不到权重文件的模型跳过。
+pub async fn find_model_by_digest(app_handle: &AppHandle, digest: &str) -> Option<ModelInfo> {
+    let models = MODEL_REGISTRY.lock().ok()?.get_models();
+    for model in models {
+        let model_digest = match &model.weights_sha256 {
+            Some(model_digest) => model_digest.clone(),
+            None => {
+                let Ok(path) = get_resource_path(app_handle, &model.path) else {
+                    continue;
+                };
+                let cached =
+                    tokio::task::spawn_blocking(move || integrity::file_digest(Path::new(&path)))
+                        .await;
+                match cached {
+                    Ok(Ok(model_digest)) => model_digest,
+                    _ => continue,
+                }
+            }
+        };
+        if model_digest.eq_ignore_ascii_case(digest) {
+            return Some(model);
+        }
+    }
+    None
+}
+
+/// 在阻塞线程中计算文件哈希值
+pub async fn hash_file(path: PathBuf) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        calculate_path_hash(&path).map_err(|e| format!("无法读取 {}: {}", path.display(), e))
+    })
+    .await
+    .map_err(|e| format!("计算文件哈希值失败: {}", e))?
+}
+
+/// 在阻塞线程中计算推理脚本同目录下辅助脚本的合并摘要
+pub async fn hash_helpers(script_path: PathBuf) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let script_dir = script_path.parent().unwrap_or(Path::new(""));
+        integrity::helpers_digest(script_dir)
+            .map_err(|e| format!("无法读取辅助脚本 {}: {}", script_dir.display(), e))
+    })
+    .await
+    .map_err(|e| format!("计算文件哈希值失败: {}", e))?
+}
+
+/// 复制文件到模型目录，返回相对于应用数据目录的路径
+///
+/// 权重文件可能有几百MB，异步复制以免阻塞运行时的工作线程。
+async fn copy_into(source: &Path, model_dir: &Path, relative_dir: &str) -> Result<String, String> {
+    let file_name = source
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or(format!("无效的文件名: {}", source.display()))?;
+    tokio::fs::copy(source, model_dir.join(file_name))
+        .await
+        .map_err(|e| format!("无法复制 {}: {}", source.display(), e))?;
+    Ok(format!("{}/{}", relative_dir, file_name))
+}
+
+/// 将辅助脚本（通信协议、Grad-CAM、特征提取模块）复制到模型目录，推理脚本按同目录导入；目录中已有的文件保留
+pub fn copy_worker_protocol(app_handle: &AppHandle, model_dir: &Path) -> Result<(), String> {
+    for name in integrity::HELPER_SCRIPTS {
+        let target = model_dir.join(name);
+        if target.exists() {
+            continue;
+        }
+        let protocol_path =
+            get_resource_path(app_handle, &format!("{}/{}", BUNDLED_SCRIPT_DIR, name))?;
+        fs::copy(&protocol_path, &target)
+            .map_err(|e| format!("无法复制辅助脚本 {}: {}", name, e))?;
+    }
+    Ok(())
+}
+
+fn ensure_file(path: &Path, description: &str) -> Result<(), String> {
+    if path.is_file() {
+        Ok(())
+    } else {
+        Err(format!("{}不存在: {}", description, path.display()))
+    }
+}
+
+/// 导入模型的文件目录，模型ID不是单一路径组件时返回 None
+pub fn user_model_dir(app_data_dir: &Path, model_id: &str) -> Option<PathBuf> {
+    let is_plain_name = Path::new(model_id)
+        .file_name()
+        .map(|name| name == model_id)
+        .unwrap_or(false);
+    is_plain_name.then(|| app_data_dir.join(USER_MODEL_DIR).join(model_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concurrent_imports_of_the_same_model_are_rejected() {
+        let guard = ImportGuard::acquire(&["user-0123456789abcdef", "digest-a"]).unwrap();
+        assert!(ImportGuard::acquire(&["user-0123456789abcdef"]).is_err());
+        assert!(ImportGuard::acquire(&["other-model", "digest-a"]).is_err());
+        assert!(ImportGuard::acquire(&["other-model", "digest-b"]).is_ok());
+
+        drop(guard);
+        assert!(ImportGuard::acquire(&["user-0123456789abcdef", "digest-a"]).is_ok());
+    }
+
+    #[test]
+    fn user_model_dir_requires_a_plain_name() {
+        let root = Path::new("data");
+        assert_eq!(
+            user_model_dir(root, "user-0123"),
+            Some(root.join(USER_MODEL_DIR).join("user-0123"))
+        );
+        assert_eq!(user_model_dir(root, "../evil"), None);
+        assert_eq!(user_model_dir(root, ""), None);
+    }
+}
@@ -0,0 +1,470 @@
+use crate::models::inference_result::{
+    ModelInfo, ModelResult, ModelTask, ServiceStatus, WorkerErrorCode, WorkerInfo,
+};
+use crate::services::inference::{
+    result_from_logits, result_from_multi_label_logits, InferenceBackend,
+};
+use crate::services::labels::LabelSet;
+use crate::services::python::ServiceError;
+use futures::future::BoxFuture;
+use image::imageops::FilterType;
+use image::RgbImage;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::watch;
+use tract_onnx::prelude::*;
+
+type OnnxPlan = TypedSimplePlan<TypedModel>;
+
+/// 图像预处理参数，默认与推理脚本的 Resize(256)/CenterCrop(224)/Normalize 一致
+#[derive(Debug, Clone, Deserialize)]
+pub struct Preprocess {
+    /// 缩放尺寸：设置了 crop 时为短边长度，否则直接缩放为 resize×resize
+    #[serde(default = "default_resize")]
+    pub resize: u32,
+
+    /// 中心裁剪尺寸，为空时不裁剪
+    #[serde(default = "default_crop")]
+    pub crop: Option<u32>,
+
+    /// 各通道均值
+    #[serde(default = "default_mean")]
+    pub mean: [f32; 3],
+
+    /// 各通道标准差
+    #[serde(default = "default_std")]
+    pub std: [f32; 3],
+}
+
+fn default_resize() -> u32 {
+    256
+}
+
+fn default_crop() -> Option<u32> {
+    Some(224)
+}
+
+fn default_mean() -> [f32; 3] {
+    [0.485, 0.456, 0.406]
+}
+
+fn default_std() -> [f32; 3] {
+    [0.229, 0.224, 0.225]
+}
+
+impl Default for Preprocess {
+    fn default() -> Self {
+        Self {
+            resize: default_resize(),
+            crop: default_crop(),
+            mean: default_mean(),
+            std: default_std(),
+        }
+    }
+}
+
+impl Preprocess {
+    /// 模型输入的边长
+    fn input_size(&self) -> u32 {
+        self.crop.unwrap_or(self.resize)
+    }
+}
+
+/// ONNX模型的说明文件，与模型同名、扩展名为 `.json`
+///
+/// 模型清单声明了类别名称文件时，说明文件可以省略，或只包含预处理参数；
+/// 此时使用类别名称文件中的类别，忽略说明文件中的 `labels`。
+#[derive(Debug, Default, Deserialize)]
+pub struct OnnxModelConfig {
+    /// 按输出顺序排列的类别名称
+    #[serde(default)]
+    pub labels: Vec<String>,
+
+    /// 图像预处理参数
+    #[serde(default)]
+    pub preprocess: Preprocess,
+}
+
+/// 加载完成的模型
+struct LoadedModel {
+    plan: OnnxPlan,
+    labels: Arc<LabelSet>,
+    preprocess: Preprocess,
+    info: WorkerInfo,
+}
+
+#[derive(Clone)]
+enum OnnxState {
+    Loading { since: Instant },
+    Ready(Arc<LoadedModel>),
+    Failed { error: String },
+}
+
+/// 基于 tract 的纯Rust ONNX推理后端，在CPU上运行
+pub struct OnnxBackend {
+    state: Arc<watch::Sender<OnnxState>>,
+    task: ModelTask,
+}
+
+impl OnnxBackend {
+    /// 创建后端并在阻塞线程中加载模型，需要在异步运行时中调用
+    pub fn new(model: &ModelInfo, model_path: PathBuf, labels: Option<Arc<LabelSet>>) -> Self {
+        let (state, _) = watch::channel(OnnxState::Loading {
+            since: Instant::now(),
+        });
+        let state = Arc::new(state);
+
+        let loading_state = Arc::clone(&state);
+        let name = model.name.clone();
+        let model_type = model.model_type.clone();
+        tokio::task::spawn_blocking(move || {
+            let next = match load_model(&model_path, labels, name, model_type) {
+                Ok(model) => {
+                    println!(
+                        "ONNX模型加载完成: {}, {} 个类别",
+                        model_path.display(),
+                        model.labels.len()
+                    );
+                    OnnxState::Ready(Arc::new(model))
+                }
+                Err(error) => {
+                    println!("ONNX模型加载失败: {}", error);
+                    OnnxState::Failed { error }
+                }
+            };
+            // 加载期间已被卸载时不再覆盖状态
+            loading_state.send_if_modified(|current| {
+                if let OnnxState::Loading { .. } = current {
+                    *current = next;
+                    true
+                } else {
+                    false
+                }
+            });
+        });
+
+        Self {
+            state,
+            task: model.task,
+        }
+    }
+
+    /// 等待加载结束，返回已加载的模型
+    async fn loaded(&self) -> Result<Arc<LoadedModel>, ServiceError> {
+        let mut receiver = self.state.subscribe();
+        let state = receiver
+            .wait_for(|state| !matches!(state, OnnxState::Loading { .. }))
+            .await
+            .map(|state| state.clone())
+            .map_err(|_| ServiceError::ModelLoadFailed("加载任务已退出".to_string()))?;
+
+        match state {
+            OnnxState::Ready(model) => Ok(model),
+            OnnxState::Failed { error } => Err(ServiceError::ModelLoadFailed(error)),
+            OnnxState::Loading { .. } => unreachable!(),
+        }
+    }
+
+    /// 在阻塞线程中识别图片，避免占用异步运行时
+    async fn run(&self, image_path: &str) -> Result<ModelResult, ServiceError> {
+        let model = self.loaded().await?;
+        let image_path = PathBuf::from(image_path);
+        let task = self.task;
+
+        tokio::task::spawn_blocking(move || {
+            let input = preprocess_image(&image_path, &model.preprocess)?;
+            let outputs = model
+                .plan
+                .run(tvec!(input.into()))
+                .map_err(inference_error)?;
+            let logits = outputs[0].to_array_view::<f32>().map_err(inference_error)?;
+            let logits: Vec<f32> = logits.iter().copied().collect();
+            match task {
+                ModelTask::MultiLabel => result_from_multi_label_logits(&logits, &model.labels),
+                _ => result_from_logits(&logits, &model.labels),
+            }
+        })
+        .await
+        .map_err(|e| inference_error(e.into()))?
+    }
+}
+
+impl InferenceBackend for OnnxBackend {
+    fn load(&self) -> BoxFuture<'_, Result<(), ServiceError>> {
+        Box::pin(async move { self.loaded().await.map(|_| ()) })
+    }
+
+    fn infer<'a>(
+        &'a self,
+        image_path: &'a str,
+    ) -> BoxFuture<'a, Result<ModelResult, ServiceError>> {
+        Box::pin(self.run(image_path))
+    }
+
+    fn unload(&self) -> BoxFuture<'_, ()> {
+        // 正在进行的推理持有模型引用，结束后释放
+        self.state.send_replace(OnnxState::Failed {
+            error: "模型已卸载".to_string(),
+        });
+        Box::pin(async {})
+    }
+
+    fn info(&self) -> BoxFuture<'_, Result<WorkerInfo, ServiceError>> {
+        Box::pin(async move { self.loaded().await.map(|model| model.info.clone()) })
+    }
+
+    fn status(&self) -> ServiceStatus {
+        match &*self.state.borrow() {
+            OnnxState::Loading { since } => ServiceStatus::Loading {
+                elapsed_secs: since.elapsed().as_secs(),
+            },
+            OnnxState::Ready(model) => ServiceStatus::Ready {
+                info: Some(model.info.clone()),
+            },
+            OnnxState::Failed { error } => ServiceStatus::Failed {
+                message: error.clone(),
+            },
+        }
+    }
+}
+
+/// 加载ONNX模型及其说明文件，有类别名称文件时优先使用其中的类别
+fn load_model(
+    model_path: &Path,
+    labels: Option<Arc<LabelSet>>,
+    name: String,
+    model_type: String,
+) -> Result<LoadedModel, String> {
+    let config_path = model_path.with_extension("json");
+    let config = if config_path.exists() || labels.is_none() {
+        let content = std::fs::read_to_string(&config_path)
+            .map_err(|e| format!("无法读取模型说明文件 {}: {}", config_path.display(), e))?;
+        serde_json::from_str::<OnnxModelConfig>(&content)
+            .map_err(|e| format!("模型说明文件格式错误: {}", e))?
+    } else {
+        OnnxModelConfig::default()
+    };
+    let labels = match labels {
+        Some(labels) => labels,
+        None if config.labels.is_empty() => return Err("模型说明文件中没有类别名称".to_string()),
+        None => Arc::new(LabelSet::from_names(config.labels)?),
+    };
+    if let Some(crop) = config.preprocess.crop {
+        if config.preprocess.resize < crop {
+            return Err(format!(
+                "预处理参数错误: resize ({}) 不能小于 crop ({})",
+                config.preprocess.resize, crop
+            ));
+        }
+    }
+
+    let size = config.preprocess.input_size() as usize;
+    let plan = tract_onnx::onnx()
+        .model_for_path(model_path)
+        .and_then(|model| model.with_input_fact(0, f32::fact([1, 3, size, size]).into()))
+        .and_then(|model| model.into_optimized())
+        .and_then(|model| model.into_runnable())
+        .map_err(|e| format!("{:#}", e))?;
+
+    let info = WorkerInfo {
+        name,
+        model_type,
+        num_classes: labels.len() as u32,
+        device: "cpu".to_string(),
+        // 内置后端不经过进程通信协议，批量识别逐张推理
+        protocol_version: 0,
+        capabilities: Vec::new(),
+    };
+    Ok(LoadedModel {
+        plan,
+        labels,
+        preprocess: config.preprocess,
+        info,
+    })
+}
+
+/// 读取图片并转换为 NCHW 格式的归一化张量
+fn preprocess_image(image_path: &Path, preprocess: &Preprocess) -> Result<Tensor, ServiceError> {
+    let image = image::open(image_path)
+        .map_err(|e| ServiceError::Worker {
+            code: WorkerErrorCode::ImageError,
+            message: format!("无法读取图像: {}", e),
+        })?
+        .to_rgb8();
+
+    let image = match preprocess.crop {
+        Some(crop) => center_crop(&resize_shorter_side(&image, preprocess.resize), crop),
+        None => image::imageops::resize(
+            &image,
+            preprocess.resize,
+            preprocess.resize,
+            FilterType::Triangle,
+        ),
+    };
+
+    let size = preprocess.input_size() as usize;
+    let tensor = tract_ndarray::Array4::from_shape_fn((1, 3, size, size), |(_, c, y, x)| {
+        let value = image.get_pixel(x as u32, y as u32)[c] as f32 / 255.0;
+        (value - preprocess.mean[c]) / preprocess.std[c]
+    });
+    Ok(tensor.into())
+}
+
+/// 按比例缩放，使短边等于 size
+fn resize_shorter_side(image: &RgbImage, size: u32) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let (new_width, new_height) = if width <= height {
+        (
+            size,
+            (height as f64 * size as f64 / width as f64).round() as u32,
+        )
+    } else {
+        (
+            (width as f64 * size as f64 / height as f64).round() as u32,
+            size,
+        )
+    };
+    image::imageops::resize(image, new_width, new_height, FilterType::Triangle)
+}
+
+/// 从图片中心裁剪 size×size 的区域
+fn center_crop(image: &RgbImage, size: u32) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let left = width.saturating_sub(size) / 2;
+    let top = height.saturating_sub(size) / 2;
+    image::imageops::crop_imm(image, left, top, size, size).to_image()
+}
+
+fn inference_error(error: TractError) -> ServiceError {
+    ServiceError::Worker {
+        code: WorkerErrorCode::InferenceError,
+        message: format!("{:#}", error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::inference::TOP_N;
+    use image::Rgb;
+
+    /// 每个像素的值记录其坐标，便于检查裁剪位置
+    fn coordinate_image(width: u32, height: u32) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, y| Rgb([x as u8, y as u8, 0]))
+    }
+
+    fn uniform_png(name: &str, width: u32, height: u32, color: [u8; 3]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "vision_match_onnx_{}_{}.png",
+            std::process::id(),
+            name
+        ));
+        RgbImage::from_pixel(width, height, Rgb(color))
+            .save(&path)
+            .unwrap();
+        path
+    }
+
+    fn labels(count: usize) -> LabelSet {
+        LabelSet::from_names((0..count).map(|i| format!("类别{}", i)).collect()).unwrap()
+    }
+
+    #[test]
+    fn shorter_side_is_resized_keeping_aspect_ratio() {
+        assert_eq!(
+            resize_shorter_side(&coordinate_image(40, 20), 10).dimensions(),
+            (20, 10)
+        );
+        assert_eq!(
+            resize_shorter_side(&coordinate_image(20, 40), 10).dimensions(),
+            (10, 20)
+        );
+        assert_eq!(
+            resize_shorter_side(&coordinate_image(30, 30), 10).dimensions(),
+            (10, 10)
+        );
+        // 比例不是整数时四舍五入
+        assert_eq!(
+            resize_shorter_side(&coordinate_image(30, 20), 7).dimensions(),
+            (11, 7)
+        );
+    }
+
+    #[test]
+    fn center_crop_takes_the_middle() {
+        let cropped = center_crop(&coordinate_image(6, 4), 2);
+        assert_eq!(cropped.dimensions(), (2, 2));
+        assert_eq!(cropped.get_pixel(0, 0), &Rgb([2, 1, 0]));
+        assert_eq!(cropped.get_pixel(1, 1), &Rgb([3, 2, 0]));
+    }
+
+    #[test]
+    fn image_is_normalized_into_nchw_tensor() {
+        let path = uniform_png("uniform", 8, 6, [255, 0, 51]);
+        let preprocess = Preprocess {
+            resize: 5,
+            crop: Some(4),
+            mean: [0.5, 0.0, 0.2],
+            std: [0.5, 1.0, 0.1],
+        };
+        let tensor = preprocess_image(&path, &preprocess).unwrap();
+        assert_eq!(tensor.shape(), &[1, 3, 4, 4]);
+
+        let values = tensor.to_array_view::<f32>().unwrap();
+        for (channel, expected) in [1.0, 0.0, 0.0].into_iter().enumerate() {
+            let actual = values[[0, channel, 2, 3]];
+            assert!(
+                (actual - expected).abs() < 1e-5,
+                "通道 {}: {}",
+                channel,
+                actual
+            );
+        }
+
+        // 不裁剪时直接缩放为 resize×resize
+        let stretched = Preprocess {
+            crop: None,
+            ..preprocess
+        };
+        let tensor = preprocess_image(&path, &stretched).unwrap();
+        assert_eq!(tensor.shape(), &[1, 3, 5, 5]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn unreadable_image_is_an_image_error() {
+        let missing = std::env::temp_dir().join("vision_match_onnx_missing.png");
+        assert!(matches!(
+            preprocess_image(&missing, &Preprocess::default()),
+            Err(ServiceError::Worker {
+                code: WorkerErrorCode::ImageError,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn logits_map_to_ranked_result() {
+        let result = result_from_logits(&[1.0, 3.0, 2.0], &labels(3)).unwrap();
+        let sum = 1f32.exp() + 2f32.exp() + 3f32.exp();
+        assert_eq!(result.prediction, "类别1");
+        assert_eq!(result.class_id.as_deref(), Some("class_1"));
+        assert!((result.confidence - 3f32.exp() / sum).abs() < 1e-6);
+
+        let order: Vec<u32> = result.top_classes.iter().map(|score| score.index).collect();
+        assert_eq!(order, vec![1, 2, 0]);
+        let total: f32 = result.class_probabilities.values().sum();
+        assert!((total - 1.0).abs() < 1e-5);
+
+        // 只保留概率最高的 TOP_N 个类别
+        let logits: Vec<f32> = (0..TOP_N + 2).map(|i| i as f32).collect();
+        let result = result_from_logits(&logits, &labels(TOP_N + 2)).unwrap();
+        assert_eq!(result.top_classes.len(), TOP_N);
+        assert_eq!(result.prediction, format!("类别{}", TOP_N + 1));
+
+        assert!(result_from_logits(&[1.0, 2.0], &labels(3)).is_err());
+        assert!(result_from_logits(&[], &labels(2)).is_err());
+    }
+}
@@ -1,142 +1,1230 @@
-use lazy_static::lazy_static;
-use std::io::{BufRead, BufReader, Write};
-use std::os::windows::process::CommandExt;
-use std::process::{Child, Command, Stdio};
-use std::sync::Mutex;
-use std::time::Duration;
-lazy_static! {
-    pub static ref PYTHON_SERVICE: Mutex<Option<PythonService>> = Mutex::new(None);
+use crate::models::inference_result::{
+    ActivationMap, BatchEntry, BoundingBox, Detection, ModelResult, ModelTask, ServiceStatus,
+    WorkerCommand, WorkerCrashEvent, WorkerDetection, WorkerErrorCode, WorkerInfo, WorkerMessage,
+    WorkerProtocol, WorkerRequest, WorkerResponse, WorkerResult, CAPABILITY_EMBED,
+    CAPABILITY_EXPLAIN, CAPABILITY_INFER_BATCH, PROTOCOL_VERSION,
+};
+use crate::services::labels::{detection_result, LabelSet};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::{mpsc, oneshot, watch};
+
+/// Windows 下不创建控制台窗口的进程标志
+#[cfg(windows)]
+pub(crate) const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+/// 关闭stdin后等待Python进程自行退出的时间
+const GRACEFUL_EXIT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 发送SIGTERM后等待进程组退出的时间
+#[cfg(unix)]
+const TERMINATE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// ping/info 等控制命令的超时时间
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 崩溃后重启的初始等待时间，每次失败翻倍
+const RESTART_BACKOFF_BASE: Duration = Duration::from_millis(500);
+
+/// 崩溃后重启的最长等待时间
+const RESTART_BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+/// 单次崩溃恢复中最多尝试重启的次数
+const MAX_RESTART_ATTEMPTS: u32 = 3;
+
+/// 推理进程崩溃时发送给前端的事件名
+pub const WORKER_CRASHED_EVENT: &str = "python-worker-crashed";
+
+/// 崩溃事件回调
+pub type CrashListener = Box<dyn Fn(WorkerCrashEvent) + Send + Sync>;
+
+#[derive(Debug, Error)]
+pub enum ServiceError {
+    #[error("无法启动Python进程: {0}")]
+    Spawn(#[from] std::io::Error),
+
+    #[error("模型仍在加载中，已等待 {0} 秒")]
+    ModelLoading(u64),
+
+    #[error("模型加载失败: {0}")]
+    ModelLoadFailed(String),
+
+    #[error("模型加载超时，{0} 秒内未收到就绪消息")]
+    StartupTimeout(u64),
+
+    #[error("推理脚本返回错误({code:?}): {message}")]
+    Worker {
+        code: WorkerErrorCode,
+        message: String,
+    },
+
+    #[error("通信协议错误: {0}")]
+    Protocol(String),
+
+    #[error("推理超时，{0} 秒内未收到结果")]
+    Timeout(u64),
+
+    #[error("Python进程已退出")]
+    ProcessExited,
+
+    #[error("远程推理服务请求失败: {0}")]
+    Remote(String),
+
+    #[error("当前模型不支持{0}")]
+    Unsupported(&'static str),
+}
+
+/// 推理进程的加载状态，由stdout读取任务更新
+#[derive(Debug, Clone)]
+enum WorkerState {
+    /// 已启动，等待就绪消息
+    Loading { since: Instant },
+    /// 模型加载完成，可以接收请求
+    Ready { info: Option<WorkerInfo> },
+    /// 模型加载失败或超时
+    Failed { error: String },
+}
+
+/// 启动推理进程所需的脚本、模型和协议
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WorkerSpec {
+    script_path: String,
+    model_path: String,
+    protocol: WorkerProtocol,
+}
+
+impl WorkerSpec {
+    fn new(script_path: &str, model_path: &str, protocol: WorkerProtocol) -> Self {
+        // 标准化路径分隔符
+        let separator = std::path::MAIN_SEPARATOR.to_string();
+        Self {
+            script_path: script_path.replace('/', &separator),
+            model_path: model_path.replace('/', &separator),
+            protocol,
+        }
+    }
+}
+
+/// 读取任务转交给调用方的响应
+type Reply = Result<WorkerMessage, ServiceError>;
+
+/// 等待响应的请求，按发送顺序排列
+#[derive(Default)]
+struct PendingRequests {
+    /// stdout已关闭，不再接收新请求
+    closed: bool,
+    entries: VecDeque<(String, oneshot::Sender<Reply>)>,
+}
+
+/// 写入任务的指令
+enum WriterMessage {
+    Line(String),
+    /// 关闭stdin，脚本读到EOF后退出
+    Close,
+}
+
+/// 一个正在运行的Python推理进程
+struct Worker {
+    spec: WorkerSpec,
+    child: tokio::sync::Mutex<Child>,
+    /// 请求队列，由写入任务依次写入stdin
+    writer: mpsc::UnboundedSender<WriterMessage>,
+    pending: Arc<Mutex<PendingRequests>>,
+    state: Arc<watch::Sender<WorkerState>>,
+}
+
+impl Worker {
+    fn state(&self) -> WorkerState {
+        self.state.borrow().clone()
+    }
+
+    /// 脚本是否在就绪消息中声明了指定能力
+    fn supports(&self, capability: &str) -> bool {
+        match &*self.state.borrow() {
+            WorkerState::Ready { info: Some(info) } => {
+                info.capabilities.iter().any(|c| c == capability)
+            }
+            _ => false,
+        }
+    }
+
+    /// 登记请求并放入写入队列，返回等待响应的接收端
+    fn enqueue(&self, id: String, line: String) -> Result<oneshot::Receiver<Reply>, ServiceError> {
+        let (sender, receiver) = oneshot::channel();
+        let mut pending = lock_pending(&self.pending);
+        if pending.closed {
+            return Err(ServiceError::ProcessExited);
+        }
+        // 持有锁时写入队列，保证兼容模式下响应顺序与登记顺序一致
+        self.writer
+            .send(WriterMessage::Line(line))
+            .map_err(|_| ServiceError::ProcessExited)?;
+        pending.entries.push_back((id, sender));
+        Ok(receiver)
+    }
+
+    /// 放弃等待某个请求的响应，迟到的响应会被丢弃
+    ///
+    /// 兼容模式按先进先出分发响应，取消会让后续响应错位，因此只取消JSON协议的请求。
+    fn cancel(&self, id: &str) {
+        if self.spec.protocol != WorkerProtocol::Json {
+            return;
+        }
+        let mut pending = lock_pending(&self.pending);
+        if let Some(index) = pending
+            .entries
+            .iter()
+            .position(|(pending_id, _)| pending_id == id)
+        {
+            pending.entries.remove(index);
+        }
+    }
+
+    /// 等待模型加载完成，超时时间从进程启动时开始计算
+    async fn wait_ready(&self, timeout: Duration) -> Result<Option<WorkerInfo>, ServiceError> {
+        let mut receiver = self.state.subscribe();
+        let remaining = match &*receiver.borrow() {
+            WorkerState::Loading { since } => timeout.saturating_sub(since.elapsed()),
+            _ => Duration::ZERO,
+        };
+
+        let finished = tokio::time::timeout(
+            remaining,
+            receiver.wait_for(|state| !matches!(state, WorkerState::Loading { .. })),
+        )
+        .await
+        .map(|state| state.map(|state| state.clone()));
+
+        match finished {
+            Ok(Ok(WorkerState::Ready { info })) => Ok(info),
+            Ok(Ok(WorkerState::Failed { error })) => Err(ServiceError::ModelLoadFailed(error)),
+            Ok(Ok(WorkerState::Loading { .. })) | Ok(Err(_)) => Err(ServiceError::ProcessExited),
+            Err(_) => Err(self.fail_startup(timeout).await),
+        }
+    }
+
+    /// 检查模型是否已就绪，加载中或加载失败时返回对应错误
+    async fn ensure_ready(&self, startup_timeout: Duration) -> Result<(), ServiceError> {
+        match self.state() {
+            WorkerState::Ready { .. } => Ok(()),
+            WorkerState::Failed { error } => Err(ServiceError::ModelLoadFailed(error)),
+            WorkerState::Loading { since } => {
+                let elapsed = since.elapsed();
+                if elapsed >= startup_timeout {
+                    Err(self.fail_startup(startup_timeout).await)
+                } else {
+                    Err(ServiceError::ModelLoading(elapsed.as_secs()))
+                }
+            }
+        }
+    }
+
+    /// 启动超时：标记为失败并终止进程
+    async fn fail_startup(&self, timeout: Duration) -> ServiceError {
+        let timeout = timeout.as_secs();
+        println!("模型加载超时: {} 秒", timeout);
+        finish_loading(
+            &self.state,
+            WorkerState::Failed {
+                error: format!("{} 秒内未收到就绪消息", timeout),
+            },
+        );
+        self.terminate().await;
+        ServiceError::StartupTimeout(timeout)
+    }
+
+    /// 进程退出码，进程仍在运行时为空
+    async fn exit_code(&self) -> Option<i32> {
+        let mut child = self.child.lock().await;
+        // 读到EOF时进程可能尚未完全退出，稍等片刻以获取退出码
+        match tokio::time::timeout(Duration::from_millis(500), child.wait()).await {
+            Ok(Ok(status)) => status.code(),
+            _ => None,
+        }
+    }
+
+    /// 终止Python进程并回收，避免留下僵尸进程
+    ///
+    /// 先通知脚本退出并关闭stdin，超时后在 Unix 上向进程组发送 SIGTERM/SIGKILL，
+    /// 在 Windows 上直接结束进程。
+    async fn terminate(&self) {
+        if self.spec.protocol == WorkerProtocol::Json
+            && matches!(self.state(), WorkerState::Ready { .. })
+        {
+            let request = WorkerRequest::new("shutdown".to_string(), WorkerCommand::Shutdown);
+            if let Ok(line) = serde_json::to_string(&request) {
+                // 写入任务可能已经结束，发送失败不影响后续清理
+                let _ = self.writer.send(WriterMessage::Line(line));
+            }
+        }
+        let _ = self.writer.send(WriterMessage::Close);
+
+        let mut child = self.child.lock().await;
+        if wait_with_timeout(&mut child, GRACEFUL_EXIT_TIMEOUT).await {
+            return;
+        }
+
+        #[cfg(unix)]
+        {
+            if signal_process_group(&child, libc::SIGTERM)
+                && wait_with_timeout(&mut child, TERMINATE_TIMEOUT).await
+            {
+                return;
+            }
+            signal_process_group(&child, libc::SIGKILL);
+        }
+
+        // kill 会等待进程退出并回收
+        if let Err(e) = child.kill().await {
+            println!("终止Python进程时出错: {}", e);
+        }
+    }
 }
 
 pub struct PythonService {
-    child: Child,
+    worker: RwLock<Arc<Worker>>,
     python_executable: String,
-    current_script: String,
-    current_model: String,
+    startup_timeout: Duration,
+    inference_timeout: Duration,
+    next_request_id: AtomicU64,
+    /// 连续重启次数，用于计算退避时间，推理成功后清零
+    restart_attempts: AtomicU32,
+    /// 串行化重启与切换模型，避免并发请求重复重启同一个进程
+    restart_lock: tokio::sync::Mutex<()>,
+    /// 服务已关闭，不再重启进程
+    closed: AtomicBool,
+    crash_listener: Option<CrashListener>,
+    /// 类别表，脚本返回类别索引时用于映射名称
+    labels: Option<Arc<LabelSet>>,
+    /// 模型的任务类型，决定如何解释脚本返回的概率
+    task: ModelTask,
 }
 
 impl PythonService {
-    pub fn new(python_executable: String, script_path: String, model_path: String) -> Self {
-        let script_path = script_path.replace('/', std::path::MAIN_SEPARATOR.to_string().as_str());
-        let model_path = model_path.replace('/', std::path::MAIN_SEPARATOR.to_string().as_str());
-        let mut command = Command::new(&python_executable);
-        command
-            .arg(&script_path)
-            .arg("--server")
-            .arg(&model_path)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            // 在 Windows 上隐藏控制台窗口
-            .creation_flags(0x08000000);
-
-        println!("启动Python进程: {:?}", command);
-
-        let mut child = command.spawn().expect("无法启动Python进程");
-
-        // 使用take()代替expect()，这样不会部分移动child
-        if let Some(stderr) = child.stderr.take() {
-            std::thread::spawn(move || {
-                let reader = BufReader::new(stderr);
-                for line in reader.lines() {
-                    if let Ok(line) = line {
-                        println!("Python stderr: {}", line);
-                    }
+    /// 启动推理进程，不等待模型加载完成
+    pub fn new(
+        python_executable: String,
+        script_path: String,
+        model_path: String,
+        protocol: WorkerProtocol,
+        startup_timeout: Duration,
+        inference_timeout: Duration,
+    ) -> Result<Self, ServiceError> {
+        let spec = WorkerSpec::new(&script_path, &model_path, protocol);
+        let worker = spawn_worker(&python_executable, spec)?;
+
+        Ok(PythonService {
+            worker: RwLock::new(Arc::new(worker)),
+            python_executable,
+            startup_timeout,
+            inference_timeout,
+            next_request_id: AtomicU64::new(0),
+            restart_attempts: AtomicU32::new(0),
+            restart_lock: tokio::sync::Mutex::new(()),
+            closed: AtomicBool::new(false),
+            crash_listener: None,
+            labels: None,
+            task: ModelTask::Classification,
+        })
+    }
+
+    /// 设置崩溃事件回调，每次检测到进程崩溃或卡死时调用
+    pub fn on_crash(&mut self, listener: impl Fn(WorkerCrashEvent) + Send + Sync + 'static) {
+        self.crash_listener = Some(Box::new(listener));
+    }
+
+    /// 设置模型的类别表，脚本只返回类别索引时由此映射为名称
+    pub fn set_labels(&mut self, labels: Arc<LabelSet>) {
+        self.labels = Some(labels);
+    }
+
+    /// 设置模型的任务类型，多标签模型的概率按类别阈值判断阳性类别
+    pub fn set_task(&mut self, task: ModelTask) {
+        self.task = task;
+    }
+
+    /// 等待模型加载完成，最长等待启动超时时间
+    pub async fn wait_ready(&self) -> Result<Option<WorkerInfo>, ServiceError> {
+        self.current().wait_ready(self.startup_timeout).await
+    }
+
+    /// 当前推理进程的状态
+    pub fn status(&self) -> ServiceStatus {
+        match self.current().state() {
+            WorkerState::Loading { since } => ServiceStatus::Loading {
+                elapsed_secs: since.elapsed().as_secs(),
+            },
+            WorkerState::Ready { info } => ServiceStatus::Ready { info },
+            WorkerState::Failed { error } => ServiceStatus::Failed { message: error },
+        }
+    }
+
+    /// 对图片执行推理，多个调用可以同时进行
+    ///
+    /// 进程崩溃时自动重启并重试一次；推理超时时重启卡死的进程并返回超时错误。
+    pub async fn process_image(&self, image_path: &str) -> Result<ModelResult, ServiceError> {
+        self.with_recovery(image_path, |worker| async move {
+            self.infer(&worker, image_path).await
+        })
+        .await
+    }
+
+    /// 对多张图片执行推理，返回与输入顺序一致的结果
+    ///
+    /// 脚本声明了 `infer_batch` 能力时一次发送整批图片，否则逐张请求并在进程内排队。
+    /// 外层错误表示整批失败，内层错误对应单张图片。
+    pub async fn process_batch(
+        &self,
+        image_paths: &[String],
+    ) -> Result<Vec<Result<ModelResult, ServiceError>>, ServiceError> {
+        if !self.current().supports(CAPABILITY_INFER_BATCH) {
+            let requests = image_paths.iter().map(|path| self.process_image(path));
+            return Ok(futures::future::join_all(requests).await);
+        }
+
+        let description = format!("{} 张图片的批量请求", image_paths.len());
+        self.with_recovery(&description, |worker| async move {
+            self.infer_batch(&worker, image_paths).await
+        })
+        .await
+    }
+
+    /// 识别图片并生成预测类别的类激活图，需要脚本声明 `explain` 能力
+    pub async fn explain_image(
+        &self,
+        image_path: &str,
+    ) -> Result<(ModelResult, ActivationMap), ServiceError> {
+        if !self.current().supports(CAPABILITY_EXPLAIN) {
+            return Err(ServiceError::Unsupported("生成热力图"));
+        }
+        self.with_recovery(image_path, |worker| async move {
+            self.explain(&worker, image_path).await
+        })
+        .await
+    }
+
+    /// 识别图片并提取倒数第二层的特征向量，需要脚本声明 `embed` 能力
+    pub async fn embed_image(
+        &self,
+        image_path: &str,
+    ) -> Result<(ModelResult, Vec<f32>), ServiceError> {
+        if !self.current().supports(CAPABILITY_EMBED) {
+            return Err(ServiceError::Unsupported("提取特征向量"));
+        }
+        self.with_recovery(image_path, |worker| async move {
+            self.embed(&worker, image_path).await
+        })
+        .await
+    }
+
+    /// 心跳检测，确认推理脚本仍在响应
+    pub async fn ping(&self) -> Result<(), ServiceError> {
+        let worker = self.current();
+        worker.ensure_ready(self.startup_timeout).await?;
+        match self
+            .request(&worker, WorkerCommand::Ping, CONTROL_TIMEOUT)
+            .await?
+        {
+            WorkerMessage::Pong => Ok(()),
+            other => Err(unexpected_message(other)),
+        }
+    }
+
+    /// 查询推理脚本加载的模型信息
+    pub async fn info(&self) -> Result<WorkerInfo, ServiceError> {
+        let worker = self.current();
+        worker.ensure_ready(self.startup_timeout).await?;
+        match self
+            .request(&worker, WorkerCommand::Info, CONTROL_TIMEOUT)
+            .await?
+        {
+            WorkerMessage::Info { info } => Ok(info),
+            other => Err(unexpected_message(other)),
+        }
+    }
+
+    /// 切换到另一个模型，新进程在后台加载
+    pub async fn switch_model(
+        &self,
+        script_path: &str,
+        model_path: &str,
+        protocol: WorkerProtocol,
+    ) -> Result<(), ServiceError> {
+        let spec = WorkerSpec::new(script_path, model_path, protocol);
+        let _guard = self.restart_lock.lock().await;
+
+        // 如果脚本和模型都没变且模型未加载失败，无需重启
+        let current = self.current();
+        let failed = matches!(current.state(), WorkerState::Failed { .. });
+        if current.spec == spec && !failed {
+            return Ok(());
+        }
+
+        println!(
+            "切换模型: 脚本={}, 模型={}",
+            spec.script_path, spec.model_path
+        );
+
+        // 先启动新进程再替换，旧进程上未完成的请求会转到新进程重试
+        let worker = Arc::new(spawn_worker(&self.python_executable, spec)?);
+        let previous = self.replace_worker(worker);
+        previous.terminate().await;
+        self.restart_attempts.store(0, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    /// 关闭推理进程
+    pub async fn shutdown(&self) {
+        let _guard = self.restart_lock.lock().await;
+        self.closed.store(true, Ordering::Relaxed);
+        self.current().terminate().await;
+    }
+
+    fn current(&self) -> Arc<Worker> {
+        let worker = self.worker.read().unwrap_or_else(|e| e.into_inner());
+        Arc::clone(&worker)
+    }
+
+    fn replace_worker(&self, worker: Arc<Worker>) -> Arc<Worker> {
+        let mut current = self.worker.write().unwrap_or_else(|e| e.into_inner());
+        std::mem::replace(&mut *current, worker)
+    }
+
+    /// 在当前进程上执行操作：进程崩溃时自动重启并重试一次
+    ///
+    /// 推理超时时只取消该请求；心跳检测也失败时才认为进程卡死并重启，
+    /// 以免一个慢请求导致同一进程上的其他请求一起失败。
+    async fn with_recovery<T, F, Fut>(
+        &self,
+        description: &str,
+        operation: F,
+    ) -> Result<T, ServiceError>
+    where
+        F: Fn(Arc<Worker>) -> Fut,
+        Fut: Future<Output = Result<T, ServiceError>>,
+    {
+        let mut worker = self.current();
+        worker.ensure_ready(self.startup_timeout).await?;
+
+        let mut result = operation(Arc::clone(&worker)).await;
+        if let Err(ServiceError::ProcessExited) = result {
+            self.recover(&worker, "推理过程中Python进程退出").await?;
+            println!("Python进程已重启，重试请求: {}", description);
+            worker = self.current();
+            result = operation(Arc::clone(&worker)).await;
+        }
+
+        // 重试后仍然失败时同样重启，避免后续请求落到已退出或卡死的进程上
+        let reason = match &result {
+            Ok(_) => {
+                self.restart_attempts.store(0, Ordering::Relaxed);
+                None
+            }
+            Err(ServiceError::ProcessExited) => Some("推理过程中Python进程退出".to_string()),
+            Err(ServiceError::Timeout(secs)) if !self.responsive(&worker).await => {
+                Some(format!("推理超时（{} 秒）且心跳无响应", secs))
+            }
+            Err(_) => None,
+        };
+        if let Some(reason) = reason {
+            if let Err(e) = self.recover(&worker, &reason).await {
+                println!("重启Python进程失败: {}", e);
+            }
+        }
+        result
+    }
+
+    /// 推理超时后检查进程是否仍在响应
+    ///
+    /// 兼容模式没有心跳命令，且请求按顺序处理，超时的请求会阻塞后续所有请求，视为无响应。
+    async fn responsive(&self, worker: &Worker) -> bool {
+        if worker.spec.protocol != WorkerProtocol::Json {
+            return false;
+        }
+        matches!(
+            self.request(worker, WorkerCommand::Ping, CONTROL_TIMEOUT)
+                .await,
+            Ok(WorkerMessage::Pong)
+        )
+    }
+
+    /// 执行一次推理请求，不做崩溃恢复
+    async fn infer(&self, worker: &Worker, image_path: &str) -> Result<ModelResult, ServiceError> {
+        let command = WorkerCommand::Infer {
+            image_path: image_path.to_string(),
+        };
+        match self
+            .request(worker, command, self.inference_timeout)
+            .await?
+        {
+            WorkerMessage::Result { result } => self.resolve(result),
+            other => Err(unexpected_message(other)),
+        }
+    }
+
+    /// 执行一次解释请求，不做崩溃恢复
+    async fn explain(
+        &self,
+        worker: &Worker,
+        image_path: &str,
+    ) -> Result<(ModelResult, ActivationMap), ServiceError> {
+        let command = WorkerCommand::Explain {
+            image_path: image_path.to_string(),
+        };
+        match self
+            .request(worker, command, self.inference_timeout)
+            .await?
+        {
+            WorkerMessage::Explanation {
+                result,
+                activation_map,
+            } => {
+                let expected = activation_map.width as usize * activation_map.height as usize;
+                if expected == 0 || activation_map.values.len() != expected {
+                    return Err(ServiceError::Protocol(format!(
+                        "类激活图尺寸为 {}x{}，但有 {} 个值",
+                        activation_map.width,
+                        activation_map.height,
+                        activation_map.values.len()
+                    )));
                 }
-            });
+                Ok((self.resolve(result)?, activation_map))
+            }
+            other => Err(unexpected_message(other)),
         }
+    }
 
-        let service = PythonService {
-            child,
-            python_executable,
-            current_script: script_path,
-            current_model: model_path,
+    /// 执行一次特征提取请求，不做崩溃恢复
+    async fn embed(
+        &self,
+        worker: &Worker,
+        image_path: &str,
+    ) -> Result<(ModelResult, Vec<f32>), ServiceError> {
+        let command = WorkerCommand::Embed {
+            image_path: image_path.to_string(),
         };
+        match self
+            .request(worker, command, self.inference_timeout)
+            .await?
+        {
+            WorkerMessage::Embedding { result, embedding } => {
+                if embedding.is_empty() || !embedding.iter().all(|value| value.is_finite()) {
+                    return Err(ServiceError::Protocol(
+                        "特征向量为空或包含无效的数值".to_string(),
+                    ));
+                }
+                Ok((self.resolve(result)?, embedding))
+            }
+            other => Err(unexpected_message(other)),
+        }
+    }
+
+    /// 执行一次批量推理请求，超时时间按图片数量放大
+    async fn infer_batch(
+        &self,
+        worker: &Worker,
+        image_paths: &[String],
+    ) -> Result<Vec<Result<ModelResult, ServiceError>>, ServiceError> {
+        let command = WorkerCommand::InferBatch {
+            image_paths: image_paths.to_vec(),
+        };
+        let timeout = self
+            .inference_timeout
+            .saturating_mul(image_paths.len().max(1) as u32);
 
-        // 等待服务启动
-        std::thread::sleep(Duration::from_secs(1));
+        let results = match self.request(worker, command, timeout).await? {
+            WorkerMessage::BatchResult { results } => results,
+            other => return Err(unexpected_message(other)),
+        };
+        if results.len() != image_paths.len() {
+            return Err(ServiceError::Protocol(format!(
+                "批量结果数量不匹配: 期望 {}, 实际 {}",
+                image_paths.len(),
+                results.len()
+            )));
+        }
+
+        Ok(results
+            .into_iter()
+            .map(|entry| match entry {
+                BatchEntry::Result { result } => self.resolve(result),
+                BatchEntry::Error { code, message } => Err(ServiceError::Worker { code, message }),
+            })
+            .collect())
+    }
 
-        service
+    /// 把脚本返回的结果转换为识别结果，类别索引按类别表映射为名称
+    fn resolve(&self, result: WorkerResult) -> Result<ModelResult, ServiceError> {
+        let (class_indices, probabilities) = match result {
+            WorkerResult::Labeled(result) => return Ok(*result),
+            WorkerResult::Detections { detections } => return self.resolve_detections(detections),
+            WorkerResult::Indexed {
+                class_indices,
+                probabilities,
+            } => (class_indices, probabilities),
+        };
+        let labels = self.labels.as_ref().ok_or_else(|| {
+            ServiceError::Protocol("脚本返回了类别索引，但模型没有类别名称文件".to_string())
+        })?;
+        if class_indices.len() != probabilities.len() {
+            return Err(ServiceError::Protocol(format!(
+                "类别索引与概率数量不一致: {} / {}",
+                class_indices.len(),
+                probabilities.len()
+            )));
+        }
+        let ranked: Vec<(usize, f32)> = class_indices
+            .into_iter()
+            .map(|index| index as usize)
+            .zip(probabilities)
+            .collect();
+        match self.task {
+            ModelTask::MultiLabel => labels.multi_label_result(&ranked, ranked.len()),
+            _ => labels.result(&ranked),
+        }
+    }
+
+    /// 把脚本返回的检测目标转换为检测结果，有类别索引的目标按类别表取名称
+    fn resolve_detections(
+        &self,
+        detections: Vec<WorkerDetection>,
+    ) -> Result<ModelResult, ServiceError> {
+        let detections = detections
+            .into_iter()
+            .map(|detection| {
+                let bbox =
+                    BoundingBox::from_corners(detection.bbox).map_err(ServiceError::Protocol)?;
+                match (detection.class_index, &self.labels, detection.label) {
+                    (Some(index), Some(labels), _) => {
+                        labels.detection(index as usize, detection.score, bbox)
+                    }
+                    (class_index, _, Some(label)) => Ok(Detection {
+                        class_index,
+                        class_id: None,
+                        label,
+                        score: detection.score,
+                        bbox,
+                    }),
+                    (Some(_), None, None) => Err(ServiceError::Protocol(
+                        "脚本返回了类别索引，但模型没有类别名称文件".to_string(),
+                    )),
+                    (None, _, None) => Err(ServiceError::Protocol("检测目标缺少类别".to_string())),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(detection_result(detections))
     }
 
-    pub fn process_image(&mut self, image_path: &str) -> Result<String, String> {
-        let stdin = self.child.stdin.as_mut().ok_or("无法获取子进程stdin")?;
-        let command = format!("process_image:{}\n", image_path);
-        stdin
-            .write_all(command.as_bytes())
-            .map_err(|e| e.to_string())?;
-        stdin.flush().map_err(|e| e.to_string())?;
+    /// 发送一条请求，并在超时时间内等待读取任务转交的响应
+    async fn request(
+        &self,
+        worker: &Worker,
+        command: WorkerCommand,
+        timeout: Duration,
+    ) -> Result<WorkerMessage, ServiceError> {
+        let id = format!(
+            "req-{}",
+            self.next_request_id.fetch_add(1, Ordering::Relaxed) + 1
+        );
 
-        // 读取输出
-        let stdout = self.child.stdout.as_mut().ok_or("无法获取子进程stdout")?;
-        let mut reader = BufReader::new(stdout);
-        let mut output = String::new();
-        reader.read_line(&mut output).map_err(|e| e.to_string())?;
+        let line = match worker.spec.protocol {
+            WorkerProtocol::Json => serde_json::to_string(&WorkerRequest::new(id.clone(), command))
+                .map_err(|e| ServiceError::Protocol(format!("序列化请求失败: {}", e)))?,
+            // 兼容模式：使用旧版 `process_image:<路径>` 命令
+            WorkerProtocol::Legacy => match command {
+                WorkerCommand::Infer { image_path } => format!("process_image:{}", image_path),
+                _ => {
+                    return Err(ServiceError::Protocol(
+                        "兼容模式的推理脚本不支持该命令".to_string(),
+                    ))
+                }
+            },
+        };
 
-        Ok(output.trim().to_string())
+        let receiver = worker.enqueue(id.clone(), line)?;
+        match tokio::time::timeout(timeout, receiver).await {
+            Ok(Ok(reply)) => reply,
+            // 进程退出（stdout EOF）时读取任务会丢弃所有等待中的请求
+            Ok(Err(_)) => Err(ServiceError::ProcessExited),
+            Err(_) => {
+                worker.cancel(&id);
+                Err(ServiceError::Timeout(timeout.as_secs()))
+            }
+        }
     }
 
-    // 修复switch_model方法中的相同问题
-    pub fn switch_model(&mut self, script_path: &str, model_path: &str) -> Result<(), String> {
-        // 标准化路径分隔符
-        let script_path = script_path.replace('/', std::path::MAIN_SEPARATOR.to_string().as_str());
-        let model_path = model_path.replace('/', std::path::MAIN_SEPARATOR.to_string().as_str());
+    /// 处理进程崩溃或卡死：通知前端，然后按退避策略重启
+    async fn recover(&self, failed: &Arc<Worker>, reason: &str) -> Result<(), ServiceError> {
+        let _guard = self.restart_lock.lock().await;
 
-        // 如果脚本和模型都没变，无需重启
-        if self.current_script == script_path && self.current_model == model_path {
+        // 服务已关闭，进程是被主动终止的
+        if self.closed.load(Ordering::Relaxed) {
+            return Err(ServiceError::ProcessExited);
+        }
+
+        // 其他请求已经完成了重启，或者模型已被切换
+        if !Arc::ptr_eq(&self.current(), failed) {
             return Ok(());
         }
 
-        println!("切换模型: 脚本={}, 模型={}", script_path, model_path);
+        let exit_code = failed.exit_code().await;
+        failed.terminate().await;
 
-        // 终止当前进程
-        if let Err(e) = self.child.kill() {
-            println!("终止Python进程时出错: {}", e);
+        let attempt = self.restart_attempts.load(Ordering::Relaxed);
+        println!("Python进程异常: {}，退出码: {:?}", reason, exit_code);
+        if let Some(listener) = &self.crash_listener {
+            listener(WorkerCrashEvent {
+                model_id: None,
+                script_path: failed.spec.script_path.clone(),
+                model_path: failed.spec.model_path.clone(),
+                reason: reason.to_string(),
+                exit_code,
+                restart_attempt: attempt + 1,
+            });
         }
 
-        // 启动新进程
-        let mut command = Command::new(&self.python_executable);
-        command
-            .arg(&script_path)
-            .arg("--server")
-            .arg(&model_path)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+        self.restart_with_backoff(&failed.spec).await
+    }
+
+    /// 按指数退避重启推理进程，新进程就绪后才替换当前进程
+    async fn restart_with_backoff(&self, spec: &WorkerSpec) -> Result<(), ServiceError> {
+        let mut last_error = ServiceError::ProcessExited;
+        for _ in 0..MAX_RESTART_ATTEMPTS {
+            let attempt = self.restart_attempts.fetch_add(1, Ordering::Relaxed);
+            let delay = restart_backoff(attempt);
+            println!(
+                "{} 毫秒后第 {} 次重启Python进程",
+                delay.as_millis(),
+                attempt + 1
+            );
+            tokio::time::sleep(delay).await;
+
+            let worker = match spawn_worker(&self.python_executable, spec.clone()) {
+                Ok(worker) => Arc::new(worker),
+                Err(e) => {
+                    println!("重启Python进程失败: {}", e);
+                    last_error = e.into();
+                    continue;
+                }
+            };
+
+            match worker.wait_ready(self.startup_timeout).await {
+                Ok(_) => {
+                    self.replace_worker(worker);
+                    return Ok(());
+                }
+                Err(e) => {
+                    println!("重启Python进程失败: {}", e);
+                    worker.terminate().await;
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+}
+
+fn lock_pending(pending: &Mutex<PendingRequests>) -> MutexGuard<'_, PendingRequests> {
+    pending.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 仅在加载阶段更新状态，避免覆盖已确定的结果
+fn finish_loading(state: &watch::Sender<WorkerState>, next: WorkerState) {
+    state.send_if_modified(|current| {
+        if let WorkerState::Loading { .. } = current {
+            *current = next;
+            true
+        } else {
+            false
+        }
+    });
+}
+
+/// 第 attempt 次重启前的等待时间
+fn restart_backoff(attempt: u32) -> Duration {
+    RESTART_BACKOFF_BASE
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(RESTART_BACKOFF_MAX)
+}
+
+/// 将非预期的协议消息转换为错误
+fn unexpected_message(message: WorkerMessage) -> ServiceError {
+    match message {
+        WorkerMessage::Error { code, message } => ServiceError::Worker { code, message },
+        other => ServiceError::Protocol(format!("收到非预期的消息: {:?}", other)),
+    }
+}
+
+/// 解析兼容模式下的一行输出，非结果输出返回None
+fn parse_legacy_output(line: &str) -> Option<Reply> {
+    let value = serde_json::from_str::<serde_json::Value>(line).ok()?;
+    // 跳过非对象输出，以及新版脚本发出的协议消息（如就绪消息）
+    if !value.is_object() || serde_json::from_value::<WorkerResponse>(value.clone()).is_ok() {
+        return None;
+    }
+    if let Some(error) = value.get("error").and_then(|e| e.as_str()) {
+        return Some(Ok(WorkerMessage::Error {
+            code: WorkerErrorCode::Unknown,
+            message: error.to_string(),
+        }));
+    }
+    Some(
+        serde_json::from_value::<WorkerResult>(value)
+            .map(|result| WorkerMessage::Result { result })
+            .map_err(|e| ServiceError::Protocol(format!("结果解析失败: {}", e))),
+    )
+}
+
+/// 构建Python服务进程的命令，并按平台设置进程属性
+fn build_command(python_executable: &str, spec: &WorkerSpec) -> Command {
+    let mut command = Command::new(python_executable);
+    command
+        .arg(&spec.script_path)
+        .arg("--server")
+        .arg(&spec.model_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // 服务被意外丢弃时兜底结束子进程
+        .kill_on_drop(true);
+
+    #[cfg(windows)]
+    {
+        // 在 Windows 上隐藏控制台窗口
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    #[cfg(unix)]
+    {
+        // 在 Unix 上放入独立的进程组：终端的 Ctrl+C 不会直接打断推理进程，
+        // 关闭时可以向整个进程组（包括脚本派生的子进程）发送信号
+        command.process_group(0);
+    }
+
+    command
+}
+
+/// 启动Python服务进程，并创建stdin写入任务和stdout读取任务
+fn spawn_worker(python_executable: &str, spec: WorkerSpec) -> std::io::Result<Worker> {
+    let mut command = build_command(python_executable, &spec);
+
+    println!("启动Python进程: {:?}", command.as_std());
+
+    let mut child = command.spawn()?;
+
+    if let Some(stderr) = child.stderr.take() {
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                println!("Python stderr: {}", line);
+            }
+        });
+    }
+
+    let stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| std::io::Error::other("无法获取子进程stdin"))?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| std::io::Error::other("无法获取子进程stdout"))?;
 
-        println!("重启Python进程: {:?}", command);
+    // 兼容模式的脚本没有就绪握手，启动后即视为可用
+    let (state, _) = watch::channel(match spec.protocol {
+        WorkerProtocol::Json => WorkerState::Loading {
+            since: Instant::now(),
+        },
+        WorkerProtocol::Legacy => WorkerState::Ready { info: None },
+    });
+    let state = Arc::new(state);
+    let pending = Arc::new(Mutex::new(PendingRequests::default()));
 
-        let mut child = command.spawn().expect("无法启动Python进程");
+    let (writer, queue) = mpsc::unbounded_channel();
+    tokio::spawn(write_stdin(stdin, queue));
+    tokio::spawn(read_stdout(
+        stdout,
+        spec.protocol,
+        Arc::clone(&pending),
+        Arc::clone(&state),
+    ));
+
+    Ok(Worker {
+        spec,
+        child: tokio::sync::Mutex::new(child),
+        writer,
+        pending,
+        state,
+    })
+}
 
-        // 使用take()代替expect()
-        if let Some(stderr) = child.stderr.take() {
-            std::thread::spawn(move || {
-                let reader = BufReader::new(stderr);
-                for line in reader.lines() {
-                    if let Ok(line) = line {
-                        println!("Python stderr: {}", line);
+/// 写入任务：按队列顺序把请求写入stdin
+async fn write_stdin(mut stdin: ChildStdin, mut queue: mpsc::UnboundedReceiver<WriterMessage>) {
+    while let Some(WriterMessage::Line(mut line)) = queue.recv().await {
+        line.push('\n');
+        let written = match stdin.write_all(line.as_bytes()).await {
+            Ok(()) => stdin.flush().await,
+            Err(e) => Err(e),
+        };
+        // 写入失败说明进程已退出，读取任务会在EOF时通知所有等待中的请求
+        if let Err(e) = written {
+            println!("写入Python进程失败: {}", e);
+            break;
+        }
+    }
+    // 收到 Close 或队列关闭时丢弃stdin，脚本读到EOF后退出
+}
+
+/// 读取任务：处理就绪握手，并把响应转交给对应的请求
+async fn read_stdout(
+    stdout: ChildStdout,
+    protocol: WorkerProtocol,
+    pending: Arc<Mutex<PendingRequests>>,
+    state: Arc<watch::Sender<WorkerState>>,
+) {
+    let mut lines = BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        match protocol {
+            WorkerProtocol::Json => route_response(line, &pending, &state),
+            // 兼容模式没有请求ID，按先进先出分发
+            WorkerProtocol::Legacy => match parse_legacy_output(line) {
+                Some(reply) => {
+                    if let Some((_, sender)) = lock_pending(&pending).entries.pop_front() {
+                        let _ = sender.send(reply);
                     }
                 }
-            });
+                None => println!("Python stdout(非协议输出): {}", line),
+            },
         }
+    }
 
-        // 更新当前服务状态
-        self.child = child;
-        self.current_script = script_path.to_string();
-        self.current_model = model_path.to_string();
+    // stdout已关闭：丢弃等待中的请求，调用方会收到进程退出错误
+    {
+        let mut pending = lock_pending(&pending);
+        pending.closed = true;
+        pending.entries.clear();
+    }
 
-        // 等待服务启动
-        std::thread::sleep(Duration::from_secs(1));
+    // 进程在就绪前退出，视为加载失败
+    finish_loading(
+        &state,
+        WorkerState::Failed {
+            error: "Python进程在模型加载完成前退出".to_string(),
+        },
+    );
+}
 
-        Ok(())
+/// 解析一行协议输出，按请求ID转交给调用方
+fn route_response(
+    line: &str,
+    pending: &Mutex<PendingRequests>,
+    state: &watch::Sender<WorkerState>,
+) {
+    let response = match serde_json::from_str::<WorkerResponse>(line) {
+        Ok(response) => response,
+        Err(_) => {
+            // 脚本中零散的print输出，不属于协议消息
+            println!("Python stdout(非协议输出): {}", line);
+            return;
+        }
+    };
+
+    // 无请求ID的消息来自脚本本身：就绪、启动失败或无法解析的请求
+    let Some(id) = response.id else {
+        match response.message {
+            WorkerMessage::Ready { info } => {
+                println!("模型加载完成: {} ({})", info.name, info.model_type);
+                finish_loading(state, WorkerState::Ready { info: Some(info) });
+            }
+            WorkerMessage::Error {
+                code: WorkerErrorCode::ModelLoadFailed,
+                message,
+            } => {
+                println!("模型加载失败: {}", message);
+                finish_loading(state, WorkerState::Failed { error: message });
+            }
+            other => println!("忽略无请求ID的消息: {:?}", other),
+        }
+        return;
+    };
+
+    let entry = {
+        let mut pending = lock_pending(pending);
+        pending
+            .entries
+            .iter()
+            .position(|(pending_id, _)| *pending_id == id)
+            .and_then(|index| pending.entries.remove(index))
+    };
+    let Some((_, sender)) = entry else {
+        // 关闭进程时发送的 shutdown 请求不需要等待响应
+        if !matches!(response.message, WorkerMessage::Bye) {
+            println!("丢弃没有对应请求的响应: {}", id);
+        }
+        return;
+    };
+
+    let reply = if response.v == PROTOCOL_VERSION {
+        Ok(response.message)
+    } else {
+        Err(ServiceError::Protocol(format!(
+            "协议版本不匹配: 期望 {}, 实际 {}",
+            PROTOCOL_VERSION, response.v
+        )))
+    };
+    // 调用方可能已经超时放弃
+    let _ = sender.send(reply);
+}
+
+/// 在超时时间内等待进程退出，返回进程是否已退出并被回收
+async fn wait_with_timeout(child: &mut Child, timeout: Duration) -> bool {
+    match tokio::time::timeout(timeout, child.wait()).await {
+        Ok(Ok(_)) => true,
+        Ok(Err(e)) => {
+            println!("等待Python进程退出时出错: {}", e);
+            false
+        }
+        Err(_) => false,
     }
 }
 
-impl Drop for PythonService {
-    fn drop(&mut self) {
-        // 终止Python进程
-        if let Err(e) = self.child.kill() {
-            println!("终止Python进程时出错: {}", e);
+/// 向子进程所在的进程组发送信号
+#[cfg(unix)]
+fn signal_process_group(child: &Child, signal: libc::c_int) -> bool {
+    // 进程已被回收
+    let Some(pid) = child.id() else {
+        return false;
+    };
+    // 子进程以 process_group(0) 启动，其进程组ID等于自身PID
+    let ret = unsafe { libc::killpg(pid as libc::pid_t, signal) };
+    if ret != 0 {
+        println!(
+            "向Python进程组发送信号失败: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+    ret == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending(ids: &[&str]) -> (Mutex<PendingRequests>, Vec<oneshot::Receiver<Reply>>) {
+        let mut requests = PendingRequests::default();
+        let mut receivers = Vec::new();
+        for id in ids {
+            let (sender, receiver) = oneshot::channel();
+            requests.entries.push_back((id.to_string(), sender));
+            receivers.push(receiver);
+        }
+        (Mutex::new(requests), receivers)
+    }
+
+    fn loading() -> watch::Sender<WorkerState> {
+        watch::channel(WorkerState::Loading {
+            since: Instant::now(),
+        })
+        .0
+    }
+
+    #[test]
+    fn legacy_result_and_error_are_parsed() {
+        let line = r#"{"prediction": "猫", "confidence": 0.9, "class_probabilities": {"猫": 0.9, "狗": 0.1}}"#;
+        match parse_legacy_output(line) {
+            Some(Ok(WorkerMessage::Result {
+                result: WorkerResult::Labeled(result),
+            })) => {
+                assert_eq!(result.prediction, "猫");
+                assert_eq!(result.class_probabilities.len(), 2);
+            }
+            other => panic!("非预期的解析结果: {:?}", other),
         }
+
+        match parse_legacy_output(r#"{"error": "模型文件不存在"}"#) {
+            Some(Ok(WorkerMessage::Error { code, message })) => {
+                assert_eq!(code, WorkerErrorCode::Unknown);
+                assert_eq!(message, "模型文件不存在");
+            }
+            other => panic!("非预期的解析结果: {:?}", other),
+        }
+
+        assert!(matches!(
+            parse_legacy_output(r#"{"unexpected": true}"#),
+            Some(Err(ServiceError::Protocol(_)))
+        ));
+    }
+
+    #[test]
+    fn legacy_parser_skips_non_results() {
+        assert!(parse_legacy_output("Loading model...").is_none());
+        assert!(parse_legacy_output("[1, 2, 3]").is_none());
+        let ready = r#"{"v": 1, "type": "ready", "info": {"name": "m", "model_type": "t", "num_classes": 2, "device": "cpu", "protocol_version": 1}}"#;
+        assert!(parse_legacy_output(ready).is_none());
+    }
+
+    #[test]
+    fn responses_are_routed_by_request_id() {
+        let (requests, mut receivers) = pending(&["a", "b"]);
+        let state = loading();
+
+        route_response(r#"{"v": 1, "id": "b", "type": "pong"}"#, &requests, &state);
+        assert!(matches!(
+            receivers[1].try_recv(),
+            Ok(Ok(WorkerMessage::Pong))
+        ));
+        assert!(receivers[0].try_recv().is_err());
+        assert_eq!(lock_pending(&requests).entries.len(), 1);
+
+        // 没有对应请求的响应和非协议输出被忽略
+        route_response(r#"{"v": 1, "id": "c", "type": "pong"}"#, &requests, &state);
+        route_response("debug output", &requests, &state);
+        assert_eq!(lock_pending(&requests).entries.len(), 1);
+
+        route_response(r#"{"v": 2, "id": "a", "type": "pong"}"#, &requests, &state);
+        assert!(matches!(
+            receivers[0].try_recv(),
+            Ok(Err(ServiceError::Protocol(_)))
+        ));
+        assert!(lock_pending(&requests).entries.is_empty());
+    }
+
+    #[test]
+    fn messages_without_id_update_loading_state() {
+        let (requests, _) = pending(&[]);
+
+        let state = loading();
+        let ready = r#"{"v": 1, "type": "ready", "info": {"name": "m", "model_type": "t", "num_classes": 2, "device": "cpu", "protocol_version": 1}}"#;
+        route_response(ready, &requests, &state);
+        assert!(matches!(
+            &*state.borrow(),
+            WorkerState::Ready { info: Some(info) } if info.num_classes == 2
+        ));
+
+        let state = loading();
+        let failed =
+            r#"{"v": 1, "type": "error", "code": "model_load_failed", "message": "缺少权重"}"#;
+        route_response(failed, &requests, &state);
+        assert!(matches!(
+            &*state.borrow(),
+            WorkerState::Failed { error } if error == "缺少权重"
+        ));
+
+        // 加载结束后不再被覆盖
+        route_response(ready, &requests, &state);
+        assert!(matches!(&*state.borrow(), WorkerState::Failed { .. }));
     }
 }
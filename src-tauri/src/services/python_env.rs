@@ -0,0 +1,530 @@
+use crate::config::constants;
+use crate::models::inference_result::{
+    PythonCandidate, PythonEnvironmentReport, PythonSetupEvent, PythonSetupStage, PythonSource,
+};
+use crate::services::diagnostics::{check_python, version_at_least, PYTHON_MIN_VERSION};
+use crate::utils::path_utils::{get_app_data_path, get_resource_path};
+use futures::future::join_all;
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+use std::sync::RwLock;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+
+/// 创建虚拟环境时发送的进度事件名
+pub const PYTHON_SETUP_EVENT: &str = "python-setup-progress";
+
+/// 应用管理的虚拟环境目录，位于应用数据目录下
+pub const MANAGED_VENV_DIR: &str = "python-env";
+
+/// 随应用分发的依赖列表
+pub const REQUIREMENTS_FILE: &str = "resources/python/requirements.txt";
+
+/// 离线安装使用的 wheel 目录，构建离线安装包时放入
+pub const WHEELHOUSE_DIR: &str = "resources/python/wheelhouse";
+
+/// 查询 conda、py 启动器等工具的超时时间
+const TOOL_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 创建虚拟环境的超时时间
+const VENV_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// 安装依赖的超时时间，在线安装 torch 需要下载较大的文件
+const PIP_INSTALL_TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// 常见的 conda 安装目录，位于用户主目录下
+const CONDA_ROOTS: &[&str] = &["miniconda3", "anaconda3", "miniforge3", "mambaforge"];
+
+lazy_static! {
+    /// 运行中选择的解释器，为空时使用配置中的 `python_executable`
+    static ref PYTHON_EXECUTABLE: RwLock<Option<String>> = RwLock::new(None);
+}
+
+/// 同一时间只允许一个创建虚拟环境的任务
+static SETUP_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// 当前使用的 Python 解释器
+pub fn current_executable() -> String {
+    PYTHON_EXECUTABLE
+        .read()
+        .ok()
+        .and_then(|executable| executable.clone())
+        .unwrap_or_else(|| constants::get_config().python_executable.clone())
+}
+
+/// 切换 Python 解释器，之后启动的模型进程使用新的解释器
+///
+/// 工作池中已加载的 Python 模型在下次使用时按新的解释器重新启动。
+pub fn set_executable(executable: &str) {
+    if let Ok(mut current) = PYTHON_EXECUTABLE.write() {
+        *current = Some(executable.to_string());
+    }
+}
+
+/// 创建不显示控制台窗口的命令，调用方被丢弃时结束子进程
+pub fn hidden_command(program: impl AsRef<std::ffi::OsStr>) -> Command {
+    let mut command = Command::new(program);
+    command.stdin(Stdio::null()).kill_on_drop(true);
+    #[cfg(windows)]
+    {
+        command.creation_flags(crate::services::python::CREATE_NO_WINDOW);
+    }
+    command
+}
+
+/// 虚拟环境中的解释器路径
+pub fn venv_python(venv_dir: &Path) -> PathBuf {
+    if cfg!(windows) {
+        venv_dir.join("Scripts").join("python.exe")
+    } else {
+        venv_dir.join("bin").join("python")
+    }
+}
+
+/// conda 环境或 pyenv 版本目录中的解释器路径
+fn env_python(env_dir: &Path) -> PathBuf {
+    if cfg!(windows) {
+        env_dir.join("python.exe")
+    } else {
+        env_dir.join("bin").join("python")
+    }
+}
+
+/// 查找本机的 Python 解释器，并检查每个解释器的版本和依赖库
+///
+/// 依次查找当前配置、应用管理的虚拟环境、工作目录中的 `.venv`、conda 环境、pyenv、
+/// py 启动器和 PATH；同一个解释器只保留第一次发现的位置。满足要求的解释器排在前面。
+pub async fn discover_interpreters(app_handle: &AppHandle) -> Vec<PythonCandidate> {
+    let current = current_executable();
+    let mut paths: Vec<(String, PythonSource)> = vec![(current.clone(), PythonSource::Configured)];
+
+    if let Ok(managed) = get_app_data_path(app_handle, MANAGED_VENV_DIR) {
+        paths.push((
+            path_string(venv_python(Path::new(&managed))),
+            PythonSource::ManagedVenv,
+        ));
+    }
+    if let Ok(current_dir) = std::env::current_dir() {
+        for dir in current_dir.ancestors() {
+            paths.push((
+                path_string(venv_python(&dir.join(".venv"))),
+                PythonSource::LocalVenv,
+            ));
+        }
+    }
+    let home = dirs::home_dir();
+    for env_dir in conda_environments(home.as_deref()).await {
+        paths.push((path_string(env_python(&env_dir)), PythonSource::Conda));
+    }
+    for path in pyenv_interpreters(home.as_deref()) {
+        paths.push((path_string(path), PythonSource::Pyenv));
+    }
+    for path in py_launcher_interpreters().await {
+        paths.push((path, PythonSource::PyLauncher));
+    }
+    for path in path_interpreters() {
+        paths.push((path_string(path), PythonSource::Path));
+    }
+
+    // 当前配置可以是 PATH 中的命令名，其余位置只保留存在的文件
+    let mut seen = HashSet::new();
+    let paths: Vec<(String, PythonSource)> = paths
+        .into_iter()
+        .filter(|(path, source)| *source == PythonSource::Configured || Path::new(path).is_file())
+        .filter(|(path, _)| seen.insert(path.clone()))
+        .collect();
+
+    let reports = join_all(paths.iter().map(|(path, _)| check_python(path, true))).await;
+
+    // 无法运行的位置（如 pyenv 未启用版本的 shim）不列出，当前配置除外；
+    // 同一解释器的不同链接（如 python 与 python3）只保留一个，虚拟环境的 prefix 不同，不会与基础解释器合并
+    let mut seen = HashSet::new();
+    let mut candidates: Vec<PythonCandidate> = paths
+        .into_iter()
+        .zip(reports)
+        .filter(
+            |((_, source), report)| match (&report.resolved_path, &report.prefix) {
+                (Some(resolved), Some(prefix)) => {
+                    let resolved = std::fs::canonicalize(resolved)
+                        .map(path_string)
+                        .unwrap_or_else(|_| resolved.clone());
+                    seen.insert((resolved, prefix.clone()))
+                }
+                _ => *source == PythonSource::Configured,
+            },
+        )
+        .map(|((path, source), environment)| PythonCandidate {
+            is_current: source == PythonSource::Configured,
+            path,
+            source,
+            environment,
+        })
+        .collect();
+    candidates.sort_by_key(|candidate| !candidate.environment.passed);
+    candidates
+}
+
+/// 检查解释器后设置为当前解释器并保存到配置文件
+///
+/// 依赖库不满足要求时仍然保存，检查结果返回给界面显示；无法启动的解释器会被拒绝。
+pub async fn select_interpreter(
+    app_handle: &AppHandle,
+    executable: &str,
+) -> Result<PythonEnvironmentReport, String> {
+    let report = check_python(executable, true).await;
+    if report.resolved_path.is_none() {
+        return Err(report
+            .error
+            .unwrap_or_else(|| format!("无法启动 Python: {}", executable)));
+    }
+    if let Some(error) = &report.error {
+        println!("Python 环境不满足要求: {}", error);
+    }
+
+    set_executable(executable);
+    if let Err(e) = constants::save_python_executable(app_handle, executable) {
+        println!("保存Python解释器失败: {}", e);
+    }
+    println!("使用Python解释器: {}", executable);
+    Ok(report)
+}
+
+/// 在应用数据目录创建虚拟环境并安装依赖，成功后设置为当前解释器并保存到配置文件
+///
+/// `base_python` 为空时使用发现的第一个版本满足要求的解释器；`wheelhouse` 为空时使用随应用
+/// 分发的 wheel 目录（如果有），指定了 wheel 目录时只从该目录离线安装。已有虚拟环境时直接安装依赖。
+/// 各步骤通过 `python-setup-progress` 事件报告进度。
+pub async fn setup_managed_venv(
+    app_handle: &AppHandle,
+    base_python: Option<String>,
+    wheelhouse: Option<String>,
+) -> Result<PythonEnvironmentReport, String> {
+    let _guard = SETUP_LOCK
+        .try_lock()
+        .map_err(|_| "正在创建Python环境，请等待完成")?;
+
+    let result = setup_venv(app_handle, base_python, wheelhouse).await;
+    match &result {
+        Ok(report) => emit_progress(
+            app_handle,
+            PythonSetupStage::Finished,
+            format!(
+                "Python环境已就绪: {}",
+                report
+                    .resolved_path
+                    .as_deref()
+                    .unwrap_or(&report.executable)
+            ),
+        ),
+        Err(e) => emit_progress(app_handle, PythonSetupStage::Failed, e.clone()),
+    }
+    result
+}
+
+async fn setup_venv(
+    app_handle: &AppHandle,
+    base_python: Option<String>,
+    wheelhouse: Option<String>,
+) -> Result<PythonEnvironmentReport, String> {
+    let requirements = get_resource_path(app_handle, REQUIREMENTS_FILE)?;
+    let wheelhouse = match wheelhouse {
+        Some(wheelhouse) if Path::new(&wheelhouse).is_dir() => Some(wheelhouse),
+        Some(wheelhouse) => return Err(format!("wheel 目录不存在: {}", wheelhouse)),
+        None => get_resource_path(app_handle, WHEELHOUSE_DIR)
+            .ok()
+            .filter(|path| Path::new(path).is_dir()),
+    };
+
+    let venv_dir = PathBuf::from(get_app_data_path(app_handle, MANAGED_VENV_DIR)?);
+    let python = venv_python(&venv_dir);
+
+    if python.is_file() {
+        emit_progress(
+            app_handle,
+            PythonSetupStage::CreateVenv,
+            format!("使用已有的虚拟环境: {}", venv_dir.display()),
+        );
+    } else {
+        let base_python = match base_python {
+            Some(base_python) => base_python,
+            None => find_base_interpreter(app_handle).await?,
+        };
+        let report = check_python(&base_python, true).await;
+        let version = report.version.as_deref().ok_or_else(|| {
+            report
+                .error
+                .clone()
+                .unwrap_or_else(|| format!("无法启动 Python: {}", base_python))
+        })?;
+        if !version_at_least(version, PYTHON_MIN_VERSION) {
+            return Err(format!(
+                "{} 的版本为 {}，需要 {} 或更高版本",
+                base_python, version, PYTHON_MIN_VERSION
+            ));
+        }
+
+        emit_progress(
+            app_handle,
+            PythonSetupStage::CreateVenv,
+            format!("使用 {} 创建虚拟环境: {}", base_python, venv_dir.display()),
+        );
+        let mut command = hidden_command(&base_python);
+        command.arg("-m").arg("venv").arg(&venv_dir);
+        let output = run_output(command, VENV_TIMEOUT).await?;
+        if !output.status.success() || !python.is_file() {
+            return Err(format!(
+                "创建虚拟环境失败: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+    }
+
+    let mut command = hidden_command(&python);
+    command
+        .args(["-m", "pip", "install", "--disable-pip-version-check"])
+        .arg("-r")
+        .arg(&requirements);
+    let message = match &wheelhouse {
+        Some(wheelhouse) => {
+            command
+                .arg("--no-index")
+                .arg("--find-links")
+                .arg(wheelhouse);
+            format!("从 {} 离线安装依赖", wheelhouse)
+        }
+        None => "从软件包索引安装依赖".to_string(),
+    };
+    emit_progress(app_handle, PythonSetupStage::InstallPackages, message);
+    run_pip(app_handle, command).await?;
+
+    emit_progress(
+        app_handle,
+        PythonSetupStage::Verify,
+        "检查Python环境".to_string(),
+    );
+    let executable = path_string(python);
+    let report = check_python(&executable, true).await;
+    if !report.passed {
+        return Err(report
+            .error
+            .unwrap_or_else(|| "Python 环境不满足要求".to_string()));
+    }
+
+    set_executable(&executable);
+    constants::save_python_executable(app_handle, &executable)?;
+    println!("已创建Python环境: {}", executable);
+    Ok(report)
+}
+
+/// 选择创建虚拟环境使用的解释器：当前配置优先，其次是发现的第一个版本满足要求的解释器
+async fn find_base_interpreter(app_handle: &AppHandle) -> Result<String, String> {
+    discover_interpreters(app_handle)
+        .await
+        .into_iter()
+        .filter(|candidate| candidate.source != PythonSource::ManagedVenv)
+        .filter(|candidate| {
+            candidate
+                .environment
+                .version
+                .as_deref()
+                .is_some_and(|version| version_at_least(version, PYTHON_MIN_VERSION))
+        })
+        .min_by_key(|candidate| !candidate.is_current)
+        .map(|candidate| candidate.path)
+        .ok_or_else(|| format!("没有找到 Python {} 或更高版本的解释器", PYTHON_MIN_VERSION))
+}
+
+/// 运行 pip，把输出逐行作为进度事件发送
+async fn run_pip(app_handle: &AppHandle, mut command: Command) -> Result<(), String> {
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    println!("安装Python依赖: {:?}", command.as_std());
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("无法启动 pip: {}", e))?;
+
+    let stdout = child
+        .stdout
+        .take()
+        .map(|out| forward_lines(app_handle, out));
+    let stderr = child
+        .stderr
+        .take()
+        .map(|err| forward_lines(app_handle, err));
+
+    let status = tokio::time::timeout(PIP_INSTALL_TIMEOUT, child.wait())
+        .await
+        .map_err(|_| {
+            format!(
+                "安装依赖超过 {} 分钟未完成",
+                PIP_INSTALL_TIMEOUT.as_secs() / 60
+            )
+        })?
+        .map_err(|e| format!("等待 pip 结束失败: {}", e))?;
+
+    let mut last_error = None;
+    if let Some(stdout) = stdout {
+        let _ = stdout.await;
+    }
+    if let Some(stderr) = stderr {
+        last_error = stderr.await.ok().flatten();
+    }
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "安装依赖失败 ({}): {}",
+            status,
+            last_error.unwrap_or_default()
+        ))
+    }
+}
+
+/// 在后台逐行转发输出，返回最后一行非空内容
+fn forward_lines(
+    app_handle: &AppHandle,
+    output: impl AsyncRead + Unpin + Send + 'static,
+) -> tokio::task::JoinHandle<Option<String>> {
+    let app_handle = app_handle.clone();
+    tokio::spawn(async move {
+        let mut last_line = None;
+        let mut lines = BufReader::new(output).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let line = line.trim_end().to_string();
+            if line.is_empty() {
+                continue;
+            }
+            println!("pip: {}", line);
+            emit_progress(&app_handle, PythonSetupStage::InstallPackages, line.clone());
+            last_line = Some(line);
+        }
+        last_line
+    })
+}
+
+fn emit_progress(app_handle: &AppHandle, stage: PythonSetupStage, message: String) {
+    let event = PythonSetupEvent { stage, message };
+    if let Err(e) = app_handle.emit(PYTHON_SETUP_EVENT, event) {
+        println!("发送Python环境进度事件失败: {}", e);
+    }
+}
+
+/// 运行命令并等待输出，超时后结束进程
+async fn run_output(mut command: Command, timeout: Duration) -> Result<Output, String> {
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    let program = command
+        .as_std()
+        .get_program()
+        .to_string_lossy()
+        .into_owned();
+    tokio::time::timeout(timeout, command.output())
+        .await
+        .map_err(|_| format!("{} 超过 {} 秒未完成", program, timeout.as_secs()))?
+        .map_err(|e| format!("无法运行 {}: {}", program, e))
+}
+
+/// conda 环境目录：`conda env list` 的结果，以及常见安装目录中的 base 和 envs 下的环境
+async fn conda_environments(home: Option<&Path>) -> Vec<PathBuf> {
+    #[derive(Deserialize)]
+    struct CondaEnvList {
+        envs: Vec<PathBuf>,
+    }
+
+    let mut environments = Vec::new();
+    if let Ok(prefix) = std::env::var("CONDA_PREFIX") {
+        environments.push(PathBuf::from(prefix));
+    }
+
+    let conda = std::env::var("CONDA_EXE").unwrap_or_else(|_| "conda".to_string());
+    let mut command = hidden_command(conda);
+    command.args(["env", "list", "--json"]);
+    if let Ok(output) = run_output(command, TOOL_TIMEOUT).await {
+        if let Ok(list) = serde_json::from_slice::<CondaEnvList>(&output.stdout) {
+            environments.extend(list.envs);
+        }
+    }
+
+    for root in CONDA_ROOTS.iter().filter_map(|name| Some(home?.join(name))) {
+        if !root.is_dir() {
+            continue;
+        }
+        environments.push(root.clone());
+        environments.extend(subdirectories(&root.join("envs")));
+    }
+    environments
+}
+
+/// pyenv 安装的各版本，pyenv-win 的版本目录位于 `pyenv-win/versions`
+fn pyenv_interpreters(home: Option<&Path>) -> Vec<PathBuf> {
+    let root = match std::env::var("PYENV_ROOT") {
+        Ok(root) => PathBuf::from(root),
+        Err(_) => match home {
+            Some(home) => home.join(".pyenv"),
+            None => return Vec::new(),
+        },
+    };
+    let versions = if cfg!(windows) {
+        root.join("pyenv-win").join("versions")
+    } else {
+        root.join("versions")
+    };
+    subdirectories(&versions)
+        .into_iter()
+        .map(|dir| env_python(&dir))
+        .collect()
+}
+
+/// Windows 的 py 启动器登记的解释器，输出格式为 ` -V:3.12 *   C:\Python312\python.exe`
+async fn py_launcher_interpreters() -> Vec<String> {
+    if !cfg!(windows) {
+        return Vec::new();
+    }
+    let mut command = hidden_command("py");
+    command.arg("-0p");
+    let Ok(output) = run_output(command, TOOL_TIMEOUT).await else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.trim().split_once(char::is_whitespace))
+        .map(|(_, path)| path.trim_start().trim_start_matches('*').trim().to_string())
+        .filter(|path| !path.is_empty())
+        .collect()
+}
+
+/// PATH 中各目录下的 Python 解释器
+fn path_interpreters() -> Vec<PathBuf> {
+    let names: Vec<String> = if cfg!(windows) {
+        vec!["python.exe".to_string(), "python3.exe".to_string()]
+    } else {
+        let mut names = vec!["python3".to_string(), "python".to_string()];
+        names.extend((10..=14).map(|minor| format!("python3.{}", minor)));
+        names
+    };
+    let Some(path) = std::env::var_os("PATH") else {
+        return Vec::new();
+    };
+    std::env::split_paths(&path)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .collect()
+}
+
+fn subdirectories(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+fn path_string(path: PathBuf) -> String {
+    path.to_string_lossy().into_owned()
+}
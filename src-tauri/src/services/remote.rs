@@ -0,0 +1,268 @@
+use crate::config::constants::RemoteInferenceConfig;
+use crate::models::inference_result::{
+    ModelResult, RemoteErrorBody, RemoteHashRequest, ServiceStatus, WorkerInfo, REMOTE_API_PREFIX,
+    REMOTE_SHA256_HEADER,
+};
+use crate::services::inference::InferenceBackend;
+use crate::services::labels::LabelSet;
+use crate::services::python::ServiceError;
+use futures::future::BoxFuture;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// 重试的初始等待时间，每次失败翻倍
+const RETRY_BACKOFF_BASE: Duration = Duration::from_millis(500);
+
+#[derive(Clone)]
+enum RemoteState {
+    Loading { since: Instant },
+    Ready { info: WorkerInfo },
+    Failed { error: String },
+}
+
+/// 远程推理后端，通过HTTP调用共享的推理服务
+///
+/// 服务端接口见 `src/bin/inference_server.rs`，各模型使用与本地相同的模型ID。
+pub struct RemoteBackend {
+    client: reqwest::Client,
+    /// `{endpoint}/v1/models/{model_id}`
+    model_url: String,
+    timeout_secs: u64,
+    max_retries: u32,
+    send_hash_first: bool,
+    /// 本地的类别表，按类别ID把服务端结果换成当前语言的名称
+    labels: Option<Arc<LabelSet>>,
+    state: watch::Sender<RemoteState>,
+}
+
+impl RemoteBackend {
+    /// 创建后端并在后台查询服务端模型信息，需要在异步运行时中调用
+    pub fn new(
+        config: &RemoteInferenceConfig,
+        model_id: &str,
+        labels: Option<Arc<LabelSet>>,
+    ) -> Result<Arc<Self>, String> {
+        let mut headers = HeaderMap::new();
+        if let Some(token) = &config.auth_token {
+            let value = HeaderValue::from_str(&format!("Bearer {}", token))
+                .map_err(|_| "访问令牌包含无效字符")?;
+            headers.insert(AUTHORIZATION, value);
+        }
+        for (name, value) in &config.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| format!("无效的请求头名称: {}", name))?;
+            let value =
+                HeaderValue::from_str(value).map_err(|_| format!("请求头 {} 的值无效", name))?;
+            headers.insert(name, value);
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .default_headers(headers)
+            .build()
+            .map_err(|e| format!("无法创建HTTP客户端: {}", e))?;
+
+        let (state, _) = watch::channel(RemoteState::Loading {
+            since: Instant::now(),
+        });
+        let backend = Arc::new(Self {
+            client,
+            model_url: format!(
+                "{}{}/models/{}",
+                config.endpoint.trim_end_matches('/'),
+                REMOTE_API_PREFIX,
+                model_id
+            ),
+            timeout_secs: config.timeout_secs,
+            max_retries: config.max_retries,
+            send_hash_first: config.send_hash_first,
+            labels,
+            state,
+        });
+
+        let loading = Arc::clone(&backend);
+        tokio::spawn(async move {
+            let next = match loading.fetch_info().await {
+                Ok(info) => {
+                    println!("远程模型已就绪: {}", loading.model_url);
+                    RemoteState::Ready { info }
+                }
+                Err(e) => {
+                    println!("远程模型不可用: {}: {}", loading.model_url, e);
+                    RemoteState::Failed {
+                        error: e.to_string(),
+                    }
+                }
+            };
+            loading.state.send_if_modified(|current| {
+                if let RemoteState::Loading { .. } = current {
+                    *current = next;
+                    true
+                } else {
+                    false
+                }
+            });
+        });
+
+        Ok(backend)
+    }
+
+    async fn fetch_info(&self) -> Result<WorkerInfo, ServiceError> {
+        let response = self.send(|| self.client.get(&self.model_url)).await?;
+        parse_response(response).await
+    }
+
+    /// 等待服务端模型信息查询结束
+    async fn ready(&self) -> Result<(), ServiceError> {
+        let mut receiver = self.state.subscribe();
+        let state = receiver
+            .wait_for(|state| !matches!(state, RemoteState::Loading { .. }))
+            .await
+            .map(|state| state.clone())
+            .map_err(|_| ServiceError::ModelLoadFailed("加载任务已退出".to_string()))?;
+
+        match state {
+            RemoteState::Ready { .. } => Ok(()),
+            RemoteState::Failed { error } => Err(ServiceError::ModelLoadFailed(error)),
+            RemoteState::Loading { .. } => unreachable!(),
+        }
+    }
+
+    async fn run(&self, image_path: &str) -> Result<ModelResult, ServiceError> {
+        let result = self.request_result(image_path).await?;
+        Ok(match &self.labels {
+            Some(labels) => labels.localize(result),
+            None => result,
+        })
+    }
+
+    async fn request_result(&self, image_path: &str) -> Result<ModelResult, ServiceError> {
+        self.ready().await?;
+
+        let data = tokio::fs::read(image_path)
+            .await
+            .map_err(|e| ServiceError::Remote(format!("无法读取图像 {}: {}", image_path, e)))?;
+        let sha256 = format!("{:x}", Sha256::digest(&data));
+
+        // 服务端已有该图片时只发送摘要，省去上传
+        if self.send_hash_first {
+            let url = format!("{}/infer-by-hash", self.model_url);
+            let body = RemoteHashRequest {
+                sha256: sha256.clone(),
+            };
+            let response = self.send(|| self.client.post(&url).json(&body)).await?;
+            if response.status() != StatusCode::NOT_FOUND {
+                return parse_response(response).await;
+            }
+        }
+
+        let url = format!("{}/infer", self.model_url);
+        let response = self
+            .send(|| {
+                self.client
+                    .post(&url)
+                    .header(REMOTE_SHA256_HEADER, &sha256)
+                    .body(data.clone())
+            })
+            .await?;
+        parse_response(response).await
+    }
+
+    /// 发送请求，连接失败、超时或服务端错误时按退避时间重试
+    async fn send(&self, build: impl Fn() -> RequestBuilder) -> Result<Response, ServiceError> {
+        let mut attempt = 0;
+        loop {
+            let error = match build().send().await {
+                Ok(response) if !response.status().is_server_error() => return Ok(response),
+                Ok(response) if attempt >= self.max_retries => return Ok(response),
+                Ok(response) => ServiceError::Remote(format!("服务端错误 {}", response.status())),
+                Err(e) if e.is_timeout() => ServiceError::Timeout(self.timeout_secs),
+                Err(e) => ServiceError::Remote(e.to_string()),
+            };
+
+            if attempt >= self.max_retries {
+                return Err(error);
+            }
+            let backoff = RETRY_BACKOFF_BASE.saturating_mul(2u32.saturating_pow(attempt));
+            attempt += 1;
+            println!(
+                "远程推理请求失败: {}，{} 毫秒后第 {} 次重试",
+                error,
+                backoff.as_millis(),
+                attempt
+            );
+            tokio::time::sleep(backoff).await;
+        }
+    }
+}
+
+impl InferenceBackend for RemoteBackend {
+    fn load(&self) -> BoxFuture<'_, Result<(), ServiceError>> {
+        Box::pin(self.ready())
+    }
+
+    fn infer<'a>(
+        &'a self,
+        image_path: &'a str,
+    ) -> BoxFuture<'a, Result<ModelResult, ServiceError>> {
+        Box::pin(self.run(image_path))
+    }
+
+    fn unload(&self) -> BoxFuture<'_, ()> {
+        // 远程模型由服务端管理，这里只停止使用
+        self.state.send_replace(RemoteState::Failed {
+            error: "模型已卸载".to_string(),
+        });
+        Box::pin(async {})
+    }
+
+    fn info(&self) -> BoxFuture<'_, Result<WorkerInfo, ServiceError>> {
+        Box::pin(async move {
+            self.ready().await?;
+            self.fetch_info().await
+        })
+    }
+
+    fn status(&self) -> ServiceStatus {
+        match &*self.state.borrow() {
+            RemoteState::Loading { since } => ServiceStatus::Loading {
+                elapsed_secs: since.elapsed().as_secs(),
+            },
+            RemoteState::Ready { info } => ServiceStatus::Ready {
+                info: Some(info.clone()),
+            },
+            RemoteState::Failed { error } => ServiceStatus::Failed {
+                message: error.clone(),
+            },
+        }
+    }
+}
+
+/// 解析响应：成功时读取结果，422 表示推理脚本拒绝了图片，其余错误作为远程服务故障
+async fn parse_response<T: DeserializeOwned>(response: Response) -> Result<T, ServiceError> {
+    let status = response.status();
+    if status.is_success() {
+        return response
+            .json()
+            .await
+            .map_err(|e| ServiceError::Protocol(format!("无法解析远程服务响应: {}", e)));
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    match serde_json::from_str::<RemoteErrorBody>(&body) {
+        Ok(error) if status == StatusCode::UNPROCESSABLE_ENTITY => Err(ServiceError::Worker {
+            code: error.code,
+            message: error.message,
+        }),
+        Ok(error) => Err(ServiceError::Remote(format!(
+            "{}: {}",
+            status, error.message
+        ))),
+        Err(_) => Err(ServiceError::Remote(format!("{}: {}", status, body))),
+    }
+}
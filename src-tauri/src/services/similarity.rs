@@ -0,0 +1,532 @@
+use crate::config::constants::{self, SimilarityIndexKind};
+use crate::config::models::MODEL_REGISTRY;
+use crate::db::embeddings_collection::EmbeddingRepository;
+use lazy_static::lazy_static;
+use mongodb::bson::oid::ObjectId;
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+
+/// 自动选择索引时，向量数量达到该值后使用近似索引
+pub const ANN_MIN_VECTORS: usize = 10_000;
+
+/// 局部敏感哈希的哈希表数量，越多召回率越高
+const LSH_TABLES: usize = 8;
+
+/// 每个哈希表使用的超平面数量，越多每个桶中的向量越少
+const LSH_BITS: usize = 12;
+
+/// 生成超平面的随机数种子，固定后同样的向量总是落在同样的桶中
+const LSH_SEED: u64 = 0x5eed_1dea_c0ff_ee00;
+
+lazy_static! {
+    /// 已加载的检索索引，按模型ID区分，首次检索时从数据库构建
+    static ref INDEXES: RwLock<HashMap<String, ModelIndex>> = RwLock::new(HashMap::new());
+}
+
+/// 向量检索索引，按余弦相似度查找最相近的图片
+///
+/// 传入的向量都已归一化，实现只需比较内积。近似索引可以实现该 trait 后在
+/// [`create_index`] 中按配置选用。
+pub trait VectorIndex: Send + Sync {
+    /// 添加图片的向量，已有时替换
+    fn insert(&mut self, image_id: ObjectId, vector: Vec<f32>);
+
+    /// 查找与 `query` 最相似的 `k` 个向量，按相似度从高到低排列
+    fn search(&self, query: &[f32], k: usize) -> Vec<(ObjectId, f32)>;
+
+    /// 索引中的向量数量
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// 逐个比较所有向量的索引
+#[derive(Default)]
+pub struct BruteForceIndex {
+    ids: Vec<ObjectId>,
+    vectors: Vec<Vec<f32>>,
+    positions: HashMap<ObjectId, usize>,
+}
+
+impl BruteForceIndex {
+    /// 添加或替换向量，返回向量的位置和被替换的旧向量
+    fn upsert(&mut self, image_id: ObjectId, vector: Vec<f32>) -> (usize, Option<Vec<f32>>) {
+        match self.positions.get(&image_id) {
+            Some(&position) => {
+                let previous = std::mem::replace(&mut self.vectors[position], vector);
+                (position, Some(previous))
+            }
+            None => {
+                self.ids.push(image_id);
+                self.vectors.push(vector);
+                self.positions.insert(image_id, self.ids.len() - 1);
+                (self.ids.len() - 1, None)
+            }
+        }
+    }
+
+    /// 只在指定位置的向量中查找
+    fn search_positions(
+        &self,
+        query: &[f32],
+        k: usize,
+        positions: impl Iterator<Item = usize>,
+    ) -> Vec<(ObjectId, f32)> {
+        let mut scored: Vec<(ObjectId, f32)> = positions
+            .map(|position| (self.ids[position], dot(query, &self.vectors[position])))
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.truncate(k);
+        scored
+    }
+}
+
+impl VectorIndex for BruteForceIndex {
+    fn insert(&mut self, image_id: ObjectId, vector: Vec<f32>) {
+        self.upsert(image_id, vector);
+    }
+
+    fn search(&self, query: &[f32], k: usize) -> Vec<(ObjectId, f32)> {
+        self.search_positions(query, k, 0..self.ids.len())
+    }
+
+    fn len(&self) -> usize {
+        self.ids.len()
+    }
+}
+
+/// 随机超平面局部敏感哈希索引
+///
+/// 每个哈希表用 [`LSH_BITS`] 个随机超平面把向量划分到桶中，方向相近的向量大概率落在同一个桶。
+/// 检索时取查询向量所在的桶及只差一位的相邻桶中的向量精确比较；候选不足 `k` 个时退回逐个比较。
+pub struct LshIndex {
+    vectors: BruteForceIndex,
+    /// 每个哈希表的超平面法向量
+    planes: Vec<Vec<Vec<f32>>>,
+    /// 每个哈希表中各个桶包含的向量位置
+    tables: Vec<HashMap<u32, Vec<usize>>>,
+}
+
+impl LshIndex {
+    pub fn new(dim: usize) -> Self {
+        let mut rng = XorShift(LSH_SEED);
+        let planes = (0..LSH_TABLES)
+            .map(|_| {
+                (0..LSH_BITS)
+                    .map(|_| (0..dim).map(|_| rng.next_gaussian()).collect())
+                    .collect()
+            })
+            .collect();
+        Self {
+            vectors: BruteForceIndex::default(),
+            planes,
+            tables: vec![HashMap::new(); LSH_TABLES],
+        }
+    }
+
+    fn bucket(planes: &[Vec<f32>], vector: &[f32]) -> u32 {
+        planes
+            .iter()
+            .enumerate()
+            .filter(|(_, plane)| dot(plane, vector) >= 0.0)
+            .fold(0, |bucket, (bit, _)| bucket | (1 << bit))
+    }
+}
+
+impl VectorIndex for LshIndex {
+    fn insert(&mut self, image_id: ObjectId, vector: Vec<f32>) {
+        let buckets: Vec<u32> = self
+            .planes
+            .iter()
+            .map(|planes| Self::bucket(planes, &vector))
+            .collect();
+        let (position, previous) = self.vectors.upsert(image_id, vector);
+
+        if let Some(previous) = previous {
+            for (planes, table) in self.planes.iter().zip(&mut self.tables) {
+                if let Some(entries) = table.get_mut(&Self::bucket(planes, &previous)) {
+                    entries.retain(|&entry| entry != position);
+                }
+            }
+        }
+        for (bucket, table) in buckets.into_iter().zip(&mut self.tables) {
+            table.entry(bucket).or_default().push(position);
+        }
+    }
+
+    fn search(&self, query: &[f32], k: usize) -> Vec<(ObjectId, f32)> {
+        let mut candidates = HashSet::new();
+        for (planes, table) in self.planes.iter().zip(&self.tables) {
+            let bucket = Self::bucket(planes, query);
+            let probes =
+                std::iter::once(bucket).chain((0..LSH_BITS).map(|bit| bucket ^ (1 << bit)));
+            for probe in probes {
+                if let Some(entries) = table.get(&probe) {
+                    candidates.extend(entries.iter().copied());
+                }
+            }
+        }
+
+        if candidates.len() < k {
+            return self.vectors.search(query, k);
+        }
+        self.vectors
+            .search_positions(query, k, candidates.into_iter())
+    }
+
+    fn len(&self) -> usize {
+        self.vectors.len()
+    }
+}
+
+/// 按配置和向量数量创建索引
+pub fn create_index(kind: SimilarityIndexKind, dim: usize, count: usize) -> Box<dyn VectorIndex> {
+    match kind {
+        SimilarityIndexKind::BruteForce => Box::new(BruteForceIndex::default()),
+        SimilarityIndexKind::Lsh => Box::new(LshIndex::new(dim)),
+        SimilarityIndexKind::Auto if count >= ANN_MIN_VECTORS => Box::new(LshIndex::new(dim)),
+        SimilarityIndexKind::Auto => Box::new(BruteForceIndex::default()),
+    }
+}
+
+/// 一个模型的检索索引，只接受与模型特征维度一致的向量，维度变化时由 [`add_to_index`] 移除重建
+struct ModelIndex {
+    dim: usize,
+    /// 自动选择时创建的是逐个比较索引，向量增多后需要重建为近似索引
+    upgradable: bool,
+    index: Box<dyn VectorIndex>,
+}
+
+impl ModelIndex {
+    fn insert(&mut self, image_id: ObjectId, vector: &[f32]) {
+        if vector.len() != self.dim {
+            println!(
+                "忽略图片 {} 的特征向量: 维度为 {}，模型的特征维度为 {}",
+                image_id,
+                vector.len(),
+                self.dim
+            );
+            return;
+        }
+        if let Some(vector) = normalize(vector) {
+            self.index.insert(image_id, vector);
+        }
+    }
+
+    fn outgrown(&self) -> bool {
+        self.upgradable && self.index.len() >= ANN_MIN_VECTORS
+    }
+}
+
+/// 保存图片在指定模型下的特征向量，并加入已加载的检索索引
+pub async fn store_embedding(
+    image_id: ObjectId,
+    model_id: &str,
+    vector: &[f32],
+) -> Result<(), String> {
+    EmbeddingRepository::upsert(image_id, model_id, vector)
+        .await
+        .map_err(|e| format!("保存特征向量失败: {}", e))?;
+
+    let mut indexes = INDEXES.write().map_err(|_| "无法获取检索索引锁")?;
+    add_to_index(&mut indexes, model_id, image_id, vector);
+    Ok(())
+}
+
+/// 把新向量加入已加载的索引，需要重建时移除索引，下次检索时从数据库重新构建
+///
+/// 向量维度与索引不同说明模型已更新（或索引建立时还没有向量），重建后以最新的维度为准，
+/// 旧维度的向量被忽略。
+fn add_to_index(
+    indexes: &mut HashMap<String, ModelIndex>,
+    model_id: &str,
+    image_id: ObjectId,
+    vector: &[f32],
+) {
+    let Some(index) = indexes.get_mut(model_id) else {
+        return;
+    };
+    if vector.len() != index.dim {
+        println!(
+            "模型 {} 的特征维度从 {} 变为 {}，下次检索时重建索引",
+            model_id,
+            index.dim,
+            vector.len()
+        );
+        indexes.remove(model_id);
+        return;
+    }
+    index.insert(image_id, vector);
+    if index.outgrown() {
+        println!(
+            "模型 {} 的特征向量达到 {} 个，下次检索时改用近似索引",
+            model_id, ANN_MIN_VECTORS
+        );
+        indexes.remove(model_id);
+    }
+}
+
+/// 查找与图片相似的图片，返回使用的模型ID和按相似度从高到低排列的 (图片ID, 余弦相似度)
+///
+/// 未指定模型时优先使用活跃模型提取的特征向量，没有则使用最近提取的。
+pub async fn find_similar(
+    image_id: &str,
+    model_id: Option<&str>,
+    k: usize,
+) -> Result<(String, Vec<(ObjectId, f32)>), String> {
+    let embeddings = EmbeddingRepository::find_by_image(image_id)
+        .await
+        .map_err(|e| format!("查询特征向量失败: {}", e))?;
+    let active_model_id = MODEL_REGISTRY
+        .lock()
+        .map_err(|_| "无法获取模型注册表锁")?
+        .get_active_model()
+        .map(|model| model.id);
+
+    let embedding = match model_id {
+        Some(model_id) => embeddings
+            .into_iter()
+            .find(|embedding| embedding.model_id == model_id)
+            .ok_or_else(|| format!("图片在模型 {} 下没有特征向量，请先提取", model_id))?,
+        None => {
+            let position = embeddings
+                .iter()
+                .position(|embedding| Some(&embedding.model_id) == active_model_id.as_ref())
+                .unwrap_or(0);
+            embeddings
+                .into_iter()
+                .nth(position)
+                .ok_or("图片没有特征向量，请先在识别时提取")?
+        }
+    };
+    let query = normalize(&embedding.vector).ok_or("图片的特征向量全为零，无法比较")?;
+
+    ensure_index(&embedding.model_id).await?;
+    let indexes = INDEXES.read().map_err(|_| "无法获取检索索引锁")?;
+    let index = indexes
+        .get(&embedding.model_id)
+        .ok_or("检索索引已被移除，请重试")?;
+    if query.len() != index.dim {
+        return Err(format!(
+            "特征向量维度为 {}，与模型的特征维度 {} 不一致，请重新提取",
+            query.len(),
+            index.dim
+        ));
+    }
+
+    // 多取一个，去掉图片自身
+    let matches = index
+        .index
+        .search(&query, k + 1)
+        .into_iter()
+        .filter(|(id, _)| *id != embedding.image_id)
+        .take(k)
+        .collect();
+    Ok((embedding.model_id, matches))
+}
+
+/// 删除模型的检索索引和保存的特征向量，模型被删除时调用
+pub async fn forget_model(model_id: &str) -> Result<(), String> {
+    if let Ok(mut indexes) = INDEXES.write() {
+        indexes.remove(model_id);
+    }
+    let deleted = EmbeddingRepository::delete_by_model(model_id)
+        .await
+        .map_err(|e| format!("删除特征向量失败: {}", e))?;
+    if deleted > 0 {
+        println!("已删除模型 {} 的 {} 个特征向量", model_id, deleted);
+    }
+    Ok(())
+}
+
+/// 模型的检索索引尚未加载时从数据库构建
+async fn ensure_index(model_id: &str) -> Result<(), String> {
+    if INDEXES
+        .read()
+        .map_err(|_| "无法获取检索索引锁")?
+        .contains_key(model_id)
+    {
+        return Ok(());
+    }
+
+    let embeddings = EmbeddingRepository::find_by_model(model_id)
+        .await
+        .map_err(|e| format!("查询特征向量失败: {}", e))?;
+    // 以最近提取的向量维度为准，模型更新后旧维度的向量被忽略
+    let dim = embeddings
+        .iter()
+        .max_by_key(|embedding| embedding.updated_at)
+        .map(|embedding| embedding.vector.len())
+        .unwrap_or_default();
+    let kind = constants::get_config().similarity_index;
+    let mut index = ModelIndex {
+        dim,
+        upgradable: kind == SimilarityIndexKind::Auto && embeddings.len() < ANN_MIN_VECTORS,
+        index: create_index(kind, dim, embeddings.len()),
+    };
+    for embedding in &embeddings {
+        index.insert(embedding.image_id, &embedding.vector);
+    }
+    println!(
+        "已构建模型 {} 的检索索引: {} 个向量，维度 {}",
+        model_id,
+        index.index.len(),
+        dim
+    );
+
+    INDEXES
+        .write()
+        .map_err(|_| "无法获取检索索引锁")?
+        .entry(model_id.to_string())
+        .or_insert(index);
+    Ok(())
+}
+
+/// 归一化为单位向量，零向量返回 None
+fn normalize(vector: &[f32]) -> Option<Vec<f32>> {
+    let norm = dot(vector, vector).sqrt();
+    (norm > f32::EPSILON).then(|| vector.iter().map(|value| value / norm).collect())
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// 生成超平面用的伪随机数
+struct XorShift(u64);
+
+impl XorShift {
+    fn next_unit(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        // 取高 24 位，范围 (0, 1]
+        ((self.0 >> 40) as f32 + 1.0) / (1u64 << 24) as f32
+    }
+
+    /// Box-Muller 变换得到标准正态分布的随机数，超平面方向因此在各个方向上均匀分布
+    fn next_gaussian(&mut self) -> f32 {
+        let (u1, u2) = (self.next_unit(), self.next_unit());
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f32::consts::PI * u2).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIM: usize = 32;
+
+    fn random_vectors(count: usize) -> Vec<(ObjectId, Vec<f32>)> {
+        let mut rng = XorShift(42);
+        (0..count)
+            .map(|_| {
+                let vector: Vec<f32> = (0..DIM).map(|_| rng.next_gaussian()).collect();
+                (ObjectId::new(), normalize(&vector).unwrap())
+            })
+            .collect()
+    }
+
+    /// 在向量上加少量扰动作为查询
+    fn near(vector: &[f32], seed: u64) -> Vec<f32> {
+        let mut rng = XorShift(seed);
+        let noisy: Vec<f32> = vector
+            .iter()
+            .map(|value| value + 0.05 * rng.next_gaussian())
+            .collect();
+        normalize(&noisy).unwrap()
+    }
+
+    fn build(
+        mut index: Box<dyn VectorIndex>,
+        vectors: &[(ObjectId, Vec<f32>)],
+    ) -> Box<dyn VectorIndex> {
+        for (id, vector) in vectors {
+            index.insert(*id, vector.clone());
+        }
+        index
+    }
+
+    #[test]
+    fn lsh_finds_same_nearest_neighbours_as_brute_force() {
+        let vectors = random_vectors(3000);
+        let brute = build(Box::new(BruteForceIndex::default()), &vectors);
+        let lsh = build(Box::new(LshIndex::new(DIM)), &vectors);
+        assert_eq!(lsh.len(), brute.len());
+
+        for (i, (id, vector)) in vectors.iter().enumerate().step_by(300) {
+            let query = near(vector, i as u64 + 1);
+            let exact = brute.search(&query, 5);
+            let approximate = lsh.search(&query, 5);
+
+            assert_eq!(exact[0].0, *id);
+            assert_eq!(approximate[0].0, *id);
+            assert!((approximate[0].1 - exact[0].1).abs() < 1e-6);
+            // 近似结果的相似度不会高于精确结果
+            for (found, best) in approximate.iter().zip(&exact) {
+                assert!(found.1 <= best.1 + 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn lsh_with_few_candidates_falls_back_to_brute_force() {
+        let vectors = random_vectors(20);
+        let brute = build(Box::new(BruteForceIndex::default()), &vectors);
+        let lsh = build(Box::new(LshIndex::new(DIM)), &vectors);
+
+        let query = near(&vectors[3].1, 7);
+        assert_eq!(lsh.search(&query, 20), brute.search(&query, 20));
+    }
+
+    #[test]
+    fn insert_replaces_existing_vector() {
+        let vectors = random_vectors(50);
+        for index in [
+            create_index(SimilarityIndexKind::BruteForce, DIM, 0),
+            create_index(SimilarityIndexKind::Lsh, DIM, 0),
+        ] {
+            let mut index = build(index, &vectors);
+            let (id, _) = vectors[0];
+            index.insert(id, vectors[1].1.clone());
+            assert_eq!(index.len(), vectors.len());
+
+            let found = index.search(&vectors[0].1, 50);
+            assert!(!found.iter().any(|(_, score)| (*score - 1.0).abs() < 1e-6));
+            let found = index.search(&vectors[1].1, 2);
+            assert!(found.iter().all(|(_, score)| (*score - 1.0).abs() < 1e-5));
+        }
+    }
+
+    #[test]
+    fn dimension_change_drops_loaded_index() {
+        let model_index = |dim: usize| ModelIndex {
+            dim,
+            upgradable: true,
+            index: create_index(SimilarityIndexKind::Auto, dim, 0),
+        };
+        let mut indexes = HashMap::from([
+            ("model".to_string(), model_index(DIM)),
+            ("empty".to_string(), model_index(0)),
+        ]);
+
+        add_to_index(&mut indexes, "model", ObjectId::new(), &[1.0; DIM]);
+        assert_eq!(indexes["model"].index.len(), 1);
+
+        // 模型更新后特征维度变化，索引移除后按新维度重建
+        add_to_index(&mut indexes, "model", ObjectId::new(), &[1.0; DIM * 2]);
+        assert!(!indexes.contains_key("model"));
+        // 建立时还没有向量的索引同样需要重建
+        add_to_index(&mut indexes, "empty", ObjectId::new(), &[1.0; DIM]);
+        assert!(indexes.is_empty());
+    }
+
+    #[test]
+    fn zero_vectors_cannot_be_normalized() {
+        assert!(normalize(&[0.0; DIM]).is_none());
+        let unit = normalize(&[3.0, 4.0]).unwrap();
+        assert!((unit[0] - 0.6).abs() < 1e-6 && (unit[1] - 0.8).abs() < 1e-6);
+    }
+}
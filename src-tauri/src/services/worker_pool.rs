@@ -0,0 +1,424 @@
+use crate::config::constants;
+use crate::models::inference_result::{BackendKind, ModelInfo, ServiceStatus};
+use crate::services::inference::{BackendSpec, InferenceBackend};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+lazy_static! {
+    pub static ref WORKER_POOL: Mutex<WorkerPool> = Mutex::new(WorkerPool::new());
+}
+
+/// 空闲检查的最长间隔
+const REAPER_INTERVAL_MAX: Duration = Duration::from_secs(30);
+
+/// 池中的一个已加载的推理后端
+struct PooledWorker {
+    backend: Arc<dyn InferenceBackend>,
+    spec: BackendSpec,
+    last_used: Instant,
+}
+
+impl PooledWorker {
+    /// 是否有请求正在使用该后端（池之外还持有引用）
+    fn in_use(&self) -> bool {
+        Arc::strong_count(&self.backend) > 1
+    }
+
+    /// 后端是否仍可用于该模型：后端类型、脚本、模型文件未变且未加载失败
+    fn matches(&self, spec: &BackendSpec) -> bool {
+        self.spec == *spec && !matches!(self.backend.status(), ServiceStatus::Failed { .. })
+    }
+
+    /// 集成模型本身不加载模型，不占用进程数上限
+    fn counts_toward_limit(&self) -> bool {
+        self.spec.kind != BackendKind::Ensemble
+    }
+}
+
+/// 按模型ID保存已加载推理后端的工作池，各模型按其声明的后端类型创建
+///
+/// 最多保留 `max_warm_workers` 个后端，超出时淘汰最久未使用的空闲后端，集成模型不计入；
+/// 空闲超过 `worker_idle_timeout_secs` 的后端由后台任务卸载。
+pub struct WorkerPool {
+    workers: HashMap<String, PooledWorker>,
+    /// 参数变化后被替换、但仍有请求在使用的旧后端，请求结束后卸载
+    retiring: Vec<Arc<dyn InferenceBackend>>,
+}
+
+impl WorkerPool {
+    pub fn new() -> Self {
+        Self {
+            workers: HashMap::new(),
+            retiring: Vec::new(),
+        }
+    }
+
+    /// 获取模型已加载且参数未变的推理后端，不存在时返回 None
+    pub fn get(&mut self, model_id: &str, spec: &BackendSpec) -> Option<Arc<dyn InferenceBackend>> {
+        let worker = self.workers.get_mut(model_id)?;
+        if !worker.matches(spec) {
+            return None;
+        }
+        worker.last_used = Instant::now();
+        Some(Arc::clone(&worker.backend))
+    }
+
+    /// 获取模型对应的推理后端，不存在时按 `spec` 创建并开始加载
+    ///
+    /// `spec` 的模型文件应已由调用方校验，池锁内不再计算摘要。
+    /// 返回的布尔值表示是否为新创建的后端。新后端在后台加载模型，调用方按需等待就绪。
+    pub fn acquire(
+        &mut self,
+        app_handle: &AppHandle,
+        model: &ModelInfo,
+        spec: BackendSpec,
+    ) -> Result<(Arc<dyn InferenceBackend>, bool), String> {
+        if let Some(backend) = self.get(&model.id, &spec) {
+            return Ok((backend, false));
+        }
+        // 后端类型、模型文件变化或上次加载失败，重新创建该模型的后端
+        self.retire(&model.id);
+        self.unload_retired();
+
+        if spec.kind != BackendKind::Ensemble {
+            self.evict_for_new_worker(constants::get_config().max_warm_workers);
+        }
+
+        let backend = spec.create(app_handle, model)?;
+        self.workers.insert(
+            model.id.clone(),
+            PooledWorker {
+                backend: Arc::clone(&backend),
+                spec,
+                last_used: Instant::now(),
+            },
+        );
+        Ok((backend, true))
+    }
+
+    /// 查询模型对应进程的状态，未启动时返回 Stopped
+    pub fn status(&self, model_id: &str) -> ServiceStatus {
+        self.workers
+            .get(model_id)
+            .map(|worker| worker.backend.status())
+            .unwrap_or(ServiceStatus::Stopped)
+    }
+
+    /// 当前常驻进程对应的模型ID
+    pub fn warm_models(&self) -> Vec<String> {
+        self.workers.keys().cloned().collect()
+    }
+
+    /// 从池中移除模型对应的后端并在后台卸载
+    pub fn remove(&mut self, model_id: &str) {
+        if let Some(worker) = self.workers.remove(model_id) {
+            println!("卸载模型: {}", model_id);
+            spawn_unload(worker.backend);
+        }
+    }
+
+    /// 从池中移除模型对应的后端，仍有请求在使用时等这些请求结束后再卸载
+    fn retire(&mut self, model_id: &str) {
+        let Some(worker) = self.workers.remove(model_id) else {
+            return;
+        };
+        if worker.in_use() {
+            println!("模型 {} 的旧后端仍在处理请求，请求结束后卸载", model_id);
+            self.retiring.push(worker.backend);
+        } else {
+            println!("卸载模型: {}", model_id);
+            spawn_unload(worker.backend);
+        }
+    }
+
+    /// 卸载已没有请求在使用的旧后端
+    fn unload_retired(&mut self) {
+        let (idle, busy) = self
+            .retiring
+            .drain(..)
+            .partition(|backend| Arc::strong_count(backend) == 1);
+        self.retiring = busy;
+        for backend in idle {
+            spawn_unload(backend);
+        }
+    }
+
+    /// 为新进程腾出位置，淘汰最久未使用的空闲进程
+    fn evict_for_new_worker(&mut self, max_workers: usize) {
+        while self
+            .workers
+            .values()
+            .filter(|worker| worker.counts_toward_limit())
+            .count()
+            >= max_workers.max(1)
+        {
+            let oldest = self
+                .workers
+                .iter()
+                .filter(|(_, worker)| worker.counts_toward_limit() && !worker.in_use())
+                .min_by_key(|(_, worker)| worker.last_used)
+                .map(|(id, _)| id.clone());
+
+            match oldest {
+                Some(model_id) => {
+                    println!("进程数已达上限 {}，淘汰模型进程: {}", max_workers, model_id);
+                    self.remove(&model_id);
+                }
+                None => {
+                    // 所有进程都在处理请求，暂时超出上限，等空闲后再回收
+                    println!("所有模型进程都在使用中，暂时超出上限 {}", max_workers);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// 回收空闲超时的进程和请求已结束的旧后端
+    fn evict_idle(&mut self, idle_timeout: Duration) {
+        self.unload_retired();
+
+        let expired: Vec<String> = self
+            .workers
+            .iter()
+            .filter(|(_, worker)| !worker.in_use() && worker.last_used.elapsed() >= idle_timeout)
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        for model_id in expired {
+            println!("模型进程空闲超时: {}", model_id);
+            self.remove(&model_id);
+        }
+    }
+
+    /// 取出所有进程，用于应用退出
+    fn drain(&mut self) -> Vec<Arc<dyn InferenceBackend>> {
+        let mut backends: Vec<_> = self.retiring.drain(..).collect();
+        backends.extend(self.workers.drain().map(|(_, worker)| worker.backend));
+        backends
+    }
+}
+
+impl Default for WorkerPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 后台卸载推理后端，不阻塞调用方
+fn spawn_unload(backend: Arc<dyn InferenceBackend>) {
+    tokio::spawn(async move {
+        backend.unload().await;
+    });
+}
+
+/// 定期回收空闲超时的进程，应在应用启动时运行一次
+pub async fn run_idle_reaper() {
+    let idle_timeout = Duration::from_secs(constants::get_config().worker_idle_timeout_secs);
+    let interval = (idle_timeout / 2).clamp(Duration::from_secs(1), REAPER_INTERVAL_MAX);
+
+    loop {
+        tokio::time::sleep(interval).await;
+        match WORKER_POOL.lock() {
+            Ok(mut pool) => pool.evict_idle(idle_timeout),
+            Err(_) => println!("无法获取模型进程池锁"),
+        }
+    }
+}
+
+/// 关闭池中所有进程并回收
+pub async fn shutdown_worker_pool_async() {
+    let backends = match WORKER_POOL.lock() {
+        Ok(mut pool) => pool.drain(),
+        Err(poisoned) => poisoned.into_inner().drain(),
+    };
+    if backends.is_empty() {
+        return;
+    }
+
+    println!("正在卸载 {} 个模型", backends.len());
+    let unloads = backends.iter().map(|backend| backend.unload());
+    futures::future::join_all(unloads).await;
+}
+
+/// 关闭池中所有进程，应用退出时在同步上下文中调用
+pub fn shutdown_worker_pool() {
+    // 在独立线程中阻塞等待，避免在运行时线程内嵌套 block_on
+    let result = std::thread::spawn(|| {
+        tauri::async_runtime::block_on(shutdown_worker_pool_async());
+    })
+    .join();
+
+    if result.is_err() {
+        println!("关闭模型进程时出错");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::inference_result::WorkerProtocol;
+    use crate::services::labels::LabelSet;
+    use crate::services::mock::MockBackend;
+
+    fn spec(kind: BackendKind, model_path: &str) -> BackendSpec {
+        BackendSpec {
+            kind,
+            model_path: model_path.to_string(),
+            script_path: None,
+            protocol: WorkerProtocol::default(),
+            python_executable: None,
+            endpoint: None,
+            labels_path: None,
+        }
+    }
+
+    fn backend() -> Arc<dyn InferenceBackend> {
+        let labels =
+            Arc::new(LabelSet::from_names(vec!["a".to_string(), "b".to_string()]).unwrap());
+        Arc::new(MockBackend::new(
+            "模拟".to_string(),
+            "mock".to_string(),
+            labels,
+        ))
+    }
+
+    /// 放入一个 `idle` 之前最后使用的后端
+    fn insert(
+        pool: &mut WorkerPool,
+        model_id: &str,
+        kind: BackendKind,
+        idle: Duration,
+    ) -> Arc<dyn InferenceBackend> {
+        let backend = backend();
+        pool.workers.insert(
+            model_id.to_string(),
+            PooledWorker {
+                backend: Arc::clone(&backend),
+                spec: spec(kind, model_id),
+                last_used: Instant::now() - idle,
+            },
+        );
+        backend
+    }
+
+    fn is_unloaded(backend: &Arc<dyn InferenceBackend>) -> bool {
+        matches!(backend.status(), ServiceStatus::Stopped)
+    }
+
+    /// 让后台卸载任务执行完
+    async fn settle() {
+        for _ in 0..8 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn least_recently_used_idle_worker_is_evicted() {
+        let mut pool = WorkerPool::new();
+        insert(
+            &mut pool,
+            "oldest",
+            BackendKind::Mock,
+            Duration::from_secs(30),
+        );
+        insert(
+            &mut pool,
+            "recent",
+            BackendKind::Mock,
+            Duration::from_secs(10),
+        );
+        insert(
+            &mut pool,
+            "ensemble",
+            BackendKind::Ensemble,
+            Duration::from_secs(60),
+        );
+
+        // 集成模型不计入上限，也不会被淘汰
+        pool.evict_for_new_worker(2);
+        let mut warm = pool.warm_models();
+        warm.sort();
+        assert_eq!(warm, vec!["ensemble", "recent"]);
+    }
+
+    #[tokio::test]
+    async fn workers_in_use_are_not_evicted() {
+        let mut pool = WorkerPool::new();
+        let _in_use = insert(
+            &mut pool,
+            "busy",
+            BackendKind::Mock,
+            Duration::from_secs(30),
+        );
+        insert(
+            &mut pool,
+            "idle",
+            BackendKind::Mock,
+            Duration::from_secs(10),
+        );
+
+        pool.evict_for_new_worker(1);
+        assert_eq!(pool.warm_models(), vec!["busy"]);
+
+        // 所有进程都在使用时暂时超出上限
+        pool.evict_for_new_worker(1);
+        assert_eq!(pool.warm_models(), vec!["busy"]);
+    }
+
+    #[tokio::test]
+    async fn idle_workers_are_unloaded_after_timeout() {
+        let mut pool = WorkerPool::new();
+        let expired = insert(
+            &mut pool,
+            "expired",
+            BackendKind::Mock,
+            Duration::from_secs(120),
+        );
+        let _busy = insert(
+            &mut pool,
+            "busy",
+            BackendKind::Mock,
+            Duration::from_secs(120),
+        );
+        insert(
+            &mut pool,
+            "fresh",
+            BackendKind::Mock,
+            Duration::from_secs(5),
+        );
+
+        // 只留下池中的引用，模拟没有请求在使用
+        drop(expired);
+        pool.evict_idle(Duration::from_secs(60));
+
+        let mut warm = pool.warm_models();
+        warm.sort();
+        assert_eq!(warm, vec!["busy", "fresh"]);
+    }
+
+    #[tokio::test]
+    async fn replaced_worker_is_unloaded_after_its_requests_finish() {
+        let mut pool = WorkerPool::new();
+        let in_flight = insert(&mut pool, "model", BackendKind::Mock, Duration::ZERO);
+
+        // 模型文件变化时换用新后端，正在处理请求的旧后端暂不卸载
+        pool.retire("model");
+        settle().await;
+        assert!(pool.warm_models().is_empty());
+        assert_eq!(pool.retiring.len(), 1);
+        assert!(!is_unloaded(&in_flight));
+
+        pool.evict_idle(Duration::from_secs(60));
+        settle().await;
+        assert_eq!(pool.retiring.len(), 1);
+        assert!(!is_unloaded(&in_flight));
+
+        // 请求结束后由下一次回收卸载
+        drop(in_flight);
+        pool.evict_idle(Duration::from_secs(60));
+        assert!(pool.retiring.is_empty());
+    }
+}
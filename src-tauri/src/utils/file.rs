@@ -1,4 +1,6 @@
 use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io;
 use std::path::Path;
 
 /// 从文件名中提取扩展名
@@ -34,3 +36,11 @@ pub fn calculate_file_hash(data: &[u8]) -> String {
     let hash = hasher.finalize();
     format!("{:x}", hash)
 }
+
+/// 分块计算文件的SHA256哈希值，适用于模型权重等大文件
+pub fn calculate_path_hash(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
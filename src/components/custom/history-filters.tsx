@@ -23,6 +23,7 @@ export function HistoryFilters({ onFilterChange }: HistoryFiltersProps) {
   const [searchTerm, setSearchTerm] = useState("");
   const [statusFilter, setStatusFilter] = useState("all");
   const [modelFilter, setModelFilter] = useState("all");
+  const [labelFilter, setLabelFilter] = useState("");
   const [sortBy, setSortBy] = useState<"newest" | "oldest" | "confidence">("newest");
   const [availableModels, setAvailableModels] = useState<ModelsState>({
     models: [],
@@ -71,12 +72,13 @@ export function HistoryFilters({ onFilterChange }: HistoryFiltersProps) {
     if (searchTerm) filters.searchTerm = searchTerm;
     if (statusFilter !== "all") filters.status = statusFilter;
     if (modelFilter !== "all") filters.model = modelFilter;
+    if (labelFilter.trim()) filters.label = labelFilter.trim();
 
     // 只在不是默认排序时添加
     if (sortBy !== "newest") filters.sortBy = sortBy;
 
     onFilterChange(filters);
-  }, [searchTerm, statusFilter, modelFilter, sortBy, onFilterChange]);
+  }, [searchTerm, statusFilter, modelFilter, labelFilter, sortBy, onFilterChange]);
 
   // 当筛选条件变化时应用筛选
   useEffect(() => {
@@ -89,7 +91,7 @@ export function HistoryFilters({ onFilterChange }: HistoryFiltersProps) {
       applyFilters();
     }, 300);
     return () => clearTimeout(timer);
-  }, [searchTerm, applyFilters]);
+  }, [searchTerm, labelFilter, applyFilters]);
 
   // 重置所有筛选条件
   const resetFilters = useCallback(() => {
@@ -100,6 +102,7 @@ export function HistoryFilters({ onFilterChange }: HistoryFiltersProps) {
       setSearchTerm("");
       setStatusFilter("all");
       setModelFilter("all");
+      setLabelFilter("");
       setSortBy("newest");
     }, 0);
   }, [onFilterChange]);
@@ -170,6 +173,13 @@ export function HistoryFilters({ onFilterChange }: HistoryFiltersProps) {
           </SelectContent>
         </Select>
 
+        <Input
+          placeholder='包含类别...'
+          className='w-[140px]'
+          value={labelFilter}
+          onChange={(e) => setLabelFilter(e.target.value)}
+        />
+
         <Select value={sortBy} onValueChange={(value: any) => setSortBy(value)}>
           <SelectTrigger className='w-[120px]'>
             <SelectValue placeholder='最新优先' />
@@ -30,6 +30,15 @@ export function HistoryStats({ stats }: HistoryStatsProps) {
       processingRate: safePercent(stats.processing, stats.total),
     };
   }, [stats]);
+
+  // 记录数最多的10个类别
+  const topLabels = useMemo(
+    () =>
+      Object.entries(stats.labelDistribution || {})
+        .sort((a, b) => b[1] - a[1])
+        .slice(0, 10),
+    [stats]
+  );
   return (
     <div className='space-y-8'>
       <div className='grid gap-6 md:grid-cols-4'>
@@ -78,6 +87,23 @@ export function HistoryStats({ stats }: HistoryStatsProps) {
         </Card>
       </div>
 
+      {topLabels.length > 0 && (
+        <Card className='bg-background shadow-sm'>
+          <CardHeader>
+            <CardTitle>类别分布</CardTitle>
+            <CardDescription>包含各类别的记录数，一条记录可以包含多个类别</CardDescription>
+          </CardHeader>
+          <CardContent className='space-y-2'>
+            {topLabels.map(([label, count]) => (
+              <div key={label} className='flex items-center justify-between text-sm'>
+                <span>{label}</span>
+                <span className='text-muted-foreground'>{count}</span>
+              </div>
+            ))}
+          </CardContent>
+        </Card>
+      )}
+
       <Card className='bg-background shadow-sm'>
         <CardHeader>
           <CardTitle>统计分析</CardTitle>
@@ -2,7 +2,26 @@
 
 import { useState, useEffect, useCallback, useRef } from "react";
 import { invoke } from "@tauri-apps/api/core";
-import { RecognitionRecord, PaginationParams, FilterParams, HistoryStats } from "@/lib/types";
+import {
+  RecognitionRecord,
+  PaginationParams,
+  FilterParams,
+  HistoryStats,
+  RustModelResult,
+  SimilarImage,
+} from "@/lib/types";
+
+// 结果中包含的类别：多标签结果为阳性类别，检测结果为各目标的类别，分类结果为预测类别
+function resultLabels(result: RustModelResult | null): string[] {
+  if (!result) return [];
+  if (result.positive_labels?.length) {
+    return result.positive_labels.map((item) => item.label);
+  }
+  if (result.detections?.length) {
+    return Array.from(new Set(result.detections.map((item) => item.label)));
+  }
+  return result.prediction ? [result.prediction] : [];
+}
 
 export function useHistory() {
   const [records, setRecords] = useState<RecognitionRecord[]>([]);
@@ -26,10 +45,12 @@ export function useHistory() {
   });
   const pendingRequestRef = useRef(false);
 
-  // 获取历史记录总数
-  const fetchTotalCount = useCallback(async (limit: number) => {
+  // 获取历史记录总数，按类别筛选时只统计包含该类别的记录
+  const fetchTotalCount = useCallback(async (limit: number, label?: string) => {
     try {
-      const count = await invoke<number>("get_history_count");
+      const count = label
+        ? await invoke<number>("count_history_by_label", { label })
+        : await invoke<number>("get_history_count");
       setTotal(count);
       setTotalPages(Math.ceil(count / limit));
       return count;
@@ -63,7 +84,13 @@ export function useHistory() {
         // 根据筛选条件选择不同的API调用
         let historyData;
 
-        if (filterParams.status && filterParams.status !== "all") {
+        if (filterParams.label) {
+          // 按结果中包含的类别筛选
+          historyData = await invoke<any[]>("get_history_by_label", {
+            label: filterParams.label,
+            limit: paginationParams.limit,
+          });
+        } else if (filterParams.status && filterParams.status !== "all") {
           // 按状态筛选
           historyData = await invoke<any[]>("get_history_by_status", {
             status: filterParams.status,
@@ -95,12 +122,14 @@ export function useHistory() {
             imageId: history.image_id, // 直接是字符串
             imageUrl: image?.image_url || null, // 直接使用后端生成的URL
             model: history.model_name,
+            task: history.task || "classification",
             result: history.result,
             confidence: history.confidence || null,
             status: history.status,
             originalFileName: image?.original_file_name,
             fileSize: image?.file_size,
             fileFormat: image?.format,
+            groupId: history.group_id || null,
           };
         });
 
@@ -155,7 +184,7 @@ export function useHistory() {
             JSON.stringify(filterParams) ||
           shouldUpdateStats
         ) {
-          await fetchTotalCount(paginationParams.limit);
+          await fetchTotalCount(paginationParams.limit, filterParams.label);
         }
 
         // 更新最后一次请求参数引用
@@ -194,6 +223,7 @@ export function useHistory() {
         model: item.history.model_name,
         status: item.history.status.toLowerCase(),
         confidence: item.history.confidence || 0,
+        labels: resultLabels(item.history.result),
       }));
 
       // 计算统计数据
@@ -213,6 +243,12 @@ export function useHistory() {
           acc[item.model] = (acc[item.model] || 0) + 1;
           return acc;
         }, {} as Record<string, number>),
+        labelDistribution: histories.reduce((acc, item) => {
+          item.labels.forEach((label) => {
+            acc[label] = (acc[label] || 0) + 1;
+          });
+          return acc;
+        }, {} as Record<string, number>),
       };
 
       setStats(statsData);
@@ -279,7 +315,7 @@ export function useHistory() {
 
         // 刷新数据 - 使用参数直接传递，避免依赖状态变量
         await fetchHistory(pagination, filters, false);
-        await fetchTotalCount(pagination.limit);
+        await fetchTotalCount(pagination.limit, filters.label);
 
         // 如果有部分失败，显示警告
         if (!success) {
@@ -299,6 +335,16 @@ export function useHistory() {
     [pagination, filters, fetchHistory, fetchTotalCount]
   );
 
+  // 查找与图片相似的图片，需要识别时已提取特征向量
+  const findSimilarImages = useCallback(async (imageId: string, k?: number) => {
+    return await invoke<SimilarImage[]>("find_similar_images", { imageId, k });
+  }, []);
+
+  // 查询同一次多模型对比保存的一组记录
+  const getHistoryGroup = useCallback(async (groupId: string) => {
+    return await invoke<any[]>("get_history_group", { groupId });
+  }, []);
+
   // 明确的刷新数据函数，用于手动触发
   const refreshData = useCallback(() => {
     return fetchHistory(pagination, filters, true);
@@ -318,5 +364,7 @@ export function useHistory() {
     updateFilters,
     deleteRecords,
     refreshData,
+    findSimilarImages,
+    getHistoryGroup,
   };
 }
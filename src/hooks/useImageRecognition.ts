@@ -1,6 +1,7 @@
 import { useState, useRef, useCallback } from "react";
-import { invoke } from "@tauri-apps/api/core";
-import { ModelResult, RustModelResult } from "@/lib/types";
+import { convertFileSrc, invoke } from "@tauri-apps/api/core";
+import { appDataDir, join } from "@tauri-apps/api/path";
+import { ModelComparison, ModelResult, RecognitionOptions, RustModelResult } from "@/lib/types";
 
 // 辅助函数
 function isErrorResult(result: any): result is { error: string } {
@@ -22,8 +23,17 @@ interface UseImageRecognitionReturn {
   result: ModelResult | null;
   originalResult: RustModelResult | null;
   uploadImage: (file: File) => Promise<void>;
-  processImage: () => Promise<void>;
+  processImage: (
+    options?: RecognitionOptions,
+    explain?: boolean,
+    embed?: boolean
+  ) => Promise<void>;
   saveHistory: () => Promise<void>;
+  compareModels: (
+    modelIds: string[],
+    options?: RecognitionOptions,
+    saveHistory?: boolean
+  ) => Promise<ModelComparison | null>;
   error: string | null;
   historyStatus: HistoryStatus;
   resultSaved: boolean;
@@ -88,7 +98,7 @@ export function useImageRecognition(): UseImageRecognitionReturn {
   /**
    * 步骤2: 处理图像 - 使用已上传的图像
    */
-  const processImage = useCallback(async (): Promise<void> => {
+  const processImage = useCallback(async (options?: RecognitionOptions, explain?: boolean, embed?: boolean): Promise<void> => {
     // 确保已上传图片
     if (!uploadedImage) {
       setError("请先上传图片");
@@ -107,6 +117,9 @@ export function useImageRecognition(): UseImageRecognitionReturn {
       // 调用处理API，使用已上传的图片路径
       const rustResult = await invoke<RustModelResult>("process_image", {
         imagePath: uploadedImage.filePath,
+        options,
+        explain,
+        embed,
       });
 
       // 保存原始结果用于历史记录
@@ -118,21 +131,41 @@ export function useImageRecognition(): UseImageRecognitionReturn {
         setError(errorMsg);
         setResult({ error: errorMsg });
       } else {
-        // 将 class_probabilities 对象转换为排序后的数组格式
-        const matches = Object.entries(rustResult.class_probabilities || {})
-          .map(([label, confidence]) => ({
-            label,
-            confidence: confidence as number,
-          }))
-          .sort((a, b) => b.confidence - a.confidence);
+        // 优先使用带类别ID的 top_classes，旧版脚本只有 class_probabilities 对象
+        const matches = rustResult.top_classes?.length
+          ? rustResult.top_classes.map((item) => ({
+              label: item.label,
+              confidence: item.probability,
+            }))
+          : Object.entries(rustResult.class_probabilities || {})
+              .map(([label, confidence]) => ({
+                label,
+                confidence: confidence as number,
+              }))
+              .sort((a, b) => b.confidence - a.confidence);
+
+        // 热力图保存在应用数据目录下，转换为 asset 地址后显示
+        const heatmapUrl = rustResult.heatmap_path
+          ? convertFileSrc(await join(await appDataDir(), rustResult.heatmap_path))
+          : undefined;
 
         setResult({
           matches,
-          topPrediction: {
-            label: rustResult.prediction,
-            confidence: rustResult.confidence,
-          },
+          // 检测或多标签结果没有目标或阳性类别时 prediction 为空
+          topPrediction: rustResult.prediction
+            ? {
+                label: rustResult.prediction,
+                confidence: rustResult.confidence,
+              }
+            : undefined,
           modelType: rustResult.model_type,
+          uncertain: rustResult.uncertain,
+          detections: rustResult.detections,
+          positiveLabels: rustResult.positive_labels?.map((item) => ({
+            label: item.label,
+            confidence: item.probability,
+          })),
+          heatmapUrl,
         });
       }
     } catch (err: any) {
@@ -206,6 +239,38 @@ export function useImageRecognition(): UseImageRecognitionReturn {
     }
   }, [uploadedImage, originalResult, resultSaved]);
 
+  /**
+   * 用多个模型识别已上传的图像并对比结果，不改变当前活跃模型
+   */
+  const compareModels = useCallback(async (
+    modelIds: string[],
+    options?: RecognitionOptions,
+    saveHistory?: boolean
+  ): Promise<ModelComparison | null> => {
+    if (!uploadedImage) {
+      setError("请先上传图片");
+      return null;
+    }
+
+    setIsProcessing(true);
+    setError(null);
+
+    try {
+      return await invoke<ModelComparison>("compare_models", {
+        imagePath: uploadedImage.filePath,
+        modelIds,
+        options,
+        saveHistory,
+      });
+    } catch (err) {
+      console.error("多模型对比失败:", err);
+      setError(`多模型对比失败: ${err}`);
+      return null;
+    } finally {
+      setIsProcessing(false);
+    }
+  }, [uploadedImage]);
+
   return {
     isUploading,
     isProcessing,
@@ -215,6 +280,7 @@ export function useImageRecognition(): UseImageRecognitionReturn {
     uploadImage,
     processImage,
     saveHistory,
+    compareModels,
     error,
     historyStatus,
     resultSaved,
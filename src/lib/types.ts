@@ -9,12 +9,200 @@ export interface RustModelResult {
 
 // 完善RustModelResult接口，确保与后端返回值匹配
 export interface RustModelResult {
+  // 旧版结果没有该字段，按分类结果处理
+  task?: ModelTask;
+  // 检测结果中为得分最高的目标，没有目标时为空字符串
   prediction: string;
   confidence: number;
   class_probabilities: Record<string, number>;
   model_type?: string;
+  // 模型带有类别文件时提供
+  class_id?: string;
+  top_classes?: ClassScore[];
+  // 最高置信度低于 unknown_threshold
+  uncertain?: boolean;
+  // 识别时使用的选项，随结果保存到历史记录
+  options?: RecognitionOptions;
+  // 检测到的目标，按得分从高到低排列，只有检测结果有
+  detections?: Detection[];
+  // 得分达到类别阈值的类别，只有多标签结果有
+  positive_labels?: ClassScore[];
+  // 热力图路径，相对于应用数据目录，只有请求 explain 且模型支持时有
+  heatmap_path?: string;
+  // 模型倒数第二层的特征向量，只有请求 embed 且模型支持时有
+  embedding?: number[];
   error?: string;
 }
+
+export type ModelTask = "classification" | "detection" | "multi_label";
+
+// 检测到的一个目标
+export interface Detection {
+  class_index?: number;
+  // 模型带有类别文件时提供
+  class_id?: string;
+  label: string;
+  score: number;
+  box: BoundingBox;
+}
+
+// 目标的外接矩形，坐标为相对图片宽高的比例（0~1），原点在左上角
+export interface BoundingBox {
+  x_min: number;
+  y_min: number;
+  x_max: number;
+  y_max: number;
+}
+
+// process_image 的识别选项，未设置的项不生效
+export interface RecognitionOptions {
+  // 最多返回的类别数
+  top_k?: number | null;
+  // 候选类别的最低置信度，置信度最高的类别总是保留
+  min_confidence?: number | null;
+  // 最高置信度低于该值时结果标记为不确定
+  unknown_threshold?: number | null;
+}
+
+// 识别结果中的候选类别，label 为当前语言的名称
+export interface ClassScore {
+  index: number;
+  id: string;
+  label: string;
+  probability: number;
+}
+
+// find_similar_images 返回的相似图片
+export interface SimilarImage {
+  image_id: string;
+  // 提取特征向量的模型ID
+  model_id: string;
+  // 余弦相似度，-1~1
+  score: number;
+  // 图片记录已被删除时为空
+  image: {
+    id: string;
+    original_file_name: string | null;
+    image_url: string | null;
+    file_size: number | null;
+    format: string | null;
+  } | null;
+}
+
+// compare_models 返回的多模型对比结果
+export interface ModelComparison {
+  image_path: string;
+  // 顺序与请求中的模型一致
+  entries: ModelComparisonEntry[];
+  agreement: ComparisonAgreement;
+  // 保存为历史记录时的组ID
+  group_id: string | null;
+}
+
+// 多模型对比中一个模型的结果，识别失败时只有 error
+export interface ModelComparisonEntry {
+  model_id: string;
+  model_name: string;
+  result: RustModelResult | null;
+  error: string | null;
+  // 识别耗时（毫秒），不含加载模型的时间
+  latency_ms: number | null;
+  // 模型尚未加载时的加载耗时（毫秒）
+  load_ms: number | null;
+  history_id: string | null;
+}
+
+// 各模型主要结果的一致性，识别失败的模型不投票
+export interface ComparisonAgreement {
+  successful: number;
+  unanimous: boolean;
+  consensus: string | null;
+  // 给出得票最多类别的模型占识别成功模型的比例，0~1
+  agreement_ratio: number;
+  // 按票数从高到低排列
+  votes: PredictionVote[];
+}
+
+export interface PredictionVote {
+  label: string;
+  class_id: string | null;
+  model_ids: string[];
+  mean_confidence: number;
+}
+
+// get_label_locales 返回的类别名称语言
+export interface LabelLocales {
+  current: string;
+  available: string[];
+}
+
+// diagnose_models 返回的诊断报告
+export interface DiagnosticsReport {
+  python: PythonEnvironmentReport;
+  models: ModelDiagnosis[];
+  healthy: boolean;
+}
+
+export interface PythonEnvironmentReport {
+  executable: string;
+  // 实际运行的解释器路径，无法启动时为空
+  resolved_path: string | null;
+  // 解释器的环境目录（sys.prefix）
+  prefix: string | null;
+  version: string | null;
+  required_version: string;
+  packages: PackageCheck[];
+  // 是否有模型使用 Python 后端
+  required: boolean;
+  passed: boolean;
+  error: string | null;
+}
+
+// discover_python_interpreters 返回的解释器
+export interface PythonCandidate {
+  path: string;
+  source: "configured" | "managed_venv" | "local_venv" | "conda" | "pyenv" | "py_launcher" | "path";
+  environment: PythonEnvironmentReport;
+  is_current: boolean;
+}
+
+// setup_python_venv 过程中的 python-setup-progress 事件
+export interface PythonSetupEvent {
+  stage: "create_venv" | "install_packages" | "verify" | "finished" | "failed";
+  message: string;
+}
+
+export interface PackageCheck {
+  name: string;
+  required_version: string;
+  installed_version: string | null;
+  passed: boolean;
+  error: string | null;
+}
+
+export type DiagnosticStage = "files" | "environment" | "integrity" | "load" | "inference";
+
+export interface ModelDiagnosis {
+  model_id: string;
+  model_name: string;
+  backend: "python" | "onnx" | "remote" | "mock" | "ensemble";
+  files: FileCheck[];
+  load_ms: number | null;
+  inference_ms: number | null;
+  prediction: string | null;
+  confidence: number | null;
+  passed: boolean;
+  // 第一个失败的步骤
+  failed_stage: DiagnosticStage | null;
+  error: string | null;
+}
+
+export interface FileCheck {
+  kind: "weights" | "script" | "labels";
+  path: string;
+  resolved_path: string | null;
+  exists: boolean;
+}
 // 前端使用的类型
 export interface ModelResult {
   matches?: {
@@ -26,6 +214,17 @@ export interface ModelResult {
     confidence: number;
   };
   modelType?: string;
+  // 结果不确定，topPrediction 只是最可能的类别
+  uncertain?: boolean;
+  // 检测模型的目标及其位置
+  detections?: Detection[];
+  // 多标签模型的阳性类别
+  positiveLabels?: {
+    label: string;
+    confidence: number;
+  }[];
+  // 热力图的 asset 地址，可直接用作 img 的 src
+  heatmapUrl?: string;
   error?: string;
 }
 
@@ -53,6 +252,138 @@ export interface ModelInfo {
   num_classes: number;
   script_path: string;
   is_active: boolean;
+  protocol?: "json" | "legacy";
+  // 推理后端，未声明时 .onnx 模型使用 onnx，其余使用 python
+  backend?: "python" | "onnx" | "remote" | "mock" | "ensemble" | null;
+  // 类别名称文件：.json 为带类别ID和多语言名称的结构化文件，其它扩展名为每行一个名称的纯文本
+  labels_path?: string | null;
+  // 用户导入的模型可以修改和删除
+  source?: "bundled" | "user";
+  weights_sha256?: string | null;
+  script_sha256?: string | null;
+  // 推理脚本同目录下辅助脚本（通信协议、Grad-CAM、特征提取）的合并摘要
+  helpers_sha256?: string | null;
+  // 发布者签名，加载前用配置中受信任的公钥校验
+  signature?: ModelSignature | null;
+  task?: ModelTask;
+  // 集成模型的成员和合并方式，只有集成模型有
+  ensemble?: EnsembleConfig | null;
+}
+
+// 集成模型的定义，成员结果按集成模型的类别文件对齐后合并
+export interface EnsembleConfig {
+  method: "weighted_average" | "majority_vote";
+  members: EnsembleMember[];
+}
+
+export interface EnsembleMember {
+  model_id: string;
+  // 未声明时为 1
+  weight: number;
+}
+
+// 模型发布者对权重和脚本摘要的 ed25519 签名
+export interface ModelSignature {
+  key_id: string;
+  // Base64 编码
+  signature: string;
+}
+
+// 导入模型的参数 (import_model)，文件路径为用户选择的源文件
+export interface ModelImportRequest {
+  name: string;
+  description?: string;
+  model_type?: string;
+  weights_path: string;
+  // Python 后端需要
+  script_path?: string | null;
+  labels_path?: string | null;
+  backend?: "python" | "onnx" | "mock" | null;
+  protocol?: "json" | "legacy";
+  // 检测模型需要使用 Python 后端
+  task?: ModelTask;
+  // 为空时取类别名称文件的行数或模型报告的类别数
+  num_classes?: number | null;
+}
+
+// 模型包中一张示例图片的验证结果
+export interface PackageSampleResult {
+  image: string;
+  expected_prediction: string;
+  expected_confidence: number | null;
+  prediction: string | null;
+  confidence: number | null;
+  passed: boolean;
+  error: string | null;
+}
+
+// 验证或安装模型包的参数 (verify_model_package, install_model_package)
+export interface ModelPackageArgs {
+  packagePath: string;
+  // 没有发布者签名的 Python 模型包会在本机运行其中的脚本，需要用户确认后传 true
+  allowUnsignedScripts?: boolean;
+}
+
+// 模型包验证结果 (verify_model_package)
+export interface ModelPackageReport {
+  model: ModelInfo;
+  // 模型包没有示例时为空
+  samples: PackageSampleResult[];
+  passed: boolean;
+}
+
+// 修改模型信息的参数 (update_model_metadata)，为空的字段保持不变
+export interface ModelMetadataUpdate {
+  name?: string | null;
+  description?: string | null;
+  model_type?: string | null;
+}
+
+// Python推理进程崩溃事件 (python-worker-crashed)
+export interface WorkerCrashEvent {
+  model_id: string | null;
+  script_path: string;
+  model_path: string;
+  reason: string;
+  exit_code: number | null;
+  restart_attempt: number;
+}
+
+// 批量识别任务 (process_batch 的返回值)
+export interface BatchJob {
+  job_id: string;
+  total: number;
+}
+
+// 批量识别中单张图片完成事件 (batch-progress)
+export interface BatchProgressEvent {
+  job_id: string;
+  index: number;
+  total: number;
+  source_path: string;
+  image_id: string | null;
+  file_path: string | null;
+  result: RustModelResult | null;
+  error: string | null;
+}
+
+// 批量识别任务结束事件 (batch-finished)
+export interface BatchFinishedEvent {
+  job_id: string;
+  total: number;
+  succeeded: number;
+  failed: number;
+  cancelled: boolean;
+}
+
+// 后台识别队列中任务结束事件 (recognition-job-finished)
+export interface RecognitionJobEvent {
+  history_id: string;
+  image_id: string;
+  status: "pending" | "processing" | "success" | "failed" | "error";
+  result: RustModelResult | null;
+  error_message: string | null;
+  duration_ms: number | null;
 }
 
 export interface ModelsState {
@@ -72,6 +403,8 @@ export interface RecognitionRecord {
   imageId: string;
   imageUrl: string | null;
   model: string;
+  // 识别结果类型，旧记录为 classification
+  task: ModelTask;
   result: any | null; // 使用any类型匹配后端返回的复杂结构
   confidence: number | null;
   status: "pending" | "processing" | "success" | "failed" | "error";
@@ -79,6 +412,12 @@ export interface RecognitionRecord {
   originalFileName: string;
   fileSize: number;
   fileFormat: string;
+  // 后台队列任务的执行时间
+  startedAt?: Date | null;
+  finishedAt?: Date | null;
+  durationMs?: number | null;
+  // 多模型对比保存的记录共享同一个组ID
+  groupId?: string | null;
 }
 
 // 分页参数类型
@@ -93,6 +432,8 @@ export interface FilterParams {
   status?: string;
   model?: string;
   sortBy?: "newest" | "oldest" | "confidence";
+  // 结果中包含的类别（ID或名称）
+  label?: string;
   startDate?: Date;
   endDate?: Date;
 }
@@ -107,4 +448,6 @@ export interface HistoryStats {
   error: number;
   avgConfidence: number;
   modelDistribution: Record<string, number>;
+  // 包含各类别的记录数，一条多标签或检测记录计入它包含的每个类别
+  labelDistribution: Record<string, number>;
 }
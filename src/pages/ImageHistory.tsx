@@ -124,6 +124,7 @@ export default function ImageHistory() {
       searchTerm: "",
       status: undefined,
       model: undefined,
+      label: undefined,
       sortBy: "newest",
     });
 
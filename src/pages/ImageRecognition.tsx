@@ -13,9 +13,12 @@ import {
   CardTitle,
 } from "@/components/ui/card";
 import { Badge } from "@/components/ui/badge";
+import { Switch } from "@/components/ui/switch";
+import { Label } from "@/components/ui/label";
 
 export function ImageRecognition() {
   const [previewUrl, setPreviewUrl] = useState<string | null>(null);
+  const [explain, setExplain] = useState(false);
   const {
     isUploading,
     isProcessing,
@@ -49,7 +52,7 @@ export function ImageRecognition() {
   // 处理图像识别提交
   const handleSubmit = async (e: React.FormEvent) => {
     e.preventDefault();
-    await processImage();
+    await processImage(undefined, explain);
   };
 
   // 释放预览URL资源并重置状态
@@ -159,7 +162,11 @@ export function ImageRecognition() {
             </div>
           </CardContent>
 
-          <CardFooter>
+          <CardFooter className='flex flex-col gap-3'>
+            <div className='flex w-full items-center gap-2'>
+              <Switch id='explain' checked={explain} onCheckedChange={setExplain} />
+              <Label htmlFor='explain'>生成热力图</Label>
+            </div>
             <Button
               variant='default'
               type='submit'
@@ -204,18 +211,47 @@ export function ImageRecognition() {
               </Alert>
             ) : result?.matches && result.matches.length > 0 ? (
               <div className='space-y-4'>
-                {/* 添加顶部预测显示 */}
-                {result.topPrediction && (
+                {/* 多标签结果列出所有阳性类别 */}
+                {result.positiveLabels && result.positiveLabels.length > 0 ? (
                   <div className='mb-2 rounded-md border border-primary/30 bg-primary/5 p-4'>
-                    <h4 className='mb-2 text-sm font-medium text-muted-foreground'>最佳匹配</h4>
-                    <div className='flex items-center justify-between'>
-                      <span className='text-lg font-semibold text-primary'>
-                        {result.topPrediction.label}
-                      </span>
-                      <span className='rounded-full bg-primary px-2.5 py-0.5 text-xs font-medium text-primary-foreground'>
-                        {(result.topPrediction.confidence * 100).toFixed(1)}%
-                      </span>
+                    <h4 className='mb-2 text-sm font-medium text-muted-foreground'>包含</h4>
+                    <div className='flex flex-wrap gap-2'>
+                      {result.positiveLabels.map((item) => (
+                        <span
+                          key={item.label}
+                          className='rounded-full bg-primary px-2.5 py-0.5 text-xs font-medium text-primary-foreground'>
+                          {item.label} {(item.confidence * 100).toFixed(1)}%
+                        </span>
+                      ))}
+                    </div>
+                  </div>
+                ) : (
+                  result.topPrediction && (
+                    <div className='mb-2 rounded-md border border-primary/30 bg-primary/5 p-4'>
+                      <h4 className='mb-2 text-sm font-medium text-muted-foreground'>
+                        {result.uncertain ? "无法确定，最可能是" : "最佳匹配"}
+                      </h4>
+                      <div className='flex items-center justify-between'>
+                        <span className='text-lg font-semibold text-primary'>
+                          {result.topPrediction.label}
+                        </span>
+                        <span className='rounded-full bg-primary px-2.5 py-0.5 text-xs font-medium text-primary-foreground'>
+                          {(result.topPrediction.confidence * 100).toFixed(1)}%
+                        </span>
+                      </div>
                     </div>
+                  )
+                )}
+
+                {/* 热力图标出对预测结果影响最大的区域 */}
+                {result.heatmapUrl && (
+                  <div>
+                    <h4 className='mb-2 text-sm font-medium text-muted-foreground'>热力图</h4>
+                    <img
+                      src={result.heatmapUrl}
+                      alt='热力图'
+                      className='max-h-48 w-full rounded-md object-contain'
+                    />
                   </div>
                 )}
 